@@ -0,0 +1,11 @@
+#!/bin/sh
+
+# Creates the raw disk image next to the kernel $1, for the ATA driver and its
+# tests, unless it exists, and prints its path.
+disk_image="$(dirname -- "$1")/disk.img"
+if [ ! -f "$disk_image" ]; then
+        dd if=/dev/zero of="$disk_image" bs=512 count=2048 2>/dev/null
+        printf 'KFS TEST DISK' | dd of="$disk_image" bs=512 seek=1 conv=notrunc 2>/dev/null
+fi
+
+printf '%s\n' "$disk_image"
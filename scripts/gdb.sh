@@ -25,8 +25,12 @@ case "$program_path" in
         *) program_path="$root_dir/$program_path" ;;
 esac
 
+disk_image=$("$script_dir/disk.sh" "$program_path")
+
 ARGS="-kernel \"$program_path\""
+ARGS="$ARGS -drive file=\"$disk_image\",format=raw,index=0,media=disk"
 ARGS="$ARGS -device isa-debug-exit,iobase=0xf4,iosize=0x04"
+ARGS="$ARGS -audiodev none,id=speaker -machine pcspk-audiodev=speaker"
 ARGS="$ARGS -S -s"
 ARGS="$ARGS -no-reboot -no-shutdown"
 ARGS="$ARGS -monitor none"
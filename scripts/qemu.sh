@@ -1,7 +1,11 @@
 #!/bin/sh
 
+disk_image=$("$(dirname -- "$0")/disk.sh" "$1")
+
 ARGS="-kernel \"$1\""
+ARGS="$ARGS -drive file=\"$disk_image\",format=raw,index=0,media=disk"
 ARGS="$ARGS -device isa-debug-exit,iobase=0xf4,iosize=0x04"
+ARGS="$ARGS -audiodev none,id=speaker -machine pcspk-audiodev=speaker"
 ARGS="$ARGS -m 4G"
 
 if [ "${CI:-}" = "true" ]; then
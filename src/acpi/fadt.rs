@@ -0,0 +1,248 @@
+//! Fixed ACPI Description Table (FADT), signature `FACP`.
+//!
+//! The FADT locates the fixed power management registers, the DSDT, and
+//! describes the legacy hardware of the platform, the century register of
+//! the RTC included. Fields were appended with each ACPI revision, so those
+//! past the length of the table are absent.
+//!
+//! Reference: ACPI specification 6.5, section 5.2.9
+use bitflags::bitflags;
+
+use super::{AcpiError, GenericAddress, SDT_HEADER_SIZE, Sdt, u16_at, u32_at, u64_at};
+
+bitflags! {
+    /// IA-PC boot architecture flags, from ACPI 2.0.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BootArch: u16 {
+        /// ISA devices, such as the serial ports, are present
+        const LEGACY_DEVICES = 0x0001;
+        /// An 8042 keyboard controller is present
+        const I8042 = 0x0002;
+        /// VGA registers must not be probed
+        const NO_VGA = 0x0004;
+        /// MSIs must not be enabled
+        const NO_MSI = 0x0008;
+        /// PCIe ASPM must not be enabled
+        const NO_ASPM = 0x0010;
+        /// There is no CMOS RTC
+        const NO_CMOS_RTC = 0x0020;
+    }
+}
+
+bitflags! {
+    /// Feature flags of the FADT.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FadtFlags: u32 {
+        /// `wbinvd` flushes the caches correctly
+        const WBINVD = 1 << 0;
+        /// C1 is supported on every CPU
+        const PROC_C1 = 1 << 2;
+        /// The power button is a control method device
+        const POWER_BUTTON = 1 << 4;
+        /// The sleep button is a control method device
+        const SLEEP_BUTTON = 1 << 5;
+        /// The RTC alarm can wake the system from S4
+        const RTC_S4 = 1 << 7;
+        /// The PM timer is 32-bit instead of 24-bit
+        const TIMER_32BIT = 1 << 8;
+        /// The reset register is supported
+        const RESET_REGISTER = 1 << 10;
+        /// The platform has no fixed hardware, registers included
+        const HARDWARE_REDUCED = 1 << 20;
+    }
+}
+
+/// Offsets of the FADT fields, from the start of the table.
+const FIRMWARE_CTRL: usize = 36;
+const DSDT: usize = 40;
+const SCI_INTERRUPT: usize = 46;
+const SMI_COMMAND: usize = 48;
+const ACPI_ENABLE: usize = 52;
+const ACPI_DISABLE: usize = 53;
+const PM1A_EVENT: usize = 56;
+const PM1B_EVENT: usize = 60;
+const PM1A_CONTROL: usize = 64;
+const PM1B_CONTROL: usize = 68;
+const PM_TIMER: usize = 76;
+const PM1_EVENT_LENGTH: usize = 88;
+const CENTURY: usize = 108;
+const BOOT_ARCH: usize = 109;
+const FLAGS: usize = 112;
+const RESET_REGISTER: usize = 116;
+const RESET_VALUE: usize = 128;
+const X_DSDT: usize = 140;
+
+/// Length of the ACPI 1.0 FADT.
+const V1_LENGTH: usize = 116;
+
+/// Fixed ACPI Description Table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fadt
+{
+        pub revision:         u8,
+        /// Physical address of the FACS
+        pub firmware_ctrl:    u32,
+        /// Physical address of the DSDT, the 64-bit one if present
+        pub dsdt:             u64,
+        /// ISA IRQ of the System Control Interrupt
+        pub sci_interrupt:    u16,
+        /// I/O port of the SMI command, 0 if ACPI mode cannot be switched
+        pub smi_command:      u32,
+        /// Values written to `smi_command` to enter and leave ACPI mode
+        pub acpi_enable:      u8,
+        pub acpi_disable:     u8,
+        /// I/O ports of the PM1 event and control blocks, 0 if absent
+        pub pm1a_event:       u32,
+        pub pm1b_event:       u32,
+        pub pm1a_control:     u32,
+        pub pm1b_control:     u32,
+        /// Length of the PM1 event blocks, half status and half enable
+        pub pm1_event_length: u8,
+        /// I/O port of the PM timer, 0 if absent
+        pub pm_timer:         u32,
+        /// CMOS index of the RTC century, if any
+        pub century:          Option<u8>,
+        pub boot_arch:        BootArch,
+        pub flags:            FadtFlags,
+        /// Register resetting the system, and the value to write to it
+        pub reset:            Option<(GenericAddress, u8)>,
+}
+
+impl Fadt
+{
+        /// Parses the FADT from its table.
+        pub fn parse(sdt: &Sdt<'_>) -> Result<Self, AcpiError>
+        {
+                let sdt = sdt.expect(b"FACP")?;
+                let data = sdt.data;
+                let len = data.len() + SDT_HEADER_SIZE;
+
+                if len < V1_LENGTH {
+                        return Err(AcpiError::Truncated);
+                }
+
+                // Offsets are given from the start of the table.
+                let u8_at = |i: usize| data[i - SDT_HEADER_SIZE];
+                let u16_at = |i: usize| u16_at(data, i - SDT_HEADER_SIZE);
+                let u32_at = |i: usize| u32_at(data, i - SDT_HEADER_SIZE);
+                let revision = sdt.header.revision;
+                let flags = FadtFlags::from_bits_retain(u32_at(FLAGS));
+
+                let boot_arch = match revision {
+                        0 | 1 => BootArch::empty(),
+                        _ => BootArch::from_bits_retain(u16_at(BOOT_ARCH)),
+                };
+                let reset = match len > RESET_VALUE && flags.contains(FadtFlags::RESET_REGISTER) {
+                        true => GenericAddress::parse(&data[RESET_REGISTER - SDT_HEADER_SIZE..])
+                                .map(|register| (register, u8_at(RESET_VALUE))),
+                        false => None,
+                };
+                let x_dsdt = match len >= X_DSDT + 8 {
+                        true => u64_at(data, X_DSDT - SDT_HEADER_SIZE),
+                        false => 0,
+                };
+
+                Ok(Self {
+                        revision,
+                        firmware_ctrl: u32_at(FIRMWARE_CTRL),
+                        dsdt: match x_dsdt {
+                                0 => u32_at(DSDT) as u64,
+                                x_dsdt => x_dsdt,
+                        },
+                        sci_interrupt: u16_at(SCI_INTERRUPT),
+                        smi_command: u32_at(SMI_COMMAND),
+                        acpi_enable: u8_at(ACPI_ENABLE),
+                        acpi_disable: u8_at(ACPI_DISABLE),
+                        pm1a_event: u32_at(PM1A_EVENT),
+                        pm1b_event: u32_at(PM1B_EVENT),
+                        pm1a_control: u32_at(PM1A_CONTROL),
+                        pm1b_control: u32_at(PM1B_CONTROL),
+                        pm1_event_length: u8_at(PM1_EVENT_LENGTH),
+                        pm_timer: u32_at(PM_TIMER),
+                        century: Some(u8_at(CENTURY)).filter(|&index| index != 0),
+                        boot_arch,
+                        flags,
+                        reset,
+                })
+        }
+
+        /// Returns whether the 8042 keyboard controller may be present.
+        ///
+        /// ACPI 1.0 firmwares do not say, so they are assumed to have one.
+        pub fn has_8042(&self) -> bool
+        {
+                self.revision < 2 || self.boot_arch.contains(BootArch::I8042)
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+        use crate::acpi::AddressSpace;
+        use crate::acpi::tests::table;
+
+        fn fadt_data(length: usize) -> Vec<u8>
+        {
+                let mut data = vec![0; length - SDT_HEADER_SIZE];
+                let mut put = |offset: usize, bytes: &[u8]| {
+                        data[offset - SDT_HEADER_SIZE..][..bytes.len()].copy_from_slice(bytes);
+                };
+
+                put(DSDT, &0x7fe0_0040u32.to_le_bytes());
+                put(SCI_INTERRUPT, &9u16.to_le_bytes());
+                put(SMI_COMMAND, &0xb2u32.to_le_bytes());
+                put(ACPI_ENABLE, &[0xf1, 0xf0]);
+                put(PM1A_CONTROL, &0x604u32.to_le_bytes());
+                put(PM_TIMER, &0x608u32.to_le_bytes());
+                put(CENTURY, &[0x32]);
+                put(BOOT_ARCH, &BootArch::I8042.bits().to_le_bytes());
+                put(FLAGS, &FadtFlags::RESET_REGISTER.bits().to_le_bytes());
+                if length > RESET_VALUE {
+                        put(RESET_REGISTER, &[1, 8, 0, 1, 0xf9, 0x0c, 0, 0, 0, 0, 0, 0]);
+                        put(RESET_VALUE, &[0x06]);
+                }
+                data
+        }
+
+        #[test]
+        fn parses_acpi_1_fadt()
+        {
+                let bytes = table(b"FACP", 1, &fadt_data(V1_LENGTH));
+                let fadt = Fadt::parse(&Sdt::parse(&bytes).unwrap()).unwrap();
+
+                assert_eq!(fadt.dsdt, 0x7fe0_0040);
+                assert_eq!(fadt.sci_interrupt, 9);
+                assert_eq!(
+                        (fadt.smi_command, fadt.acpi_enable, fadt.acpi_disable),
+                        (0xb2, 0xf1, 0xf0)
+                );
+                assert_eq!(fadt.pm1a_control, 0x604);
+                assert_eq!(fadt.pm_timer, 0x608);
+                assert_eq!(fadt.century, Some(0x32));
+                assert_eq!(fadt.boot_arch, BootArch::empty());
+                assert!(fadt.has_8042());
+                assert_eq!(fadt.reset, None);
+        }
+
+        #[test]
+        fn parses_reset_register()
+        {
+                let bytes = table(b"FACP", 3, &fadt_data(244));
+                let fadt = Fadt::parse(&Sdt::parse(&bytes).unwrap()).unwrap();
+                let (register, value) = fadt.reset.unwrap();
+
+                assert_eq!(register.space, AddressSpace::SystemIo);
+                assert_eq!(register.address, 0xcf9);
+                assert_eq!(value, 0x06);
+                assert_eq!(fadt.boot_arch, BootArch::I8042);
+
+                let bytes = table(b"FACP", 1, &[0; 64]);
+                assert_eq!(
+                        Fadt::parse(&Sdt::parse(&bytes).unwrap()),
+                        Err(AcpiError::Truncated)
+                );
+        }
+}
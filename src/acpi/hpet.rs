@@ -0,0 +1,82 @@
+//! High Precision Event Timer table (HPET), signature `HPET`.
+//!
+//! Reference: IA-PC HPET Specification 1.0a, section 3.2.4
+use super::{AcpiError, GenericAddress, Sdt, u16_at, u32_at};
+
+/// Length of the table contents past the header.
+const DATA_LENGTH: usize = 20;
+
+/// HPET description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hpet
+{
+        pub hardware_revision: u8,
+        /// Number of comparators of the first timer block
+        pub comparators:       u8,
+        /// Whether the main counter is 64-bit
+        pub counter_64bit:     bool,
+        /// Whether the HPET can replace the PIT and RTC interrupts
+        pub legacy_capable:    bool,
+        pub pci_vendor_id:     u16,
+        /// Registers of the timer block
+        pub base:              GenericAddress,
+        /// Number of the HPET, for systems with several
+        pub number:            u8,
+        /// Smallest periodic tick without lost interrupts, in counter ticks
+        pub minimum_tick:      u16,
+}
+
+impl Hpet
+{
+        /// Parses the HPET description from its table.
+        pub fn parse(sdt: &Sdt<'_>) -> Result<Self, AcpiError>
+        {
+                let sdt = sdt.expect(b"HPET")?;
+                let data = sdt.data.get(..DATA_LENGTH).ok_or(AcpiError::Truncated)?;
+                let id = u32_at(data, 0);
+                let base = GenericAddress::parse(&data[4..]).ok_or(AcpiError::Truncated)?;
+
+                Ok(Self {
+                        hardware_revision: id as u8,
+                        comparators: ((id >> 8) & 0x1f) as u8 + 1,
+                        counter_64bit: id & (1 << 13) != 0,
+                        legacy_capable: id & (1 << 15) != 0,
+                        pci_vendor_id: (id >> 16) as u16,
+                        base,
+                        number: data[16],
+                        minimum_tick: u16_at(data, 17),
+                })
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+        use crate::acpi::AddressSpace;
+        use crate::acpi::tests::table;
+
+        #[test]
+        fn parses_timer_block()
+        {
+                let mut data = vec![0x01, 0xa2, 0x86, 0x80];
+                data.extend_from_slice(&[0, 64, 0, 0, 0, 0, 0xd0, 0xfe, 0, 0, 0, 0]);
+                data.extend_from_slice(&[0, 0x80, 0x00, 0]);
+                let bytes = table(b"HPET", 1, &data);
+                let hpet = Hpet::parse(&Sdt::parse(&bytes).unwrap()).unwrap();
+
+                assert_eq!(hpet.comparators, 3);
+                assert!(hpet.counter_64bit);
+                assert!(hpet.legacy_capable);
+                assert_eq!(hpet.pci_vendor_id, 0x8086);
+                assert_eq!(hpet.base.space, AddressSpace::SystemMemory);
+                assert_eq!(hpet.base.address, 0xfed0_0000);
+                assert_eq!(hpet.minimum_tick, 0x80);
+
+                let bytes = table(b"HPET", 1, &data[..12]);
+                assert_eq!(
+                        Hpet::parse(&Sdt::parse(&bytes).unwrap()),
+                        Err(AcpiError::Truncated)
+                );
+        }
+}
@@ -0,0 +1,319 @@
+//! Multiple APIC Description Table (MADT), signature `APIC`.
+//!
+//! The MADT gives the address of the local APICs, then a list of entries,
+//! each starting with its type and length: the local APIC of each CPU, the
+//! IO-APICs with their first GSI, and the overrides of the ISA IRQ routes.
+//!
+//! Reference: ACPI specification 6.5, section 5.2.12
+use bitflags::bitflags;
+
+use super::{AcpiError, Sdt, u16_at, u32_at, u64_at};
+use crate::apic::{InterruptOverride, IsaRoutes};
+
+bitflags! {
+    /// Flags of the MADT.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MadtFlags: u32 {
+        /// The system also has 8259 PICs, to mask in APIC mode
+        const PCAT_COMPAT = 0x01;
+    }
+}
+
+/// Bit of the local APIC flags set when the CPU is enabled.
+const LOCAL_APIC_ENABLED: u32 = 0x01;
+
+/// Bit of the local APIC flags set when the CPU can be enabled at runtime.
+const LOCAL_APIC_ONLINE_CAPABLE: u32 = 0x02;
+
+/// Local APIC of a CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalApic
+{
+        /// ACPI processor UID
+        pub processor_id: u8,
+        pub apic_id:      u8,
+        pub flags:        u32,
+}
+
+impl LocalApic
+{
+        /// Returns whether the CPU is enabled or can be.
+        pub const fn is_usable(&self) -> bool
+        {
+                self.flags & (LOCAL_APIC_ENABLED | LOCAL_APIC_ONLINE_CAPABLE) != 0
+        }
+}
+
+/// IO-APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoApic
+{
+        pub id:       u8,
+        /// Physical address of its registers
+        pub address:  u32,
+        /// GSI of its first input
+        pub gsi_base: u32,
+}
+
+/// Entry of the MADT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MadtEntry
+{
+        LocalApic(LocalApic),
+        IoApic(IoApic),
+        InterruptOverride(InterruptOverride),
+        /// GSI to deliver as an NMI
+        NmiSource
+        {
+                flags: u16,
+                gsi:   u32,
+        },
+        /// Local APIC input, `lint` 0 or 1, wired to the NMI, of the CPU
+        /// with `processor_id`, 0xff for all of them
+        LocalApicNmi
+        {
+                processor_id: u8,
+                flags:        u16,
+                lint:         u8,
+        },
+        /// 64-bit address of the local APICs, replacing the MADT one
+        LocalApicOverride(u64),
+        /// Entry of another type
+        Other(u8),
+}
+
+impl MadtEntry
+{
+        /// Parses the entry of type `kind` from its contents.
+        fn parse(
+                kind: u8,
+                data: &[u8],
+        ) -> Option<Self>
+        {
+                let entry = match kind {
+                        0 if data.len() >= 6 => Self::LocalApic(LocalApic {
+                                processor_id: data[0],
+                                apic_id:      data[1],
+                                flags:        u32_at(data, 2),
+                        }),
+                        1 if data.len() >= 10 => Self::IoApic(IoApic {
+                                id:       data[0],
+                                address:  u32_at(data, 2),
+                                gsi_base: u32_at(data, 6),
+                        }),
+                        2 if data.len() >= 8 => Self::InterruptOverride(InterruptOverride {
+                                source: data[1],
+                                gsi:    u32_at(data, 2),
+                                flags:  u16_at(data, 6),
+                        }),
+                        3 if data.len() >= 6 => Self::NmiSource {
+                                flags: u16_at(data, 0),
+                                gsi:   u32_at(data, 2),
+                        },
+                        4 if data.len() >= 4 => Self::LocalApicNmi {
+                                processor_id: data[0],
+                                flags:        u16_at(data, 1),
+                                lint:         data[3],
+                        },
+                        5 if data.len() >= 10 => Self::LocalApicOverride(u64_at(data, 2)),
+                        0..=5 => return None,
+                        kind => Self::Other(kind),
+                };
+
+                Some(entry)
+        }
+}
+
+/// Multiple APIC Description Table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Madt<'a>
+{
+        /// 32-bit address of the local APICs
+        pub local_apic_address: u32,
+        pub flags:              MadtFlags,
+        entries:                &'a [u8],
+}
+
+impl<'a> Madt<'a>
+{
+        /// Parses the MADT from its table.
+        pub fn parse(sdt: &Sdt<'a>) -> Result<Self, AcpiError>
+        {
+                let sdt = sdt.expect(b"APIC")?;
+                let data = sdt.data;
+
+                if data.len() < 8 {
+                        return Err(AcpiError::Truncated);
+                }
+                Ok(Self {
+                        local_apic_address: u32_at(data, 0),
+                        flags:              MadtFlags::from_bits_retain(u32_at(data, 4)),
+                        entries:            &data[8..],
+                })
+        }
+
+        /// Returns the entries of the MADT.
+        pub fn entries(&self) -> MadtEntries<'a>
+        {
+                MadtEntries {
+                        bytes: self.entries,
+                }
+        }
+
+        /// Returns the address of the local APICs, overrides applied.
+        pub fn local_apic(&self) -> u64
+        {
+                self.entries()
+                        .find_map(|entry| match entry {
+                                MadtEntry::LocalApicOverride(address) => Some(address),
+                                _ => None,
+                        })
+                        .unwrap_or(self.local_apic_address as u64)
+        }
+
+        /// Returns the local APICs of the usable CPUs.
+        pub fn cpus(&self) -> impl Iterator<Item = LocalApic> + 'a
+        {
+                self.entries().filter_map(|entry| match entry {
+                        MadtEntry::LocalApic(apic) if apic.is_usable() => Some(apic),
+                        _ => None,
+                })
+        }
+
+        /// Returns the IO-APICs.
+        pub fn io_apics(&self) -> impl Iterator<Item = IoApic> + 'a
+        {
+                self.entries().filter_map(|entry| match entry {
+                        MadtEntry::IoApic(apic) => Some(apic),
+                        _ => None,
+                })
+        }
+
+        /// Returns the IO-APIC whose inputs include `gsi`.
+        ///
+        /// The MADT does not give the number of inputs, so the IO-APIC with
+        /// the highest GSI base not above `gsi` is taken.
+        pub fn io_apic_for(
+                &self,
+                gsi: u32,
+        ) -> Option<IoApic>
+        {
+                self.io_apics()
+                        .filter(|apic| apic.gsi_base <= gsi)
+                        .max_by_key(|apic| apic.gsi_base)
+        }
+
+        /// Returns the routes of the ISA IRQs, overrides applied.
+        pub fn isa_routes(&self) -> IsaRoutes
+        {
+                IsaRoutes::identity().with_overrides(self.entries().filter_map(
+                        |entry| match entry {
+                                MadtEntry::InterruptOverride(entry) => Some(entry),
+                                _ => None,
+                        },
+                ))
+        }
+}
+
+/// Iterator over the entries of the MADT, stopping at the first malformed
+/// one.
+#[derive(Debug, Clone)]
+pub struct MadtEntries<'a>
+{
+        bytes: &'a [u8],
+}
+
+impl Iterator for MadtEntries<'_>
+{
+        type Item = MadtEntry;
+
+        fn next(&mut self) -> Option<Self::Item>
+        {
+                let &[kind, length, ..] = self.bytes else {
+                        return None;
+                };
+                let entry = self
+                        .bytes
+                        .get(2..length as usize)
+                        .and_then(|data| MadtEntry::parse(kind, data));
+
+                self.bytes = match entry {
+                        Some(_) => &self.bytes[length as usize..],
+                        None => &[],
+                };
+                entry
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+        use crate::acpi::tests::table;
+        use crate::apic::{Polarity, Trigger};
+
+        fn madt_bytes() -> Vec<u8>
+        {
+                let mut data = Vec::new();
+
+                data.extend_from_slice(&0xfee0_0000u32.to_le_bytes());
+                data.extend_from_slice(&1u32.to_le_bytes());
+                // Two CPUs, the second one disabled.
+                data.extend_from_slice(&[0, 8, 0, 0, 1, 0, 0, 0]);
+                data.extend_from_slice(&[0, 8, 1, 1, 0, 0, 0, 0]);
+                // IO-APIC 2 at 0xfec00000, from GSI 0.
+                data.extend_from_slice(&[1, 12, 2, 0, 0, 0, 0xc0, 0xfe, 0, 0, 0, 0]);
+                // IRQ0 on GSI 2, IRQ9 level triggered and active low.
+                data.extend_from_slice(&[2, 10, 0, 0, 2, 0, 0, 0, 0, 0]);
+                data.extend_from_slice(&[2, 10, 0, 9, 9, 0, 0, 0, 0x0f, 0]);
+                // NMI on LINT1 of every CPU.
+                data.extend_from_slice(&[4, 6, 0xff, 0, 0, 1]);
+                table(b"APIC", 1, &data)
+        }
+
+        #[test]
+        fn parses_entries()
+        {
+                let bytes = madt_bytes();
+                let madt = Madt::parse(&Sdt::parse(&bytes).unwrap()).unwrap();
+
+                assert_eq!(madt.flags, MadtFlags::PCAT_COMPAT);
+                assert_eq!(madt.local_apic(), 0xfee0_0000);
+                assert_eq!(madt.cpus().map(|cpu| cpu.apic_id).collect::<Vec<_>>(), [0]);
+                assert_eq!(
+                        madt.io_apic_for(9),
+                        Some(IoApic {
+                                id:       2,
+                                address:  0xfec0_0000,
+                                gsi_base: 0,
+                        })
+                );
+                assert_eq!(
+                        madt.entries().last(),
+                        Some(MadtEntry::LocalApicNmi {
+                                processor_id: 0xff,
+                                flags:        0,
+                                lint:         1,
+                        })
+                );
+
+                let routes = madt.isa_routes();
+                assert_eq!(routes.get(0).gsi, 2);
+                assert_eq!(routes.get(9).trigger, Trigger::Level);
+                assert_eq!(routes.get(9).polarity, Polarity::Low);
+        }
+
+        #[test]
+        fn stops_at_malformed_entries()
+        {
+                let mut bytes = madt_bytes();
+                // The first IO-APIC entry claims to be 4 bytes long.
+                bytes[36 + 8 + 16 + 1] = 4;
+                let bytes = crate::acpi::tests::with_checksum(bytes, 9);
+                let madt = Madt::parse(&Sdt::parse(&bytes).unwrap()).unwrap();
+
+                assert_eq!(madt.entries().count(), 2);
+                assert_eq!(madt.io_apics().next(), None);
+        }
+}
@@ -0,0 +1,112 @@
+//! PCI Express memory mapped configuration table (MCFG), signature `MCFG`.
+//!
+//! Each entry maps the configuration space of a range of buses of a PCI
+//! segment group, 4 KiB per function.
+//!
+//! Reference: PCI Firmware Specification 3.2, section 4.1.2
+use super::{AcpiError, Sdt, u16_at, u64_at};
+
+/// Size in bytes of an allocation entry.
+const ENTRY_SIZE: usize = 16;
+
+/// Configuration space allocation of a range of buses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct McfgEntry
+{
+        /// Physical address of the configuration space of bus 0, even when
+        /// `start_bus` is not 0
+        pub base:      u64,
+        pub segment:   u16,
+        pub start_bus: u8,
+        pub end_bus:   u8,
+}
+
+impl McfgEntry
+{
+        /// Returns the physical address of the configuration space of a
+        /// function, `None` if its bus is out of the range.
+        pub const fn address(
+                &self,
+                bus: u8,
+                device: u8,
+                function: u8,
+        ) -> Option<u64>
+        {
+                if bus < self.start_bus || bus > self.end_bus {
+                        return None;
+                }
+                Some(self.base
+                        + ((bus as u64) << 20
+                                | ((device & 0x1f) as u64) << 15
+                                | ((function & 0x07) as u64) << 12))
+        }
+}
+
+/// PCI Express memory mapped configuration table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mcfg<'a>
+{
+        entries: &'a [u8],
+}
+
+impl<'a> Mcfg<'a>
+{
+        /// Parses the MCFG from its table.
+        pub fn parse(sdt: &Sdt<'a>) -> Result<Self, AcpiError>
+        {
+                let sdt = sdt.expect(b"MCFG")?;
+
+                // 8 reserved bytes precede the entries.
+                Ok(Self {
+                        entries: sdt.data.get(8..).ok_or(AcpiError::Truncated)?,
+                })
+        }
+
+        /// Returns the allocation entries.
+        pub fn entries(&self) -> impl Iterator<Item = McfgEntry> + 'a
+        {
+                self.entries
+                        .chunks_exact(ENTRY_SIZE)
+                        .map(|entry| McfgEntry {
+                                base:      u64_at(entry, 0),
+                                segment:   u16_at(entry, 8),
+                                start_bus: entry[10],
+                                end_bus:   entry[11],
+                        })
+        }
+
+        /// Returns the entry of `bus` in `segment`.
+        pub fn find(
+                &self,
+                segment: u16,
+                bus: u8,
+        ) -> Option<McfgEntry>
+        {
+                self.entries().find(|entry| {
+                        entry.segment == segment && (entry.start_bus..=entry.end_bus).contains(&bus)
+                })
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+        use crate::acpi::tests::table;
+
+        #[test]
+        fn maps_functions()
+        {
+                let mut data = vec![0; 8];
+                data.extend_from_slice(&0xb000_0000u64.to_le_bytes());
+                data.extend_from_slice(&[0, 0, 0, 0xff, 0, 0, 0, 0]);
+                let bytes = table(b"MCFG", 1, &data);
+                let mcfg = Mcfg::parse(&Sdt::parse(&bytes).unwrap()).unwrap();
+                let entry = mcfg.find(0, 3).unwrap();
+
+                assert_eq!(mcfg.entries().count(), 1);
+                assert_eq!(entry.address(0, 0, 0), Some(0xb000_0000));
+                assert_eq!(entry.address(3, 2, 1), Some(0xb031_1000));
+                assert_eq!(mcfg.find(1, 0), None);
+        }
+}
@@ -0,0 +1,535 @@
+//! ACPI tables.
+//!
+//! The firmware describes the hardware in tables, each starting with the
+//! same header and summing to 0 bytewise. The Root System Description
+//! Pointer (RSDP), found by scanning BIOS memory for its signature, points
+//! to the RSDT, or the XSDT with 64-bit addresses since ACPI 2.0, which in
+//! turn lists the physical addresses of the other tables.
+//!
+//! The tables are parsed from byte slices, the kernel mapping the physical
+//! addresses to memory.
+//!
+//! Reference: ACPI specification 6.5, chapter 5.2
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+pub mod mcfg;
+
+use core::str;
+
+use self::fadt::Fadt;
+use self::hpet::Hpet;
+use self::madt::Madt;
+use self::mcfg::Mcfg;
+
+/// Reasons an ACPI table cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError
+{
+        /// No RSDP was found
+        NoRsdp,
+        /// The table does not have the expected signature
+        BadSignature,
+        /// The bytes of the table do not sum to 0
+        BadChecksum,
+        /// The data is shorter than announced by the table
+        Truncated,
+        /// The table is out of the memory the kernel can reach
+        Unreachable,
+}
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+/// Alignment of the RSDP in the scanned regions.
+pub const RSDP_ALIGN: usize = 16;
+
+/// Size in bytes of the ACPI 1.0 RSDP, covered by its checksum.
+const RSDP_V1_SIZE: usize = 20;
+
+/// Size in bytes of the ACPI 2.0 RSDP, covered by its extended checksum.
+const RSDP_V2_SIZE: usize = 36;
+
+/// Size in bytes of the header of the system description tables.
+pub const SDT_HEADER_SIZE: usize = 36;
+
+/// Most tables [`Tables`] lists.
+pub const MAX_TABLES: usize = 32;
+
+pub(crate) fn u16_at(
+        bytes: &[u8],
+        i: usize,
+) -> u16
+{
+        u16::from_le_bytes(bytes[i..i + 2].try_into().unwrap())
+}
+
+pub(crate) fn u32_at(
+        bytes: &[u8],
+        i: usize,
+) -> u32
+{
+        u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap())
+}
+
+pub(crate) fn u64_at(
+        bytes: &[u8],
+        i: usize,
+) -> u64
+{
+        u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap())
+}
+
+/// Returns whether `bytes` sum to 0, as every ACPI structure does.
+pub fn checksum(bytes: &[u8]) -> bool { bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0 }
+
+/// Returns the printable part of an identifier padded with spaces or NULs.
+pub fn name(bytes: &[u8]) -> &str
+{
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+
+        str::from_utf8(&bytes[..len]).unwrap_or("?").trim_end()
+}
+
+/// Root System Description Pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rsdp
+{
+        pub oem_id:       [u8; 6],
+        /// 0 for ACPI 1.0, 2 from ACPI 2.0
+        pub revision:     u8,
+        pub rsdt_address: u32,
+        /// Address of the XSDT, from ACPI 2.0
+        pub xsdt_address: Option<u64>,
+}
+
+impl Rsdp
+{
+        /// Parses the RSDP at the start of `bytes`, checking its checksums.
+        pub fn parse(bytes: &[u8]) -> Result<Self, AcpiError>
+        {
+                let v1 = bytes.get(..RSDP_V1_SIZE).ok_or(AcpiError::Truncated)?;
+
+                if &v1[..8] != RSDP_SIGNATURE {
+                        return Err(AcpiError::BadSignature);
+                }
+                if !checksum(v1) {
+                        return Err(AcpiError::BadChecksum);
+                }
+
+                let revision = v1[15];
+                let xsdt_address = match revision {
+                        0 => None,
+                        _ => {
+                                let v2 = bytes.get(..RSDP_V2_SIZE).ok_or(AcpiError::Truncated)?;
+                                let length = (u32_at(v2, 20) as usize).max(RSDP_V2_SIZE);
+                                let v2 = bytes.get(..length).ok_or(AcpiError::Truncated)?;
+                                if !checksum(v2) {
+                                        return Err(AcpiError::BadChecksum);
+                                }
+                                Some(u64_at(v2, 24)).filter(|&address| address != 0)
+                        }
+                };
+
+                Ok(Self {
+                        oem_id: v1[9..15].try_into().unwrap(),
+                        revision,
+                        rsdt_address: u32_at(v1, 16),
+                        xsdt_address,
+                })
+        }
+
+        /// Scans `region` for a valid RSDP on a 16-byte boundary, and returns
+        /// its offset in `region` along with it.
+        pub fn find(region: &[u8]) -> Option<(usize, Self)>
+        {
+                (0..region.len()).step_by(RSDP_ALIGN).find_map(|offset| {
+                        Self::parse(&region[offset..])
+                                .ok()
+                                .map(|rsdp| (offset, rsdp))
+                })
+        }
+}
+
+/// Header of the system description tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdtHeader
+{
+        pub signature:        [u8; 4],
+        /// Length of the table, header included
+        pub length:           u32,
+        pub revision:         u8,
+        pub oem_id:           [u8; 6],
+        pub oem_table_id:     [u8; 8],
+        pub oem_revision:     u32,
+        pub creator_id:       [u8; 4],
+        pub creator_revision: u32,
+}
+
+impl SdtHeader
+{
+        /// Parses the header at the start of `bytes`, without checking the
+        /// table.
+        pub fn parse(bytes: &[u8]) -> Result<Self, AcpiError>
+        {
+                let bytes = bytes.get(..SDT_HEADER_SIZE).ok_or(AcpiError::Truncated)?;
+
+                Ok(Self {
+                        signature:        bytes[0..4].try_into().unwrap(),
+                        length:           u32_at(bytes, 4),
+                        revision:         bytes[8],
+                        oem_id:           bytes[10..16].try_into().unwrap(),
+                        oem_table_id:     bytes[16..24].try_into().unwrap(),
+                        oem_revision:     u32_at(bytes, 24),
+                        creator_id:       bytes[28..32].try_into().unwrap(),
+                        creator_revision: u32_at(bytes, 32),
+                })
+        }
+
+        /// Returns the signature, such as `APIC` for the MADT.
+        pub fn signature(&self) -> &str { name(&self.signature) }
+}
+
+/// System description table, checked against its length and checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sdt<'a>
+{
+        pub header: SdtHeader,
+        /// Contents of the table following the header
+        pub data:   &'a [u8],
+}
+
+impl<'a> Sdt<'a>
+{
+        /// Parses the table at the start of `bytes`.
+        pub fn parse(bytes: &'a [u8]) -> Result<Self, AcpiError>
+        {
+                let header = SdtHeader::parse(bytes)?;
+                let length = (header.length as usize).max(SDT_HEADER_SIZE);
+                let bytes = bytes.get(..length).ok_or(AcpiError::Truncated)?;
+
+                if !checksum(bytes) {
+                        return Err(AcpiError::BadChecksum);
+                }
+                Ok(Self {
+                        header,
+                        data: &bytes[SDT_HEADER_SIZE..],
+                })
+        }
+
+        /// Returns the table if it has `signature`.
+        pub fn expect(
+                self,
+                signature: &[u8; 4],
+        ) -> Result<Self, AcpiError>
+        {
+                match &self.header.signature == signature {
+                        true => Ok(self),
+                        false => Err(AcpiError::BadSignature),
+                }
+        }
+
+        /// Returns the physical addresses of the tables the RSDT or the XSDT
+        /// lists.
+        pub fn root_entries(&self) -> Result<RootEntries<'a>, AcpiError>
+        {
+                let size = match &self.header.signature {
+                        b"RSDT" => 4,
+                        b"XSDT" => 8,
+                        _ => return Err(AcpiError::BadSignature),
+                };
+
+                Ok(RootEntries {
+                        data: self.data,
+                        size,
+                })
+        }
+}
+
+/// Iterator over the table addresses of the RSDT or the XSDT.
+#[derive(Debug, Clone)]
+pub struct RootEntries<'a>
+{
+        data: &'a [u8],
+        /// Size of an address, 4 in the RSDT and 8 in the XSDT
+        size: usize,
+}
+
+impl Iterator for RootEntries<'_>
+{
+        type Item = u64;
+
+        fn next(&mut self) -> Option<Self::Item>
+        {
+                let entry = self.data.get(..self.size)?;
+
+                self.data = &self.data[self.size..];
+                Some(match self.size {
+                        4 => u32_at(entry, 0) as u64,
+                        _ => u64_at(entry, 0),
+                })
+        }
+}
+
+/// Address space of a [`GenericAddress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace
+{
+        SystemMemory,
+        SystemIo,
+        PciConfig,
+        Other(u8),
+}
+
+impl From<u8> for AddressSpace
+{
+        fn from(value: u8) -> Self
+        {
+                match value {
+                        0 => Self::SystemMemory,
+                        1 => Self::SystemIo,
+                        2 => Self::PciConfig,
+                        value => Self::Other(value),
+                }
+        }
+}
+
+/// Generic Address Structure, locating a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenericAddress
+{
+        pub space:       AddressSpace,
+        pub bit_width:   u8,
+        pub bit_offset:  u8,
+        /// 1 to 4 for byte to quad word accesses, 0 if undefined
+        pub access_size: u8,
+        pub address:     u64,
+}
+
+impl GenericAddress
+{
+        /// Size in bytes of the structure.
+        pub const SIZE: usize = 12;
+
+        /// Parses the structure at the start of `bytes`, `None` if it is too
+        /// short or the address is 0.
+        pub fn parse(bytes: &[u8]) -> Option<Self>
+        {
+                let bytes = bytes.get(..Self::SIZE)?;
+
+                Some(Self {
+                        space:       AddressSpace::from(bytes[0]),
+                        bit_width:   bytes[1],
+                        bit_offset:  bytes[2],
+                        access_size: bytes[3],
+                        address:     u64_at(bytes, 4),
+                })
+                .filter(|address| address.address != 0)
+        }
+}
+
+/// Table listed by the RSDT or the XSDT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableInfo
+{
+        pub address: u64,
+        pub header:  SdtHeader,
+        /// Whether the table is complete and its checksum valid
+        pub valid:   bool,
+}
+
+/// ACPI tables the kernel uses, parsed from the RSDP.
+#[derive(Debug, Clone, Copy)]
+pub struct Tables<'a>
+{
+        pub rsdp: Rsdp,
+        /// RSDT or XSDT
+        pub root: TableInfo,
+        listed:   [Option<TableInfo>; MAX_TABLES],
+        pub madt: Option<Madt<'a>>,
+        pub fadt: Option<Fadt>,
+        pub hpet: Option<Hpet>,
+        pub mcfg: Option<Mcfg<'a>>,
+}
+
+impl<'a> Tables<'a>
+{
+        /// Walks the tables from `rsdp`, `table` returning the bytes of the
+        /// table at a physical address, from its header to at least its
+        /// length.
+        ///
+        /// The XSDT is preferred over the RSDT when `table` can reach it.
+        /// Tables that cannot be reached or fail their checksum are listed
+        /// but not parsed.
+        pub fn parse(
+                rsdp: Rsdp,
+                mut table: impl FnMut(u64) -> Option<&'a [u8]>,
+        ) -> Result<Self, AcpiError>
+        {
+                let (address, bytes) = rsdp
+                        .xsdt_address
+                        .and_then(|address| Some((address, table(address)?)))
+                        .or_else(|| {
+                                Some((rsdp.rsdt_address as u64, table(rsdp.rsdt_address as u64)?))
+                        })
+                        .ok_or(AcpiError::Unreachable)?;
+                let root = Sdt::parse(bytes)?;
+                let mut tables = Self {
+                        rsdp,
+                        root: TableInfo {
+                                address,
+                                header: root.header,
+                                valid: true,
+                        },
+                        listed: [None; MAX_TABLES],
+                        madt: None,
+                        fadt: None,
+                        hpet: None,
+                        mcfg: None,
+                };
+
+                for (slot, address) in tables.listed.iter_mut().zip(root.root_entries()?) {
+                        let Some(bytes) = table(address) else {
+                                continue;
+                        };
+                        let Ok(header) = SdtHeader::parse(bytes) else {
+                                continue;
+                        };
+                        let sdt = Sdt::parse(bytes);
+
+                        *slot = Some(TableInfo {
+                                address,
+                                header,
+                                valid: sdt.is_ok(),
+                        });
+                        let Ok(sdt) = sdt else {
+                                continue;
+                        };
+                        match &header.signature {
+                                b"APIC" => tables.madt = Madt::parse(&sdt).ok(),
+                                b"FACP" => tables.fadt = Fadt::parse(&sdt).ok(),
+                                b"HPET" => tables.hpet = Hpet::parse(&sdt).ok(),
+                                b"MCFG" => tables.mcfg = Mcfg::parse(&sdt).ok(),
+                                _ => {}
+                        }
+                }
+                Ok(tables)
+        }
+
+        /// Returns the tables the RSDT or the XSDT lists.
+        pub fn listed(&self) -> impl Iterator<Item = &TableInfo> { self.listed.iter().flatten() }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+pub(crate) mod tests
+{
+        use super::*;
+
+        /// Returns `bytes` with the checksum byte at `offset` fixed.
+        pub(crate) fn with_checksum(
+                mut bytes: Vec<u8>,
+                offset: usize,
+        ) -> Vec<u8>
+        {
+                bytes[offset] = 0;
+                bytes[offset] =
+                        0u8.wrapping_sub(bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)));
+                bytes
+        }
+
+        /// Returns a table with `signature` and `data`.
+        pub(crate) fn table(
+                signature: &[u8; 4],
+                revision: u8,
+                data: &[u8],
+        ) -> Vec<u8>
+        {
+                let mut bytes = Vec::new();
+
+                bytes.extend_from_slice(signature);
+                bytes.extend_from_slice(&((SDT_HEADER_SIZE + data.len()) as u32).to_le_bytes());
+                bytes.push(revision);
+                bytes.push(0);
+                bytes.extend_from_slice(b"KFSOEM");
+                bytes.extend_from_slice(b"KFSTABLE");
+                bytes.extend_from_slice(&1u32.to_le_bytes());
+                bytes.extend_from_slice(b"KFS ");
+                bytes.extend_from_slice(&1u32.to_le_bytes());
+                bytes.extend_from_slice(data);
+                with_checksum(bytes, 9)
+        }
+
+        fn rsdp(rsdt: u32) -> Vec<u8>
+        {
+                let mut bytes = RSDP_SIGNATURE.to_vec();
+
+                bytes.push(0);
+                bytes.extend_from_slice(b"KFSOEM");
+                bytes.push(0);
+                bytes.extend_from_slice(&rsdt.to_le_bytes());
+                with_checksum(bytes, 8)
+        }
+
+        #[test]
+        fn finds_rsdp_on_16_byte_boundaries()
+        {
+                let mut region = vec![0; 256];
+                region[0x38..0x38 + 20].copy_from_slice(&rsdp(0x1000));
+                assert_eq!(Rsdp::find(&region), None);
+
+                region[0x40..0x40 + 20].copy_from_slice(&rsdp(0x1000));
+                let (offset, found) = Rsdp::find(&region).unwrap();
+                assert_eq!(offset, 0x40);
+                assert_eq!(found.rsdt_address, 0x1000);
+                assert_eq!(found.xsdt_address, None);
+                assert_eq!(name(&found.oem_id), "KFSOEM");
+
+                region[0x50] ^= 1;
+                assert_eq!(Rsdp::parse(&region[0x40..]), Err(AcpiError::BadChecksum));
+        }
+
+        #[test]
+        fn checks_tables()
+        {
+                let bytes = table(b"TEST", 1, &[1, 2, 3]);
+                let sdt = Sdt::parse(&bytes).unwrap();
+
+                assert_eq!(sdt.header.signature(), "TEST");
+                assert_eq!(sdt.data, &[1, 2, 3]);
+                assert_eq!(sdt.expect(b"APIC"), Err(AcpiError::BadSignature));
+                assert_eq!(Sdt::parse(&bytes[..38]), Err(AcpiError::Truncated));
+
+                let mut corrupted = bytes.clone();
+                corrupted[37] = 0;
+                assert_eq!(Sdt::parse(&corrupted), Err(AcpiError::BadChecksum));
+        }
+
+        #[test]
+        fn walks_rsdt()
+        {
+                let hpet = table(b"HPET", 1, &[0; 20]);
+                let mut bad = table(b"SSDT", 1, &[0; 4]);
+                bad[36] = 1;
+                let mut entries = Vec::new();
+                entries.extend_from_slice(&0x2000u32.to_le_bytes());
+                entries.extend_from_slice(&0x3000u32.to_le_bytes());
+                entries.extend_from_slice(&0x4000u32.to_le_bytes());
+                let rsdt = table(b"RSDT", 1, &entries);
+                let rsdp = Rsdp::parse(&rsdp(0x1000)).unwrap();
+
+                let tables = Tables::parse(rsdp, |address| match address {
+                        0x1000 => Some(&rsdt[..]),
+                        0x2000 => Some(&hpet[..]),
+                        0x3000 => Some(&bad[..]),
+                        _ => None,
+                })
+                .unwrap();
+
+                let listed: Vec<_> = tables
+                        .listed()
+                        .map(|t| (t.header.signature(), t.valid))
+                        .collect();
+                assert_eq!(tables.root.header.signature(), "RSDT");
+                assert_eq!(listed, [("HPET", true), ("SSDT", false)]);
+                assert!(tables.madt.is_none());
+        }
+}
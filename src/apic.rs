@@ -0,0 +1,344 @@
+//! Encoding of the APIC registers.
+//!
+//! Each IO-APIC input, or Global System Interrupt (GSI), has a 64-bit
+//! redirection entry giving the vector it raises, how it is delivered and to
+//! which local APIC. The 16 ISA IRQs are wired to GSIs 0 to 15, edge
+//! triggered and active high, unless the ACPI MADT overrides them: the PIT
+//! commonly comes in on GSI 2.
+//!
+//! Reference: Intel 82093AA I/O Advanced Programmable Interrupt Controller
+//! (IOAPIC) datasheet, and the ACPI specification, section 5.2.12.
+use bitflags::bitflags;
+
+/// Number of ISA IRQs.
+pub const ISA_IRQS: usize = 16;
+
+/// Level of an asserted interrupt line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity
+{
+        High,
+        Low,
+}
+
+/// What raises an interrupt on a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger
+{
+        Edge,
+        Level,
+}
+
+/// How an interrupt is delivered to the local APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DeliveryMode
+{
+        Fixed          = 0b000,
+        LowestPriority = 0b001,
+        Smi            = 0b010,
+        Nmi            = 0b100,
+        Init           = 0b101,
+        ExtInt         = 0b111,
+}
+
+bitflags! {
+    /// Bits of a redirection entry besides its vector, delivery mode and
+    /// destination.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EntryFlags: u64 {
+        /// The destination is a logical APIC set instead of an APIC ID
+        const LOGICAL = 1 << 11;
+        /// Read only, the interrupt is waiting to be delivered
+        const PENDING = 1 << 12;
+        /// The line is active low
+        const ACTIVE_LOW = 1 << 13;
+        /// Read only, a level triggered interrupt waits for its EOI
+        const REMOTE_IRR = 1 << 14;
+        /// The line is level triggered
+        const LEVEL = 1 << 15;
+        /// The interrupt is not delivered
+        const MASKED = 1 << 16;
+    }
+}
+
+const VECTOR_MASK: u64 = 0xff;
+const DELIVERY_SHIFT: u32 = 8;
+const DELIVERY_MASK: u64 = 0b111;
+const DESTINATION_SHIFT: u32 = 56;
+
+/// Redirection entry of an IO-APIC input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedirectionEntry
+{
+        pub vector:      u8,
+        pub delivery:    DeliveryMode,
+        pub polarity:    Polarity,
+        pub trigger:     Trigger,
+        pub masked:      bool,
+        /// APIC ID of the local APIC receiving the interrupt
+        pub destination: u8,
+}
+
+impl RedirectionEntry
+{
+        /// Returns a masked entry delivering `vector` to the local APIC
+        /// `destination`, edge triggered and active high.
+        pub const fn new(
+                vector: u8,
+                destination: u8,
+        ) -> Self
+        {
+                Self {
+                        vector,
+                        delivery: DeliveryMode::Fixed,
+                        polarity: Polarity::High,
+                        trigger: Trigger::Edge,
+                        masked: true,
+                        destination,
+                }
+        }
+
+        /// Encodes the entry, with a physical destination.
+        pub fn to_bits(&self) -> u64
+        {
+                let mut flags = EntryFlags::empty();
+
+                flags.set(EntryFlags::ACTIVE_LOW, self.polarity == Polarity::Low);
+                flags.set(EntryFlags::LEVEL, self.trigger == Trigger::Level);
+                flags.set(EntryFlags::MASKED, self.masked);
+                self.vector as u64
+                        | (self.delivery as u64) << DELIVERY_SHIFT
+                        | flags.bits()
+                        | (self.destination as u64) << DESTINATION_SHIFT
+        }
+
+        /// Decodes an entry, or returns `None` if its delivery mode is
+        /// reserved.
+        pub fn from_bits(bits: u64) -> Option<Self>
+        {
+                let flags = EntryFlags::from_bits_truncate(bits);
+                let delivery = match (bits >> DELIVERY_SHIFT) & DELIVERY_MASK {
+                        0b000 => DeliveryMode::Fixed,
+                        0b001 => DeliveryMode::LowestPriority,
+                        0b010 => DeliveryMode::Smi,
+                        0b100 => DeliveryMode::Nmi,
+                        0b101 => DeliveryMode::Init,
+                        0b111 => DeliveryMode::ExtInt,
+                        _ => return None,
+                };
+
+                Some(Self {
+                        vector: (bits & VECTOR_MASK) as u8,
+                        delivery,
+                        polarity: match flags.contains(EntryFlags::ACTIVE_LOW) {
+                                true => Polarity::Low,
+                                false => Polarity::High,
+                        },
+                        trigger: match flags.contains(EntryFlags::LEVEL) {
+                                true => Trigger::Level,
+                                false => Trigger::Edge,
+                        },
+                        masked: flags.contains(EntryFlags::MASKED),
+                        destination: (bits >> DESTINATION_SHIFT) as u8,
+                })
+        }
+}
+
+/// Route of an ISA IRQ to an IO-APIC input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route
+{
+        pub gsi:      u32,
+        pub polarity: Polarity,
+        pub trigger:  Trigger,
+}
+
+/// Interrupt Source Override of the MADT, changing the route of an ISA
+/// IRQ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptOverride
+{
+        /// ISA IRQ
+        pub source: u8,
+        pub gsi:    u32,
+        /// MPS INTI flags: polarity in bits 0-1, trigger mode in bits 2-3
+        pub flags:  u16,
+}
+
+impl InterruptOverride
+{
+        /// Returns the route of the overridden IRQ, flags left to conform to
+        /// the bus taking the ISA defaults.
+        pub const fn route(&self) -> Route
+        {
+                Route {
+                        gsi:      self.gsi,
+                        polarity: match self.flags & 0b11 {
+                                0b11 => Polarity::Low,
+                                _ => Polarity::High,
+                        },
+                        trigger:  match (self.flags >> 2) & 0b11 {
+                                0b11 => Trigger::Level,
+                                _ => Trigger::Edge,
+                        },
+                }
+        }
+}
+
+/// Routes of the ISA IRQs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsaRoutes
+{
+        routes: [Route; ISA_IRQS],
+}
+
+impl IsaRoutes
+{
+        /// Returns the default routes, each IRQ on the GSI of the same
+        /// number.
+        pub const fn identity() -> Self
+        {
+                let mut routes = [Route {
+                        gsi:      0,
+                        polarity: Polarity::High,
+                        trigger:  Trigger::Edge,
+                }; ISA_IRQS];
+                let mut irq = 0;
+
+                while irq < ISA_IRQS {
+                        routes[irq].gsi = irq as u32;
+                        irq += 1;
+                }
+                Self { routes }
+        }
+
+        /// Applies the interrupt source overrides `overrides`, ignoring
+        /// those of non-ISA sources.
+        pub fn with_overrides(
+                mut self,
+                overrides: impl IntoIterator<Item = InterruptOverride>,
+        ) -> Self
+        {
+                for entry in overrides {
+                        if let Some(route) = self.routes.get_mut(entry.source as usize) {
+                                *route = entry.route();
+                        }
+                }
+                self
+        }
+
+        /// Returns the route of ISA IRQ `irq`.
+        ///
+        /// # Panics
+        /// Panics if `irq` is not an ISA IRQ.
+        pub const fn get(
+                &self,
+                irq: u8,
+        ) -> Route
+        {
+                self.routes[irq as usize]
+        }
+
+        /// Returns the ISA IRQ routed to `gsi`, if any.
+        pub fn irq(
+                &self,
+                gsi: u32,
+        ) -> Option<u8>
+        {
+                self.routes
+                        .iter()
+                        .position(|route| route.gsi == gsi)
+                        .map(|irq| irq as u8)
+        }
+}
+
+impl Default for IsaRoutes
+{
+        fn default() -> Self { Self::identity() }
+}
+
+/// Returns the initial count of the local APIC timer firing every
+/// `period_ms` milliseconds, given the ticks counted in `calibration_ms`
+/// milliseconds.
+///
+/// The count saturates at `u32::MAX`, and is at least 1.
+pub const fn timer_initial_count(
+        ticks: u32,
+        calibration_ms: u32,
+        period_ms: u32,
+) -> u32
+{
+        if calibration_ms == 0 {
+                return u32::MAX;
+        }
+
+        let count = ticks as u64 * period_ms as u64 / calibration_ms as u64;
+        match count {
+                0 => 1,
+                count if count > u32::MAX as u64 => u32::MAX,
+                count => count as u32,
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn redirection_entries_round_trip()
+        {
+                let entry = RedirectionEntry {
+                        polarity: Polarity::Low,
+                        trigger: Trigger::Level,
+                        masked: false,
+                        ..RedirectionEntry::new(0x2b, 3)
+                };
+
+                assert_eq!(entry.to_bits(), 0x0300_0000_0000_a02b);
+                assert_eq!(RedirectionEntry::from_bits(entry.to_bits()), Some(entry));
+                assert_eq!(RedirectionEntry::new(0x20, 0).to_bits(), 0x1_0020);
+                assert_eq!(RedirectionEntry::from_bits(0b011 << 8), None);
+        }
+
+        #[test]
+        fn overrides_change_isa_routes()
+        {
+                let overrides = [
+                        InterruptOverride {
+                                source: 0,
+                                gsi:    2,
+                                flags:  0,
+                        },
+                        InterruptOverride {
+                                source: 9,
+                                gsi:    9,
+                                flags:  0b1111,
+                        },
+                        InterruptOverride {
+                                source: 42,
+                                gsi:    42,
+                                flags:  0,
+                        },
+                ];
+                let routes = IsaRoutes::identity().with_overrides(overrides);
+
+                assert_eq!(routes.get(0).gsi, 2);
+                assert_eq!(routes.irq(2), Some(0));
+                assert_eq!(routes.get(9).polarity, Polarity::Low);
+                assert_eq!(routes.get(9).trigger, Trigger::Level);
+                assert_eq!(routes.get(4), IsaRoutes::identity().get(4));
+                assert_eq!(routes.irq(42), None);
+        }
+
+        #[test]
+        fn timer_count_scales_calibration()
+        {
+                assert_eq!(timer_initial_count(62_500, 10, 1), 6250);
+                assert_eq!(timer_initial_count(62_500, 10, 100), 625_000);
+                assert_eq!(timer_initial_count(5, 10, 1), 1);
+                assert_eq!(timer_initial_count(u32::MAX, 1, 10), u32::MAX);
+        }
+}
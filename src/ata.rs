@@ -0,0 +1,254 @@
+//! ATA register encoding and IDENTIFY data.
+//!
+//! ATA drives are addressed in Logical Block Addresses: 28-bit LBA reaches
+//! 128 GiB, 256 sectors per command, and the 48-bit commands of ATA-6 go
+//! further, 65536 sectors per command. The IDENTIFY DEVICE command returns
+//! 256 words describing the drive, strings being stored with the two bytes
+//! of each word swapped.
+//!
+//! Reference: ATA/ATAPI-6 (T13/1410D), and https://wiki.osdev.org/ATA_PIO_Mode
+use bitflags::bitflags;
+
+/// Highest sector count of a 28-bit command, written as 0.
+pub const LBA28_MAX_SECTORS: u32 = 256;
+
+/// Highest sector count of a 48-bit command, written as 0.
+pub const LBA48_MAX_SECTORS: u32 = 65536;
+
+/// First sector 28-bit commands cannot reach.
+pub const LBA28_LIMIT: u64 = 1 << 28;
+
+/// Words of the IDENTIFY data.
+pub const IDENTIFY_WORDS: usize = 256;
+
+bitflags! {
+    /// Bits of the status register.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Status: u8 {
+        /// The last command failed, the error register says why
+        const ERR = 0x01;
+        /// The drive is ready to transfer a sector
+        const DRQ = 0x08;
+        /// The drive fault
+        const DF = 0x20;
+        /// The drive is spun up and ready for commands
+        const DRDY = 0x40;
+        /// The drive is busy, the other bits are meaningless
+        const BSY = 0x80;
+    }
+}
+
+/// Commands of the command register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Command
+{
+        ReadSectors     = 0x20,
+        ReadSectorsExt  = 0x24,
+        WriteSectors    = 0x30,
+        WriteSectorsExt = 0x34,
+        FlushCache      = 0xe7,
+        FlushCacheExt   = 0xea,
+        Identify        = 0xec,
+}
+
+/// Addressing of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addressing
+{
+        Lba28,
+        Lba48,
+}
+
+impl Addressing
+{
+        /// Returns the addressing of a transfer of `count` sectors from
+        /// `lba`, the 28-bit one when it is enough, or `None` if the drive
+        /// cannot address them in one command.
+        pub const fn select(
+                lba: u64,
+                count: u32,
+                lba48: bool,
+        ) -> Option<Self>
+        {
+                let end = lba + count as u64;
+
+                if count == 0 {
+                        None
+                } else if end <= LBA28_LIMIT && count <= LBA28_MAX_SECTORS {
+                        Some(Self::Lba28)
+                } else if lba48 && end <= 1 << 48 && count <= LBA48_MAX_SECTORS {
+                        Some(Self::Lba48)
+                } else {
+                        None
+                }
+        }
+
+        /// Returns the most sectors a command can transfer.
+        pub const fn max_sectors(self) -> u32
+        {
+                match self {
+                        Self::Lba28 => LBA28_MAX_SECTORS,
+                        Self::Lba48 => LBA48_MAX_SECTORS,
+                }
+        }
+
+        pub const fn read_command(self) -> Command
+        {
+                match self {
+                        Self::Lba28 => Command::ReadSectors,
+                        Self::Lba48 => Command::ReadSectorsExt,
+                }
+        }
+
+        pub const fn write_command(self) -> Command
+        {
+                match self {
+                        Self::Lba28 => Command::WriteSectors,
+                        Self::Lba48 => Command::WriteSectorsExt,
+                }
+        }
+
+        pub const fn flush_command(self) -> Command
+        {
+                match self {
+                        Self::Lba28 => Command::FlushCache,
+                        Self::Lba48 => Command::FlushCacheExt,
+                }
+        }
+}
+
+/// Drive described by its IDENTIFY data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identify
+{
+        model:           [u8; 40],
+        serial:          [u8; 20],
+        firmware:        [u8; 8],
+        /// Whether the drive supports LBA, as every ATA drive since ATA-4
+        pub lba:         bool,
+        /// Whether the drive supports the 48-bit commands
+        pub lba48:       bool,
+        /// Sectors reachable by 28-bit commands
+        pub lba28_count: u32,
+        /// Sectors reachable by 48-bit commands, 0 without them
+        pub lba48_count: u64,
+}
+
+/// Copies the string of `words`, swapping the bytes of each word.
+fn ata_string<const N: usize>(words: &[u16]) -> [u8; N]
+{
+        let mut string = [0; N];
+
+        for (bytes, word) in string.chunks_exact_mut(2).zip(words) {
+                bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        string
+}
+
+fn trimmed(bytes: &[u8]) -> &str { core::str::from_utf8(bytes).unwrap_or("?").trim() }
+
+impl Identify
+{
+        /// Decodes the IDENTIFY data.
+        pub fn parse(words: &[u16; IDENTIFY_WORDS]) -> Self
+        {
+                let lba48 = words[83] & (1 << 10) != 0;
+
+                Self {
+                        model: ata_string(&words[27..47]),
+                        serial: ata_string(&words[10..20]),
+                        firmware: ata_string(&words[23..27]),
+                        lba: words[49] & (1 << 9) != 0,
+                        lba48,
+                        lba28_count: words[60] as u32 | (words[61] as u32) << 16,
+                        lba48_count: match lba48 {
+                                true => words[100..104]
+                                        .iter()
+                                        .rev()
+                                        .fold(0, |count, &word| count << 16 | word as u64),
+                                false => 0,
+                        },
+                }
+        }
+
+        pub fn model(&self) -> &str { trimmed(&self.model) }
+
+        pub fn serial(&self) -> &str { trimmed(&self.serial) }
+
+        pub fn firmware(&self) -> &str { trimmed(&self.firmware) }
+
+        /// Returns the number of sectors of the drive.
+        pub const fn sectors(&self) -> u64
+        {
+                match self.lba48 && self.lba48_count != 0 {
+                        true => self.lba48_count,
+                        false => self.lba28_count as u64,
+                }
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        /// Stores `s` in `words` as IDENTIFY strings are.
+        fn put_string(
+                words: &mut [u16],
+                s: &str,
+        )
+        {
+                let mut bytes = s.as_bytes().to_vec();
+                bytes.resize(words.len() * 2, b' ');
+
+                for (word, pair) in words.iter_mut().zip(bytes.chunks_exact(2)) {
+                        *word = u16::from_be_bytes([pair[0], pair[1]]);
+                }
+        }
+
+        #[test]
+        fn parses_identify_data()
+        {
+                let mut words = [0; IDENTIFY_WORDS];
+                put_string(&mut words[27..47], "QEMU HARDDISK");
+                put_string(&mut words[10..20], "QM00001");
+                put_string(&mut words[23..27], "2.5+");
+                words[49] = 1 << 9;
+                words[60] = 0x0800;
+                words[83] = 1 << 10;
+                words[100] = 0x0800;
+                words[101] = 0x0001;
+
+                let identify = Identify::parse(&words);
+                assert_eq!(identify.model(), "QEMU HARDDISK");
+                assert_eq!(identify.serial(), "QM00001");
+                assert_eq!(identify.firmware(), "2.5+");
+                assert!(identify.lba && identify.lba48);
+                assert_eq!(identify.lba28_count, 0x800);
+                assert_eq!(identify.sectors(), 0x1_0800);
+
+                words[83] = 0;
+                assert_eq!(Identify::parse(&words).sectors(), 0x800);
+        }
+
+        #[test]
+        fn selects_addressing()
+        {
+                assert_eq!(Addressing::select(0, 1, false), Some(Addressing::Lba28));
+                assert_eq!(Addressing::select(0, 256, false), Some(Addressing::Lba28));
+                assert_eq!(Addressing::select(0, 257, false), None);
+                assert_eq!(Addressing::select(0, 257, true), Some(Addressing::Lba48));
+                assert_eq!(
+                        Addressing::select(LBA28_LIMIT - 1, 1, false),
+                        Some(Addressing::Lba28)
+                );
+                assert_eq!(
+                        Addressing::select(LBA28_LIMIT - 1, 2, true),
+                        Some(Addressing::Lba48)
+                );
+                assert_eq!(Addressing::select(LBA28_LIMIT, 1, false), None);
+                assert_eq!(Addressing::select(0, 0, true), None);
+        }
+}
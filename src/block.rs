@@ -0,0 +1,189 @@
+//! Block devices.
+//!
+//! Storage is read and written in fixed-size blocks, addressed by their
+//! Logical Block Address (LBA). Drivers implement [`BlockDevice`], so that
+//! the code above them does not depend on the hardware.
+
+/// Size in bytes of a disk sector.
+pub const SECTOR_SIZE: usize = 512;
+
+/// Reasons a block transfer fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError
+{
+        /// The blocks are past the end of the device
+        OutOfRange,
+        /// The buffer is not a whole number of blocks
+        BadBufferSize,
+        /// The device reported an error, with its error register
+        Device(u8),
+        /// The device did not answer in time
+        Timeout,
+        /// The device cannot be written
+        ReadOnly,
+}
+
+/// Device read and written in blocks.
+pub trait BlockDevice
+{
+        /// Returns the size in bytes of a block.
+        fn block_size(&self) -> usize { SECTOR_SIZE }
+
+        /// Returns the number of blocks of the device.
+        fn block_count(&self) -> u64;
+
+        /// Reads the blocks from `lba` into `buf`, whose length is a multiple
+        /// of the block size.
+        fn read_blocks(
+                &mut self,
+                lba: u64,
+                buf: &mut [u8],
+        ) -> Result<(), BlockError>;
+
+        /// Writes `buf`, whose length is a multiple of the block size, to
+        /// the blocks from `lba`.
+        fn write_blocks(
+                &mut self,
+                lba: u64,
+                buf: &[u8],
+        ) -> Result<(), BlockError>;
+
+        /// Waits for the written blocks to reach the medium.
+        fn flush(&mut self) -> Result<(), BlockError> { Ok(()) }
+}
+
+/// Checks that `len` bytes from block `lba` are whole blocks of `device`,
+/// and returns their number.
+pub fn blocks<D: BlockDevice + ?Sized>(
+        device: &D,
+        lba: u64,
+        len: usize,
+) -> Result<u64, BlockError>
+{
+        let size = device.block_size();
+
+        if !len.is_multiple_of(size) {
+                return Err(BlockError::BadBufferSize);
+        }
+
+        let count = (len / size) as u64;
+        match lba.checked_add(count) {
+                Some(end) if end <= device.block_count() => Ok(count),
+                _ => Err(BlockError::OutOfRange),
+        }
+}
+
+/// Block device in memory.
+#[derive(Debug)]
+pub struct RamDisk<'a>
+{
+        data:     &'a mut [u8],
+        readonly: bool,
+}
+
+impl<'a> RamDisk<'a>
+{
+        /// Returns a disk holding `data`, whose trailing partial block is
+        /// ignored.
+        pub fn new(data: &'a mut [u8]) -> Self
+        {
+                Self {
+                        data,
+                        readonly: false,
+                }
+        }
+
+        pub fn set_readonly(
+                &mut self,
+                readonly: bool,
+        )
+        {
+                self.readonly = readonly;
+        }
+
+        fn range(
+                &self,
+                lba: u64,
+                len: usize,
+        ) -> Result<core::ops::Range<usize>, BlockError>
+        {
+                blocks(self, lba, len)?;
+
+                let start = lba as usize * SECTOR_SIZE;
+                Ok(start..start + len)
+        }
+}
+
+impl BlockDevice for RamDisk<'_>
+{
+        fn block_count(&self) -> u64 { (self.data.len() / SECTOR_SIZE) as u64 }
+
+        fn read_blocks(
+                &mut self,
+                lba: u64,
+                buf: &mut [u8],
+        ) -> Result<(), BlockError>
+        {
+                let range = self.range(lba, buf.len())?;
+
+                buf.copy_from_slice(&self.data[range]);
+                Ok(())
+        }
+
+        fn write_blocks(
+                &mut self,
+                lba: u64,
+                buf: &[u8],
+        ) -> Result<(), BlockError>
+        {
+                if self.readonly {
+                        return Err(BlockError::ReadOnly);
+                }
+
+                let range = self.range(lba, buf.len())?;
+                self.data[range].copy_from_slice(buf);
+                Ok(())
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn ram_disk_reads_back_writes()
+        {
+                let mut data = vec![0; SECTOR_SIZE * 4 + 100];
+                let mut disk = RamDisk::new(&mut data);
+                let mut buf = [0; SECTOR_SIZE * 2];
+
+                assert_eq!(disk.block_count(), 4);
+                disk.write_blocks(1, &[0xa5; SECTOR_SIZE * 2]).unwrap();
+                disk.read_blocks(2, &mut buf).unwrap();
+                assert!(buf[..SECTOR_SIZE].iter().all(|&b| b == 0xa5));
+                assert!(buf[SECTOR_SIZE..].iter().all(|&b| b == 0));
+
+                disk.set_readonly(true);
+                assert_eq!(disk.write_blocks(0, &buf), Err(BlockError::ReadOnly));
+        }
+
+        #[test]
+        fn checks_ranges()
+        {
+                let mut data = vec![0; SECTOR_SIZE * 4];
+                let mut disk = RamDisk::new(&mut data);
+
+                assert_eq!(blocks(&disk, 3, SECTOR_SIZE), Ok(1));
+                assert_eq!(
+                        blocks(&disk, 3, SECTOR_SIZE * 2),
+                        Err(BlockError::OutOfRange)
+                );
+                assert_eq!(
+                        blocks(&disk, u64::MAX, SECTOR_SIZE),
+                        Err(BlockError::OutOfRange)
+                );
+                assert_eq!(blocks(&disk, 0, 100), Err(BlockError::BadBufferSize));
+                assert_eq!(disk.read_blocks(4, &mut []), Ok(()));
+        }
+}
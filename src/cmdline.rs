@@ -0,0 +1,40 @@
+//! Kernel command line parsing.
+//!
+//! The command line is a list of words separated by spaces, options being
+//! given as `key=value`:
+//!
+//! ```text
+//! /boot/kfs.bin theme=gruvbox
+//! ```
+
+/// Returns the value of the `key=value` option of `cmdline`, if any.
+///
+/// If the option is given several times, the last one wins.
+pub fn option<'a>(
+        cmdline: &'a str,
+        key: &str,
+) -> Option<&'a str>
+{
+        cmdline.split_ascii_whitespace()
+                .filter_map(|word| word.split_once('='))
+                .filter(|&(k, _)| k == key)
+                .map(|(_, value)| value)
+                .next_back()
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn finds_options()
+        {
+                let cmdline = "/boot/kfs.bin theme=gruvbox quiet  mode=80x50 theme=solarized";
+
+                assert_eq!(option(cmdline, "theme"), Some("solarized"));
+                assert_eq!(option(cmdline, "mode"), Some("80x50"));
+                assert_eq!(option(cmdline, "quiet"), None);
+                assert_eq!(option("", "theme"), None);
+        }
+}
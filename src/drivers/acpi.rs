@@ -0,0 +1,144 @@
+//! ACPI table discovery.
+//!
+//! The RSDP is searched in the first KiB of the Extended BIOS Data Area,
+//! whose segment the BIOS Data Area holds at `0x40e`, then in the BIOS
+//! area from `0xe0000` to `0xfffff`. The tables are then read in place:
+//! without paging, any physical address below 4 GiB can be reached, and
+//! tables above are ignored.
+//!
+//! The tables are parsed once, by [`init`], and the parsed form is kept for
+//! the APIC setup and power management.
+//!
+//! Reference: ACPI specification 6.5, section 5.2.5.1
+
+use core::slice;
+
+use kfs::acpi::{self, AcpiError, Rsdp, SDT_HEADER_SIZE, SdtHeader, Tables};
+use spin::Once;
+
+use crate::println;
+
+/// Address of the EBDA segment in the BIOS Data Area.
+const EBDA_POINTER: usize = 0x40e;
+
+/// Size of the part of the EBDA searched.
+const EBDA_SEARCH_SIZE: usize = 1024;
+
+/// BIOS area searched after the EBDA.
+const BIOS_AREA_START: usize = 0xe_0000;
+const BIOS_AREA_END: usize = 0x10_0000;
+
+static TABLES: Once<Tables<'static>> = Once::new();
+
+/// Returns the `len` bytes at physical address `address`, `None` if they
+/// are not all below 4 GiB.
+fn physical(
+        address: u64,
+        len: usize,
+) -> Option<&'static [u8]>
+{
+        let end = address.checked_add(len as u64)?;
+
+        if address == 0 || end > u32::MAX as u64 + 1 {
+                return None;
+        }
+
+        // SAFETY: Without paging, physical memory is identity mapped, and
+        // firmware tables are never written to.
+        Some(unsafe { slice::from_raw_parts(address as usize as *const u8, len) })
+}
+
+/// Returns the bytes of the table at `address`, as long as its header says.
+fn table(address: u64) -> Option<&'static [u8]>
+{
+        let header = SdtHeader::parse(physical(address, SDT_HEADER_SIZE)?).ok()?;
+
+        physical(address, (header.length as usize).max(SDT_HEADER_SIZE))
+}
+
+/// Searches the EBDA and the BIOS area for the RSDP.
+fn find_rsdp() -> Option<Rsdp>
+{
+        // SAFETY: The BIOS Data Area is at a fixed address.
+        let segment = unsafe { (EBDA_POINTER as *const u16).read_volatile() };
+        let ebda = (segment as u64) << 4;
+        let bios = physical(BIOS_AREA_START as u64, BIOS_AREA_END - BIOS_AREA_START)?;
+
+        physical(ebda, EBDA_SEARCH_SIZE)
+                .and_then(Rsdp::find)
+                .or_else(|| Rsdp::find(bios))
+                .map(|(_, rsdp)| rsdp)
+}
+
+/// Finds and parses the ACPI tables, on the first call only.
+pub(crate) fn init() -> Result<&'static Tables<'static>, AcpiError>
+{
+        TABLES.try_call_once(|| Tables::parse(find_rsdp().ok_or(AcpiError::NoRsdp)?, table))
+}
+
+/// Returns the ACPI tables, if [`init`] found them.
+pub(crate) fn tables() -> Option<&'static Tables<'static>> { TABLES.get() }
+
+/// Prints the tables listed by the RSDT or the XSDT, and what the kernel
+/// uses of them.
+pub(crate) fn print_summary(tables: &Tables)
+{
+        println!(
+                "ACPI {} from {}, {} at {:#010x}",
+                match tables.rsdp.revision {
+                        0 => "1.0",
+                        _ => "2.0+",
+                },
+                acpi::name(&tables.rsdp.oem_id),
+                tables.root.header.signature(),
+                tables.root.address
+        );
+        for table in tables.listed() {
+                println!(
+                        "  {:4} {:#010x} {:5} bytes rev {} {:8}{}",
+                        table.header.signature(),
+                        table.address,
+                        table.header.length,
+                        table.header.revision,
+                        acpi::name(&table.header.oem_table_id),
+                        match table.valid {
+                                true => "",
+                                false => " bad checksum",
+                        }
+                );
+        }
+        if let Some(madt) = tables.madt {
+                println!(
+                        "  {} CPU(s), {} IO-APIC(s), local APIC at {:#x}",
+                        madt.cpus().count(),
+                        madt.io_apics().count(),
+                        madt.local_apic()
+                );
+        }
+        if let Some(hpet) = tables.hpet {
+                println!(
+                        "  HPET at {:#x}, {} comparators",
+                        hpet.base.address, hpet.comparators
+                );
+        }
+}
+
+#[cfg(test)]
+mod tests
+{
+        use super::*;
+
+        #[test_case]
+        fn qemu_tables_are_found()
+        {
+                let tables = init().unwrap();
+                let madt = tables.madt.unwrap();
+
+                assert!(core::ptr::eq(tables, super::tables().unwrap()));
+                assert_eq!(madt.local_apic(), 0xfee0_0000);
+                assert_eq!(madt.io_apic_for(0).unwrap().address, 0xfec0_0000);
+                assert!(madt.cpus().count() >= 1);
+                assert!(tables.fadt.unwrap().pm1a_control != 0);
+                assert!(tables.listed().all(|table| table.valid));
+        }
+}
@@ -0,0 +1,452 @@
+//! Local APIC and IO-APIC.
+//!
+//! The local APIC of each CPU receives its interrupts and has a timer. It is
+//! found through CPUID and the `IA32_APIC_BASE` MSR, which also enables it,
+//! and its registers are mapped at that base, 16-byte aligned.
+//!
+//! The IO-APIC replaces the 8259 PICs, routing each input to a vector of a
+//! local APIC. Its registers are accessed indirectly, through a register
+//! select and a data window. The inputs of the ISA IRQs are given by
+//! [`kfs::apic::IsaRoutes`].
+//!
+//! The local APIC timer counts down at a frequency that depends on the CPU
+//! bus, so [`calibrate_timer`] measures it against the PIT.
+//!
+//! The kernel has no IDT yet: vectors are programmed but nothing handles
+//! them, and interrupts stay disabled.
+//!
+//! Reference: Intel SDM Vol. 3A, chapter 11, and
+//! https://wiki.osdev.org/APIC
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use kfs::apic::{self, DeliveryMode, RedirectionEntry, Route};
+
+use super::pit;
+use crate::instructions::cpu;
+
+/// CPUID leaf 1 EDX bit of the local APIC.
+const CPUID_APIC: u32 = 1 << 9;
+
+/// MSR holding the local APIC base.
+const IA32_APIC_BASE: u32 = 0x1b;
+
+/// Bit of `IA32_APIC_BASE` enabling the local APIC.
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+/// Bits of `IA32_APIC_BASE` holding the base address.
+const APIC_BASE_MASK: u64 = 0xffff_f000;
+
+/// Local APIC registers, as offsets from its base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+enum Register
+{
+        Id           = 0x020,
+        Version      = 0x030,
+        TaskPriority = 0x080,
+        Eoi          = 0x0b0,
+        /// Spurious interrupt vector, and software enable
+        Spurious     = 0x0f0,
+        Error        = 0x280,
+        LvtTimer     = 0x320,
+        LvtLint0     = 0x350,
+        LvtLint1     = 0x360,
+        LvtError     = 0x370,
+        TimerInitial = 0x380,
+        TimerCurrent = 0x390,
+        TimerDivide  = 0x3e0,
+}
+
+/// Bit of the spurious interrupt vector register enabling the local APIC.
+const SOFTWARE_ENABLE: u32 = 1 << 8;
+
+/// Vector of the spurious interrupts.
+pub(crate) const SPURIOUS_VECTOR: u8 = 0xff;
+
+/// Bit of the LVT entries masking the interrupt.
+const LVT_MASKED: u32 = 1 << 16;
+
+/// Bit of the LVT timer entry making the timer periodic.
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+
+/// Timer divide configuration dividing the bus clock by 16.
+const TIMER_DIVIDE_16: u32 = 0b0011;
+
+/// Duration of the timer calibration, in milliseconds.
+const CALIBRATION_MS: u32 = 10;
+
+/// Timer ticks counted during the calibration, 0 if the PIT could not time
+/// it.
+static TIMER_TICKS: AtomicU32 = AtomicU32::new(0);
+
+/// Whether [`calibrate_timer`] has run, [`TIMER_TICKS`] being meaningless
+/// before.
+static TIMER_CALIBRATED: AtomicBool = AtomicBool::new(false);
+
+/// Physical address of the first IO-APIC on PCs.
+pub(crate) const IO_APIC_DEFAULT_BASE: usize = 0xfec0_0000;
+
+/// IO-APIC registers, selected through `IOREGSEL`.
+const IO_APIC_VERSION: u32 = 0x01;
+const IO_APIC_REDIRECTION: u32 = 0x10;
+
+/// Offset of the `IOWIN` data window from the IO-APIC base.
+const IO_WINDOW: usize = 0x10;
+
+/// Returns whether the CPU has a local APIC.
+pub(crate) fn is_supported() -> bool { cpu::cpuid(1, 0).edx & CPUID_APIC != 0 }
+
+/// Returns the physical address of the local APIC registers.
+///
+/// # Safety
+/// The CPU must have a local APIC.
+unsafe fn local_base() -> usize { (cpu::rdmsr(IA32_APIC_BASE) & APIC_BASE_MASK) as usize }
+
+/// Reads local APIC register `reg`.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled.
+unsafe fn read(reg: Register) -> u32
+{
+        ptr::read_volatile((local_base() + reg as usize) as *const u32)
+}
+
+/// Writes `value` to local APIC register `reg`.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled, and `value` be valid for the
+/// register.
+unsafe fn write(
+        reg: Register,
+        value: u32,
+)
+{
+        ptr::write_volatile((local_base() + reg as usize) as *mut u32, value);
+}
+
+/// Enables the local APIC, with every local interrupt masked besides
+/// LINT0, which delivers the PICs as ExtINT when `virtual_wire` is set.
+///
+/// # Safety
+/// The CPU must have a local APIC.
+pub(crate) unsafe fn init_local(virtual_wire: bool)
+{
+        cpu::wrmsr(
+                IA32_APIC_BASE,
+                cpu::rdmsr(IA32_APIC_BASE) | APIC_BASE_ENABLE,
+        );
+        write(Register::Spurious, SOFTWARE_ENABLE | SPURIOUS_VECTOR as u32);
+        write(Register::TaskPriority, 0);
+
+        let lint0 = match virtual_wire {
+                true => (DeliveryMode::ExtInt as u32) << 8,
+                false => LVT_MASKED,
+        };
+        write(Register::LvtLint0, lint0);
+        write(Register::LvtLint1, (DeliveryMode::Nmi as u32) << 8);
+        write(Register::LvtError, LVT_MASKED);
+        write(Register::LvtTimer, LVT_MASKED);
+
+        // Writing the error status latches the errors, then clears them.
+        write(Register::Error, 0);
+        write(Register::Error, 0);
+}
+
+/// Returns the APIC ID of the current CPU.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled.
+pub(crate) unsafe fn id() -> u8 { (read(Register::Id) >> 24) as u8 }
+
+/// Returns the version of the local APIC, and its number of LVT entries.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled.
+pub(crate) unsafe fn version() -> (u8, u8)
+{
+        let version = read(Register::Version);
+
+        (version as u8, ((version >> 16) as u8) + 1)
+}
+
+/// Signals the end of the handling of the current interrupt.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled, and be handling an interrupt.
+pub(crate) unsafe fn eoi() { write(Register::Eoi, 0); }
+
+/// Measures the local APIC timer against the PIT, and returns the ticks it
+/// counts per millisecond, the bus clock divided by 16, or 0 if the PIT
+/// cannot time it.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled.
+pub(crate) unsafe fn calibrate_timer() -> u32
+{
+        write(Register::TimerDivide, TIMER_DIVIDE_16);
+        write(Register::LvtTimer, LVT_MASKED);
+        write(Register::TimerInitial, u32::MAX);
+        let timed = pit::wait_ms(CALIBRATION_MS);
+
+        let ticks = match timed {
+                true => u32::MAX - read(Register::TimerCurrent),
+                false => 0,
+        };
+        write(Register::TimerInitial, 0);
+        TIMER_TICKS.store(ticks, Ordering::Relaxed);
+        TIMER_CALIBRATED.store(true, Ordering::Relaxed);
+        ticks / CALIBRATION_MS
+}
+
+/// Starts the local APIC timer, raising `vector` every `period_ms`
+/// milliseconds, and calibrates it first if needed.
+///
+/// Returns `false`, leaving the timer masked, if the calibration failed or
+/// `period_ms` is 0, as the timer would then fire on every tick.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled.
+pub(crate) unsafe fn start_timer(
+        vector: u8,
+        period_ms: u32,
+) -> bool
+{
+        if !TIMER_CALIBRATED.load(Ordering::Relaxed) {
+                calibrate_timer();
+        }
+
+        let ticks = TIMER_TICKS.load(Ordering::Relaxed);
+        if ticks == 0 || period_ms == 0 {
+                stop_timer();
+                return false;
+        }
+        write(Register::TimerDivide, TIMER_DIVIDE_16);
+        write(Register::LvtTimer, LVT_TIMER_PERIODIC | vector as u32);
+        write(
+                Register::TimerInitial,
+                apic::timer_initial_count(ticks, CALIBRATION_MS, period_ms),
+        );
+        true
+}
+
+/// Stops the local APIC timer.
+///
+/// # Safety
+/// The CPU must have a local APIC, enabled.
+pub(crate) unsafe fn stop_timer()
+{
+        write(Register::LvtTimer, LVT_MASKED);
+        write(Register::TimerInitial, 0);
+}
+
+/// IO-APIC, whose inputs are the GSIs from `gsi_base`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IoApic
+{
+        ia_base:     usize,
+        ia_gsi_base: u32,
+}
+
+impl IoApic
+{
+        /// Returns the IO-APIC with its registers at `base`.
+        ///
+        /// # Safety
+        /// An IO-APIC must be mapped at `base`.
+        pub(crate) const unsafe fn new(
+                base: usize,
+                gsi_base: u32,
+        ) -> Self
+        {
+                Self {
+                        ia_base:     base,
+                        ia_gsi_base: gsi_base,
+                }
+        }
+
+        fn read(
+                &self,
+                reg: u32,
+        ) -> u32
+        {
+                // SAFETY: The IO-APIC is mapped at the base, and reading its
+                // registers has no side effect.
+                unsafe {
+                        ptr::write_volatile(self.ia_base as *mut u32, reg);
+                        ptr::read_volatile((self.ia_base + IO_WINDOW) as *const u32)
+                }
+        }
+
+        /// # Safety
+        /// `value` must be valid for register `reg`.
+        unsafe fn write(
+                &self,
+                reg: u32,
+                value: u32,
+        )
+        {
+                ptr::write_volatile(self.ia_base as *mut u32, reg);
+                ptr::write_volatile((self.ia_base + IO_WINDOW) as *mut u32, value);
+        }
+
+        /// Returns the first GSI of the IO-APIC.
+        pub(crate) const fn gsi_base(&self) -> u32 { self.ia_gsi_base }
+
+        /// Returns the number of inputs of the IO-APIC.
+        pub(crate) fn inputs(&self) -> u32 { ((self.read(IO_APIC_VERSION) >> 16) & 0xff) + 1 }
+
+        /// Returns whether `gsi` is an input of the IO-APIC.
+        pub(crate) fn handles(
+                &self,
+                gsi: u32,
+        ) -> bool
+        {
+                gsi.checked_sub(self.ia_gsi_base)
+                        .is_some_and(|input| input < self.inputs())
+        }
+
+        /// Returns the redirection entry of `gsi`, `None` if it is not an
+        /// input of the IO-APIC.
+        pub(crate) fn redirection(
+                &self,
+                gsi: u32,
+        ) -> Option<RedirectionEntry>
+        {
+                if !self.handles(gsi) {
+                        return None;
+                }
+
+                let reg = IO_APIC_REDIRECTION + (gsi - self.ia_gsi_base) * 2;
+                let bits = self.read(reg) as u64 | (self.read(reg + 1) as u64) << 32;
+                RedirectionEntry::from_bits(bits)
+        }
+
+        /// Sets the redirection entry of `gsi`, returning whether it is an
+        /// input of the IO-APIC.
+        ///
+        /// The entry is masked while its halves are written.
+        pub(crate) fn set_redirection(
+                &self,
+                gsi: u32,
+                entry: RedirectionEntry,
+        ) -> bool
+        {
+                if !self.handles(gsi) {
+                        return false;
+                }
+
+                let reg = IO_APIC_REDIRECTION + (gsi - self.ia_gsi_base) * 2;
+                let bits = entry.to_bits();
+                let masked = RedirectionEntry {
+                        masked: true,
+                        ..entry
+                };
+
+                // SAFETY: The entry encodes a valid redirection.
+                unsafe {
+                        self.write(reg, masked.to_bits() as u32);
+                        self.write(reg + 1, (bits >> 32) as u32);
+                        self.write(reg, bits as u32);
+                }
+                true
+        }
+
+        /// Masks every input.
+        pub(crate) fn mask_all(&self)
+        {
+                for input in 0..self.inputs() {
+                        let gsi = self.ia_gsi_base + input;
+                        if let Some(entry) = self.redirection(gsi) {
+                                self.set_redirection(
+                                        gsi,
+                                        RedirectionEntry {
+                                                masked: true,
+                                                ..entry
+                                        },
+                                );
+                        }
+                }
+        }
+
+        /// Routes an ISA IRQ along `route` to `vector` of the local APIC
+        /// `destination`, returning whether the GSI is an input of the
+        /// IO-APIC.
+        pub(crate) fn route(
+                &self,
+                route: Route,
+                vector: u8,
+                destination: u8,
+                masked: bool,
+        ) -> bool
+        {
+                let entry = RedirectionEntry {
+                        polarity: route.polarity,
+                        trigger: route.trigger,
+                        masked,
+                        ..RedirectionEntry::new(vector, destination)
+                };
+
+                self.set_redirection(route.gsi, entry)
+        }
+}
+
+#[cfg(test)]
+mod tests
+{
+        use kfs::apic::{Polarity, Trigger};
+
+        use super::*;
+
+        #[test_case]
+        fn local_apic_is_detected()
+        {
+                assert!(is_supported());
+
+                // SAFETY: QEMU emulates a local APIC.
+                unsafe {
+                        init_local(true);
+                        assert_eq!(id(), 0);
+                        assert!(version().1 >= 4);
+                        assert!(calibrate_timer() > 0);
+                }
+        }
+
+        #[test_case]
+        fn timer_stays_masked_without_a_period()
+        {
+                // SAFETY: QEMU emulates a local APIC.
+                unsafe {
+                        init_local(true);
+                        assert!(!start_timer(0x40, 0));
+                        assert_ne!(read(Register::LvtTimer) & LVT_MASKED, 0);
+
+                        assert!(start_timer(0x40, 10));
+                        assert!(TIMER_CALIBRATED.load(Ordering::Relaxed));
+                        assert_eq!(read(Register::LvtTimer) & LVT_MASKED, 0);
+                        stop_timer();
+                        assert_ne!(read(Register::LvtTimer) & LVT_MASKED, 0);
+                }
+        }
+
+        #[test_case]
+        fn io_apic_entries_round_trip()
+        {
+                // SAFETY: QEMU emulates an IO-APIC at the default base.
+                let io_apic = unsafe { IoApic::new(IO_APIC_DEFAULT_BASE, 0) };
+                let entry = RedirectionEntry {
+                        polarity: Polarity::Low,
+                        trigger: Trigger::Level,
+                        ..RedirectionEntry::new(0x30, 0)
+                };
+
+                assert_eq!(io_apic.inputs(), 24);
+                assert!(io_apic.set_redirection(10, entry));
+                assert_eq!(io_apic.redirection(10), Some(entry));
+                assert!(!io_apic.set_redirection(24, entry));
+
+                io_apic.mask_all();
+                assert!(io_apic.redirection(10).unwrap().masked);
+        }
+}
@@ -0,0 +1,581 @@
+//! ATA PIO disk driver.
+//!
+//! The IDE controller has two channels of up to two drives each, a master
+//! and a slave. A channel has a command block of 8 registers, and a control
+//! block whose alternate status reads like the status register without
+//! acknowledging the interrupt. The legacy ports are used unless the PCI IDE
+//! controller runs a channel in native mode, its BARs then giving the ports.
+//!
+//! Sectors are transferred in PIO, 256 words through the data register for
+//! each, the drive setting DRQ when it is ready for one. The driver only
+//! polls: the kernel has no IDT to handle IRQs 14 and 15, so the drive
+//! interrupts stay disabled with nIEN, and the alternate status is watched
+//! instead. A channel is locked, interrupts masked, for a whole command.
+//!
+//! Reference: https://wiki.osdev.org/ATA_PIO_Mode
+
+use core::hint;
+use kfs::ata::{Addressing, Command, IDENTIFY_WORDS, Identify, Status};
+use kfs::block::{self, BlockDevice, BlockError, SECTOR_SIZE};
+use kfs::pci::{Bar, Match};
+
+use super::pci;
+use crate::instructions::io::{inb, inw, outb, outw};
+use crate::println;
+use crate::sync::IrqSafeMutex;
+
+/// Command block registers, as offsets from its base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+enum Register
+{
+        Data        = 0,
+        /// Error when read, features when written
+        Error       = 1,
+        SectorCount = 2,
+        LbaLow      = 3,
+        LbaMid      = 4,
+        LbaHigh     = 5,
+        /// Drive select, and LBA bits 24 to 27 of 28-bit commands
+        Drive       = 6,
+        /// Status when read, command when written
+        Status      = 7,
+}
+
+/// Bits of the drive register.
+const DRIVE_LBA: u8 = 0x40;
+const DRIVE_SLAVE: u8 = 0x10;
+/// Obsolete bits, set by convention.
+const DRIVE_OBSOLETE: u8 = 0xa0;
+
+/// Bit of the device control register masking the drive interrupt.
+const CONTROL_NIEN: u8 = 0x02;
+
+/// Status read from a channel without drives.
+const FLOATING_BUS: u8 = 0xff;
+
+/// Alternate status reads before giving up on a drive, about a second.
+const TIMEOUT: u32 = 1_000_000;
+
+/// Legacy channels of the IDE controller.
+const LEGACY: [Channel; 2] = [
+        Channel {
+                ch_base:    0x1f0,
+                ch_control: 0x3f6,
+        },
+        Channel {
+                ch_base:    0x170,
+                ch_control: 0x376,
+        },
+];
+
+/// Drives of each channel.
+const DRIVES_PER_CHANNEL: usize = 2;
+
+/// Ports of a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Channel
+{
+        ch_base:    u16,
+        /// Alternate status when read, device control when written
+        ch_control: u16,
+}
+
+/// Channels, locked while a command runs.
+static CHANNELS: [IrqSafeMutex<Channel>; 2] =
+        [IrqSafeMutex::new(LEGACY[0]), IrqSafeMutex::new(LEGACY[1])];
+
+/// Drives found by [`init`], master then slave of each channel.
+static DRIVES: IrqSafeMutex<[Option<Drive>; 4]> = IrqSafeMutex::new([None; 4]);
+
+impl Channel
+{
+        fn read(
+                &self,
+                reg: Register,
+        ) -> u8
+        {
+                // SAFETY: The ports belong to the channel, locked by the
+                // caller.
+                unsafe { inb(self.ch_base + reg as u16) }
+        }
+
+        fn write(
+                &self,
+                reg: Register,
+                value: u8,
+        )
+        {
+                // SAFETY: The ports belong to the channel, locked by the
+                // caller.
+                unsafe { outb(self.ch_base + reg as u16, value) };
+        }
+
+        fn alt_status(&self) -> Status
+        {
+                // SAFETY: Reading the alternate status has no side effect.
+                Status::from_bits_retain(unsafe { inb(self.ch_control) })
+        }
+
+        /// Reads the status register, which acknowledges the interrupt.
+        fn status(&self) -> Status { Status::from_bits_retain(self.read(Register::Status)) }
+
+        /// Masks the drive interrupts through the device control register.
+        fn mask_interrupts(&self)
+        {
+                // SAFETY: Only the interrupt mask changes.
+                unsafe { outb(self.ch_control, CONTROL_NIEN) };
+        }
+
+        /// Waits 400 ns, for the status to reflect a drive select or a
+        /// command.
+        fn delay(&self)
+        {
+                for _ in 0..4 {
+                        self.alt_status();
+                }
+        }
+
+        /// Selects the drive, with `bits` in the drive register.
+        fn select(
+                &self,
+                slave: bool,
+                bits: u8,
+        )
+        {
+                let slave = match slave {
+                        true => DRIVE_SLAVE,
+                        false => 0,
+                };
+
+                self.write(Register::Drive, DRIVE_OBSOLETE | slave | bits);
+                self.delay();
+        }
+
+        /// Waits for the drive to be idle.
+        fn wait_idle(&self) -> Result<Status, BlockError>
+        {
+                for _ in 0..TIMEOUT {
+                        let status = self.alt_status();
+                        if !status.contains(Status::BSY) {
+                                return Ok(status);
+                        }
+                        hint::spin_loop();
+                }
+                Err(BlockError::Timeout)
+        }
+
+        /// Waits for the end of a command or for the next sector, then checks
+        /// the status for errors.
+        fn wait(&self) -> Result<Status, BlockError>
+        {
+                self.wait_idle()?;
+                self.check()
+        }
+
+        /// Reads the status, returning the error register if the command
+        /// failed.
+        fn check(&self) -> Result<Status, BlockError>
+        {
+                let status = self.status();
+
+                match status.intersects(Status::ERR | Status::DF) {
+                        true => Err(BlockError::Device(self.read(Register::Error))),
+                        false => Ok(status),
+                }
+        }
+
+        /// Identifies the drive, `None` if it is absent or not an ATA drive.
+        fn identify(
+                &self,
+                slave: bool,
+        ) -> Option<Identify>
+        {
+                if self.alt_status().bits() == FLOATING_BUS {
+                        return None;
+                }
+
+                self.select(slave, 0);
+                for reg in [
+                        Register::SectorCount,
+                        Register::LbaLow,
+                        Register::LbaMid,
+                        Register::LbaHigh,
+                ] {
+                        self.write(reg, 0);
+                }
+                self.write(Register::Status, Command::Identify as u8);
+                self.delay();
+                if self.status().is_empty() {
+                        return None;
+                }
+                self.wait_idle().ok()?;
+
+                // ATAPI and SATA drives set a signature in the LBA registers.
+                if self.read(Register::LbaMid) != 0 || self.read(Register::LbaHigh) != 0 {
+                        return None;
+                }
+                let status = (0..TIMEOUT)
+                        .map(|_| self.alt_status())
+                        .find(|status| status.intersects(Status::DRQ | Status::ERR))?;
+                if status.contains(Status::ERR) {
+                        return None;
+                }
+
+                let mut words = [0; IDENTIFY_WORDS];
+                for word in words.iter_mut() {
+                        // SAFETY: The drive has its IDENTIFY data ready.
+                        *word = unsafe { inw(self.ch_base + Register::Data as u16) };
+                }
+                self.status();
+                Some(Identify::parse(&words))
+        }
+
+        /// Sends `command` for `count` sectors from `lba`.
+        fn command(
+                &self,
+                slave: bool,
+                lba: u64,
+                count: u32,
+                addressing: Addressing,
+                command: Command,
+        ) -> Result<(), BlockError>
+        {
+                match addressing {
+                        Addressing::Lba28 => {
+                                self.select(slave, DRIVE_LBA | ((lba >> 24) as u8 & 0x0f));
+                                self.wait_idle()?;
+                                self.write(Register::Error, 0);
+                        }
+                        Addressing::Lba48 => {
+                                self.select(slave, DRIVE_LBA);
+                                self.wait_idle()?;
+                                // The high bytes are written first.
+                                self.write(Register::SectorCount, (count >> 8) as u8);
+                                self.write(Register::LbaLow, (lba >> 24) as u8);
+                                self.write(Register::LbaMid, (lba >> 32) as u8);
+                                self.write(Register::LbaHigh, (lba >> 40) as u8);
+                        }
+                }
+
+                // The highest count is written as 0.
+                self.write(Register::SectorCount, count as u8);
+                self.write(Register::LbaLow, lba as u8);
+                self.write(Register::LbaMid, (lba >> 8) as u8);
+                self.write(Register::LbaHigh, (lba >> 16) as u8);
+                self.write(Register::Status, command as u8);
+                self.delay();
+                Ok(())
+        }
+}
+
+/// ATA drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Drive
+{
+        dr_channel:  usize,
+        dr_slave:    bool,
+        dr_identify: Identify,
+}
+
+impl Drive
+{
+        pub(crate) fn identify(&self) -> &Identify { &self.dr_identify }
+
+        /// Returns the name Linux gives the drive, from `hda` to `hdd`.
+        pub(crate) fn name(&self) -> &'static str
+        {
+                ["hda", "hdb", "hdc", "hdd"]
+                        [self.dr_channel * DRIVES_PER_CHANNEL + self.dr_slave as usize]
+        }
+
+        /// Returns the addressing of the commands of the drive.
+        fn addressing(&self) -> Addressing
+        {
+                match self.dr_identify.lba48 {
+                        true => Addressing::Lba48,
+                        false => Addressing::Lba28,
+                }
+        }
+
+        /// Reads `buf` from `lba` with a single command.
+        fn read_command(
+                &self,
+                channel: &Channel,
+                lba: u64,
+                buf: &mut [u8],
+                addressing: Addressing,
+        ) -> Result<(), BlockError>
+        {
+                let count = (buf.len() / SECTOR_SIZE) as u32;
+
+                channel.command(
+                        self.dr_slave,
+                        lba,
+                        count,
+                        addressing,
+                        addressing.read_command(),
+                )?;
+                for sector in buf.chunks_exact_mut(SECTOR_SIZE) {
+                        channel.wait()?;
+                        for bytes in sector.chunks_exact_mut(2) {
+                                // SAFETY: The drive has a sector ready.
+                                let word = unsafe { inw(channel.ch_base + Register::Data as u16) };
+                                bytes.copy_from_slice(&word.to_le_bytes());
+                        }
+                }
+                Ok(())
+        }
+
+        /// Writes `buf` to `lba` with a single command.
+        fn write_command(
+                &self,
+                channel: &Channel,
+                lba: u64,
+                buf: &[u8],
+                addressing: Addressing,
+        ) -> Result<(), BlockError>
+        {
+                let count = (buf.len() / SECTOR_SIZE) as u32;
+
+                channel.command(
+                        self.dr_slave,
+                        lba,
+                        count,
+                        addressing,
+                        addressing.write_command(),
+                )?;
+                for sector in buf.chunks_exact(SECTOR_SIZE) {
+                        channel.wait()?;
+                        for bytes in sector.chunks_exact(2) {
+                                // SAFETY: The drive waits for a sector.
+                                unsafe {
+                                        outw(
+                                                channel.ch_base + Register::Data as u16,
+                                                u16::from_le_bytes([bytes[0], bytes[1]]),
+                                        )
+                                };
+                        }
+                }
+                channel.wait_idle()?;
+                channel.check().map(|_| ())
+        }
+
+        /// Splits a transfer of `len` bytes from `lba` into single commands,
+        /// running `transfer` on each with its LBA and byte range.
+        fn split(
+                &self,
+                lba: u64,
+                len: usize,
+                mut transfer: impl FnMut(
+                        u64,
+                        core::ops::Range<usize>,
+                        Addressing,
+                ) -> Result<(), BlockError>,
+        ) -> Result<(), BlockError>
+        {
+                block::blocks(self, lba, len)?;
+
+                let chunk = self.addressing().max_sectors() as usize * SECTOR_SIZE;
+                for start in (0..len).step_by(chunk) {
+                        let end = (start + chunk).min(len);
+                        let lba = lba + (start / SECTOR_SIZE) as u64;
+                        let count = ((end - start) / SECTOR_SIZE) as u32;
+                        let addressing = Addressing::select(lba, count, self.dr_identify.lba48)
+                                .ok_or(BlockError::OutOfRange)?;
+                        transfer(lba, start..end, addressing)?;
+                }
+                Ok(())
+        }
+}
+
+impl BlockDevice for Drive
+{
+        fn block_count(&self) -> u64 { self.dr_identify.sectors() }
+
+        fn read_blocks(
+                &mut self,
+                lba: u64,
+                buf: &mut [u8],
+        ) -> Result<(), BlockError>
+        {
+                let channel = CHANNELS[self.dr_channel].lock();
+
+                self.split(lba, buf.len(), |lba, range, addressing| {
+                        self.read_command(&channel, lba, &mut buf[range], addressing)
+                })
+        }
+
+        fn write_blocks(
+                &mut self,
+                lba: u64,
+                buf: &[u8],
+        ) -> Result<(), BlockError>
+        {
+                let channel = CHANNELS[self.dr_channel].lock();
+
+                self.split(lba, buf.len(), |lba, range, addressing| {
+                        self.write_command(&channel, lba, &buf[range], addressing)
+                })
+        }
+
+        fn flush(&mut self) -> Result<(), BlockError>
+        {
+                let channel = CHANNELS[self.dr_channel].lock();
+                let addressing = self.addressing();
+
+                channel.command(self.dr_slave, 0, 0, addressing, addressing.flush_command())?;
+                channel.wait_idle()?;
+                channel.check().map(|_| ())
+        }
+}
+
+/// Returns the channels of the IDE controller, from its BARs for those in
+/// native mode.
+fn find_channels() -> [Channel; 2]
+{
+        let mut channels = LEGACY;
+        let Some(ide) = pci::find(&Match::class(0x01, 0x01)) else {
+                return channels;
+        };
+
+        // Bits 0 and 2 of the programming interface put the primary and the
+        // secondary channels in native mode.
+        for (index, channel) in channels.iter_mut().enumerate() {
+                if ide.prog_if & (1 << (index * 2)) == 0 {
+                        continue;
+                }
+                if let (Some(Bar::Io { port: base, .. }), Some(Bar::Io { port: control, .. })) =
+                        (ide.bars[index * 2], ide.bars[index * 2 + 1])
+                {
+                        *channel = Channel {
+                                ch_base:    base,
+                                ch_control: control + 2,
+                        };
+                }
+        }
+        channels
+}
+
+/// Finds the drives and returns how many there are.
+pub(crate) fn init() -> usize
+{
+        let mut drives = DRIVES.lock();
+
+        for (index, ports) in find_channels().into_iter().enumerate() {
+                let mut channel = CHANNELS[index].lock();
+
+                *channel = ports;
+                channel.mask_interrupts();
+                for slave in [false, true] {
+                        drives[index * DRIVES_PER_CHANNEL + slave as usize] = channel
+                                .identify(slave)
+                                .filter(|identify| identify.lba)
+                                .map(|identify| Drive {
+                                        dr_channel:  index,
+                                        dr_slave:    slave,
+                                        dr_identify: identify,
+                                });
+                }
+        }
+        drives.iter().flatten().count()
+}
+
+/// Returns drive `index`, from 0 for `hda` to 3 for `hdd`.
+pub(crate) fn drive(index: usize) -> Option<Drive> { DRIVES.lock().get(index).copied().flatten() }
+
+/// Prints the drives found by [`init`].
+pub(crate) fn print_drives()
+{
+        for drive in DRIVES.lock().iter().flatten() {
+                let identify = drive.identify();
+                println!(
+                        "{}: {}, {} sectors ({} MiB){}",
+                        drive.name(),
+                        identify.model(),
+                        identify.sectors(),
+                        (identify.sectors() * SECTOR_SIZE as u64) >> 20,
+                        match identify.lba48 {
+                                true => ", LBA48",
+                                false => "",
+                        }
+                );
+        }
+}
+
+#[cfg(test)]
+mod tests
+{
+        use super::*;
+
+        /// Text `scripts/disk.sh` writes to sector 1 of the test disk.
+        const DISK_SIGNATURE: &[u8] = b"KFS TEST DISK";
+
+        /// Sector of the test disk the tests write to.
+        const SCRATCH_LBA: u64 = 64;
+
+        fn hda() -> Drive
+        {
+                if drive(0).is_none() {
+                        init();
+                }
+                drive(0).expect("no disk on the primary master")
+        }
+
+        #[test_case]
+        fn identifies_qemu_disk()
+        {
+                let hda = hda();
+
+                assert_eq!(hda.name(), "hda");
+                assert_eq!(hda.identify().model(), "QEMU HARDDISK");
+                assert!(hda.block_count() >= 2048);
+        }
+
+        #[test_case]
+        fn reads_the_disk_signature()
+        {
+                let mut hda = hda();
+                let mut sector = [0; SECTOR_SIZE];
+
+                hda.read_blocks(1, &mut sector).unwrap();
+                assert_eq!(&sector[..DISK_SIGNATURE.len()], DISK_SIGNATURE);
+                assert_eq!(
+                        hda.read_blocks(hda.block_count(), &mut sector),
+                        Err(BlockError::OutOfRange)
+                );
+        }
+
+        #[test_case]
+        fn writes_read_back_with_both_addressings()
+        {
+                let hda = hda();
+                let channel = CHANNELS[0].lock();
+                let mut written = [0; SECTOR_SIZE * 2];
+                let mut read = [0; SECTOR_SIZE * 2];
+
+                for (addressing, seed) in [(Addressing::Lba28, 1u8), (Addressing::Lba48, 7u8)] {
+                        for (i, byte) in written.iter_mut().enumerate() {
+                                *byte = (i as u8).wrapping_mul(seed);
+                        }
+                        hda.write_command(&channel, SCRATCH_LBA, &written, addressing)
+                                .unwrap();
+                        hda.read_command(&channel, SCRATCH_LBA, &mut read, addressing)
+                                .unwrap();
+                        assert_eq!(read, written);
+                }
+        }
+
+        #[test_case]
+        fn block_device_round_trips()
+        {
+                let mut hda = hda();
+                let mut sector = [0; SECTOR_SIZE];
+
+                hda.write_blocks(SCRATCH_LBA + 2, &[0x5a; SECTOR_SIZE])
+                        .unwrap();
+                hda.flush().unwrap();
+                hda.read_blocks(SCRATCH_LBA + 2, &mut sector).unwrap();
+                assert!(sector.iter().all(|&b| b == 0x5a));
+        }
+}
@@ -0,0 +1,256 @@
+//! Interrupt controller selection.
+//!
+//! The ISA IRQs are delivered either by the 8259 PICs or by the IO-APIC,
+//! which [`set_mode`] switches between at runtime. Both deliver IRQ `n` to
+//! vector `pic::VECTOR_BASE + n`, and the IRQ masks carry over, so drivers
+//! only see [`set_masked`] and [`eoi`].
+//!
+//! In APIC mode, the PICs are masked and the IMCR, on the chipsets that have
+//! one, routes their output away from the CPU. In PIC mode, the IO-APIC
+//! inputs are masked and the local APIC, if enabled, lets the PICs through
+//! LINT0.
+//!
+//! The IO-APIC and the ISA IRQ routes default to those of PCs without
+//! overrides, until [`use_madt`] takes those of the ACPI MADT.
+
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+use kfs::acpi::madt::Madt;
+use kfs::apic::{ISA_IRQS, IsaRoutes, RedirectionEntry};
+
+use super::apic::{self, IoApic};
+use super::pic;
+use crate::instructions::io::outb;
+use crate::sync::IrqSafeMutex;
+
+/// Interrupt Mode Configuration Register ports.
+const IMCR_SELECT: u16 = 0x22;
+const IMCR_DATA: u16 = 0x23;
+const IMCR: u8 = 0x70;
+const IMCR_APIC: u8 = 0x01;
+const IMCR_PIC: u8 = 0x00;
+
+/// Controller delivering the ISA IRQs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode
+{
+        Pic,
+        Apic,
+}
+
+/// The CPU has no local APIC, so APIC mode is unavailable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Unsupported;
+
+/// IO-APIC delivering the ISA IRQs, and their routes to its inputs.
+struct Topology
+{
+        tp_io_apic: IoApic,
+        tp_routes:  IsaRoutes,
+}
+
+static TOPOLOGY: IrqSafeMutex<Topology> = IrqSafeMutex::new(Topology {
+        // SAFETY: PCs have their first IO-APIC at the default base.
+        tp_io_apic: unsafe { IoApic::new(apic::IO_APIC_DEFAULT_BASE, 0) },
+        tp_routes:  IsaRoutes::identity(),
+});
+
+/// Whether the IO-APIC delivers the ISA IRQs.
+static APIC_MODE: AtomicBool = AtomicBool::new(false);
+
+/// IRQ masks, bit `n` masking IRQ `n`.
+static MASKS: AtomicU16 = AtomicU16::new(u16::MAX);
+
+/// Returns the vector of ISA IRQ `irq`.
+pub(crate) const fn vector(irq: u8) -> u8 { pic::VECTOR_BASE + irq }
+
+/// Sets the IO-APIC delivering the ISA IRQs and their routes, applied on
+/// the next switch to APIC mode.
+pub(crate) fn set_topology(
+        io_apic: IoApic,
+        routes: IsaRoutes,
+)
+{
+        let mut topology = TOPOLOGY.lock();
+
+        topology.tp_io_apic = io_apic;
+        topology.tp_routes = routes;
+}
+
+/// Takes the IO-APIC delivering the ISA IRQs and their routes from the
+/// MADT, returning whether it lists an IO-APIC for them.
+pub(crate) fn use_madt(madt: &Madt) -> bool
+{
+        let Some(io_apic) = madt.io_apic_for(0) else {
+                return false;
+        };
+
+        // SAFETY: The MADT gives the address of the IO-APIC.
+        let io_apic = unsafe { IoApic::new(io_apic.address as usize, io_apic.gsi_base) };
+        set_topology(io_apic, madt.isa_routes());
+        true
+}
+
+/// Returns the controller delivering the ISA IRQs.
+pub(crate) fn mode() -> Mode
+{
+        match APIC_MODE.load(Ordering::Relaxed) {
+                true => Mode::Apic,
+                false => Mode::Pic,
+        }
+}
+
+/// Selects the IMCR routing of the PIC output.
+fn set_imcr(value: u8)
+{
+        // SAFETY: Chipsets without an IMCR ignore these ports.
+        unsafe {
+                outb(IMCR_SELECT, IMCR);
+                outb(IMCR_DATA, value);
+        }
+}
+
+/// Programs the IO-APIC inputs of the ISA IRQs with `masks`.
+fn route_isa_irqs(
+        topology: &Topology,
+        masks: u16,
+)
+{
+        // SAFETY: `set_mode` enabled the local APIC before.
+        let destination = unsafe { apic::id() };
+
+        for irq in 0..ISA_IRQS as u8 {
+                if irq == pic::CASCADE_IRQ {
+                        continue;
+                }
+                topology.tp_io_apic.route(
+                        topology.tp_routes.get(irq),
+                        vector(irq),
+                        destination,
+                        masks & (1 << irq) != 0,
+                );
+        }
+}
+
+/// Switches the ISA IRQs to the controller `mode`, keeping their masks.
+///
+/// The PICs are remapped on every switch, so that their vectors never
+/// overlap the CPU exceptions.
+pub(crate) fn set_mode(mode: Mode) -> Result<(), Unsupported>
+{
+        if mode == Mode::Apic && !apic::is_supported() {
+                return Err(Unsupported);
+        }
+
+        let topology = TOPOLOGY.lock();
+        let masks = MASKS.load(Ordering::Relaxed);
+
+        pic::remap();
+        match mode {
+                Mode::Apic => {
+                        set_imcr(IMCR_APIC);
+                        // SAFETY: The CPU has a local APIC.
+                        unsafe { apic::init_local(false) };
+                        route_isa_irqs(&topology, masks);
+                }
+                Mode::Pic => {
+                        if apic::is_supported() {
+                                topology.tp_io_apic.mask_all();
+                                // SAFETY: The CPU has a local APIC.
+                                unsafe { apic::init_local(true) };
+                        }
+                        set_imcr(IMCR_PIC);
+                        pic::set_masks(masks);
+                }
+        }
+        APIC_MODE.store(mode == Mode::Apic, Ordering::Relaxed);
+        Ok(())
+}
+
+/// Masks or unmasks ISA IRQ `irq` on the current controller, returning
+/// `false` if `irq` is not an ISA IRQ.
+pub(crate) fn set_masked(
+        irq: u8,
+        masked: bool,
+) -> bool
+{
+        if irq as usize >= ISA_IRQS {
+                return false;
+        }
+
+        let topology = TOPOLOGY.lock();
+        let masks = match masked {
+                true => MASKS.fetch_or(1 << irq, Ordering::Relaxed) | 1 << irq,
+                false => MASKS.fetch_and(!(1 << irq), Ordering::Relaxed) & !(1 << irq),
+        };
+
+        match mode() {
+                Mode::Pic => pic::set_masks(masks),
+                Mode::Apic => {
+                        let route = topology.tp_routes.get(irq);
+                        if let Some(entry) = topology.tp_io_apic.redirection(route.gsi) {
+                                topology.tp_io_apic.set_redirection(
+                                        route.gsi,
+                                        RedirectionEntry { masked, ..entry },
+                                );
+                        }
+                }
+        }
+        true
+}
+
+/// Returns whether ISA IRQ `irq` is masked, which IRQs that are not ISA
+/// ones always are.
+pub(crate) fn is_masked(irq: u8) -> bool
+{
+        (irq as usize) >= ISA_IRQS || MASKS.load(Ordering::Relaxed) & (1 << irq) != 0
+}
+
+/// Signals the end of the handling of ISA IRQ `irq`.
+pub(crate) fn eoi(irq: u8)
+{
+        match mode() {
+                Mode::Pic => pic::eoi(irq),
+                // SAFETY: APIC mode enabled the local APIC.
+                Mode::Apic => unsafe { apic::eoi() },
+        }
+}
+
+#[cfg(test)]
+mod tests
+{
+        use super::*;
+
+        #[test_case]
+        fn switches_between_pic_and_apic()
+        {
+                set_masked(1, false);
+                assert_eq!(set_mode(Mode::Apic), Ok(()));
+                assert_eq!(mode(), Mode::Apic);
+                assert_eq!(pic::masks(), u16::MAX);
+
+                let topology = TOPOLOGY.lock();
+                let route = topology.tp_routes.get(1);
+                let entry = topology.tp_io_apic.redirection(route.gsi).unwrap();
+                assert_eq!(entry.vector, vector(1));
+                assert!(!entry.masked);
+                drop(topology);
+
+                set_masked(1, true);
+                assert_eq!(set_mode(Mode::Pic), Ok(()));
+                assert_eq!(mode(), Mode::Pic);
+                assert!(is_masked(1));
+                assert_eq!(pic::masks(), u16::MAX);
+        }
+
+        #[test_case]
+        fn rejects_non_isa_irqs()
+        {
+                let masks = MASKS.load(Ordering::Relaxed);
+
+                assert!(!set_masked(ISA_IRQS as u8, false));
+                assert!(!set_masked(u8::MAX, true));
+                assert!(is_masked(ISA_IRQS as u8));
+                assert_eq!(MASKS.load(Ordering::Relaxed), masks);
+        }
+}
@@ -1 +1,10 @@
+pub mod acpi;
+pub mod apic;
+pub mod ata;
+pub mod irq;
+pub mod pci;
+pub mod pic;
+pub mod pit;
+pub mod rtc;
+pub mod speaker;
 pub mod video;
@@ -0,0 +1,342 @@
+//! PCI bus enumeration.
+//!
+//! The configuration space is accessed through mechanism #1: the address of
+//! a register is written to `0xcf8`, then the register is read or written
+//! through `0xcfc`, 32 bits at a time.
+//!
+//! [`init`] scans the buses recursively, from the host bridges down through
+//! the PCI-to-PCI bridges, sizes the BARs of each function, and keeps them
+//! in a [`Registry`] drivers look their devices up in. Host bridges are left
+//! alone: sizing their BARs turns their decoding off, which may cut the CPU
+//! off from the RAM on real chipsets.
+//!
+//! Reference: https://wiki.osdev.org/PCI
+
+use kfs::pci::{
+        Address, BAR_OFFSET, Bar, Command, DEVICES, FUNCTIONS, Function, HEADER_REGISTERS,
+        MAX_FUNCTIONS, Match, Registry,
+};
+
+use crate::instructions::io::{indw, outdw};
+use crate::sync::IrqSafeMutex;
+use crate::{log, println};
+
+const CONFIG_ADDRESS: u16 = 0xcf8;
+const CONFIG_DATA: u16 = 0xcfc;
+
+/// Offset of the command register.
+const COMMAND_OFFSET: u8 = 0x04;
+
+/// Taken while a register is selected.
+static CONFIG: IrqSafeMutex<()> = IrqSafeMutex::new(());
+
+/// Functions found by the last scan.
+static REGISTRY: IrqSafeMutex<Registry> = IrqSafeMutex::new(Registry::new());
+
+/// Reads the configuration register at `offset`, rounded down to 4 bytes.
+pub(crate) fn read(
+        address: Address,
+        offset: u8,
+) -> u32
+{
+        let _config = CONFIG.lock();
+
+        // SAFETY: Reading the configuration space has no side effect.
+        unsafe {
+                outdw(CONFIG_ADDRESS, address.config_address(offset));
+                indw(CONFIG_DATA)
+        }
+}
+
+/// Writes the configuration register at `offset`, rounded down to 4 bytes.
+///
+/// # Safety
+/// `value` must be valid for the register, and the change must not break
+/// the function while it is in use.
+pub(crate) unsafe fn write(
+        address: Address,
+        offset: u8,
+        value: u32,
+)
+{
+        let _config = CONFIG.lock();
+
+        outdw(CONFIG_ADDRESS, address.config_address(offset));
+        outdw(CONFIG_DATA, value);
+}
+
+/// Returns the command register of a function.
+pub(crate) fn command(address: Address) -> Command
+{
+        Command::from_bits_retain(read(address, COMMAND_OFFSET) as u16)
+}
+
+/// Sets the command register of a function, leaving its status register
+/// alone.
+///
+/// # Safety
+/// The function must handle the accesses `command` enables.
+pub(crate) unsafe fn set_command(
+        address: Address,
+        command: Command,
+)
+{
+        // Writing ones to the status bits clears them, so zeros are kept.
+        write(address, COMMAND_OFFSET, command.bits() as u32);
+}
+
+/// Reads the standard header of the function at `address`.
+fn read_function(address: Address) -> Option<Function>
+{
+        let mut regs = [0; HEADER_REGISTERS];
+
+        // Absent functions answer all ones, which the vendor ID gives away.
+        regs[0] = read(address, 0);
+        if regs[0] as u16 == kfs::pci::NO_VENDOR {
+                return None;
+        }
+        for (i, reg) in regs.iter_mut().enumerate().skip(1) {
+                *reg = read(address, (i * 4) as u8);
+        }
+        Function::from_header(address, &regs)
+}
+
+/// Reads BAR register `index`, and the value read back after writing all
+/// ones to it, then restores it.
+///
+/// # Safety
+/// The function must not decode accesses meanwhile.
+unsafe fn probe_register(
+        address: Address,
+        index: usize,
+) -> (u32, u32)
+{
+        let offset = BAR_OFFSET + index as u8 * 4;
+        let raw = read(address, offset);
+
+        write(address, offset, u32::MAX);
+        let mask = read(address, offset);
+        write(address, offset, raw);
+        (raw, mask)
+}
+
+/// Sizes the BARs of `function`, with its decoding disabled meanwhile.
+/// Host bridges are skipped, their BARs being left unknown.
+fn probe_bars(function: &mut Function)
+{
+        if function.is_host_bridge() {
+                return;
+        }
+
+        let address = function.address;
+        let command = command(address);
+        let mut index = 0;
+
+        // SAFETY: Nothing uses the function during the scan, and its
+        // decoding is restored afterwards.
+        unsafe {
+                set_command(
+                        address,
+                        command - (Command::IO_SPACE | Command::MEMORY_SPACE),
+                );
+                while index < function.bar_registers() {
+                        let (raw, mask) = probe_register(address, index);
+                        let (raw, mask, registers) = match Bar::is_wide(raw) {
+                                true if index + 1 < function.bar_registers() => {
+                                        let (high, high_mask) = probe_register(address, index + 1);
+                                        (
+                                                (high as u64) << 32 | raw as u64,
+                                                (high_mask as u64) << 32 | mask as u64,
+                                                2,
+                                        )
+                                }
+                                _ => (raw as u64, mask as u64, 1),
+                        };
+
+                        function.bars[index] = Bar::decode(raw, mask);
+                        index += registers;
+                }
+                set_command(address, command);
+        }
+}
+
+/// Adds the functions of `bus`, and of the buses behind its bridges, to
+/// `registry`, returning `false` if it got full before the end of the scan.
+fn scan_bus(
+        registry: &mut Registry,
+        bus: u8,
+) -> bool
+{
+        for device in 0..DEVICES {
+                let Some(first) = read_function(Address::new(bus, device, 0)) else {
+                        continue;
+                };
+                let functions = match first.multifunction {
+                        true => FUNCTIONS,
+                        false => 1,
+                };
+
+                for function in 0..functions {
+                        let Some(mut function) = read_function(Address::new(bus, device, function))
+                        else {
+                                continue;
+                        };
+                        probe_bars(&mut function);
+                        if !registry.add(function) {
+                                return false;
+                        }
+
+                        // Buses are numbered depth first, so a bridge to a
+                        // lower bus is misconfigured and would loop.
+                        let secondary = function.secondary_bus.filter(|&secondary| secondary > bus);
+                        if secondary.is_some_and(|secondary| !scan_bus(registry, secondary)) {
+                                return false;
+                        }
+                }
+        }
+        true
+}
+
+/// Scans the PCI buses, replacing the registry, and returns the number of
+/// functions found. The scan stops once the registry is full.
+///
+/// A multi-function host bridge means several host controllers, function
+/// `n` being the one of bus `n`.
+pub(crate) fn init() -> usize
+{
+        let mut registry = REGISTRY.lock();
+
+        registry.clear();
+        let complete = match read_function(Address::new(0, 0, 0)) {
+                Some(host) if host.multifunction => (0..FUNCTIONS).all(|function| {
+                        read_function(Address::new(0, 0, function)).is_none()
+                                || scan_bus(&mut registry, function)
+                }),
+                _ => scan_bus(&mut registry, 0),
+        };
+        if !complete {
+                log!(
+                        "PCI: more than {} functions, the scan stopped there",
+                        MAX_FUNCTIONS
+                );
+        }
+        registry.len()
+}
+
+/// Returns the first function `criteria` matches.
+pub(crate) fn find(criteria: &Match) -> Option<Function> { REGISTRY.lock().find(criteria).copied() }
+
+/// Runs `f` on each function `criteria` matches.
+///
+/// The registry is locked meanwhile, so `f` must not use it.
+pub(crate) fn for_each(
+        criteria: &Match,
+        f: impl FnMut(&Function),
+)
+{
+        REGISTRY.lock().matching(*criteria).for_each(f);
+}
+
+/// Lists the functions found by the scan, like `lspci -nn -v`.
+pub(crate) fn lspci()
+{
+        for_each(&Match::default(), |function| {
+                println!(
+                        "{} {} [{:02x}{:02x}]: {:04x}:{:04x} (rev {:02x})",
+                        function.address,
+                        function.class_name(),
+                        function.class,
+                        function.subclass,
+                        function.vendor_id,
+                        function.device_id,
+                        function.revision
+                );
+                if let (Some(pin), Some(line)) = (function.interrupt_pin, function.interrupt_line) {
+                        println!(
+                                "        Interrupt: pin {} routed to IRQ {}",
+                                (b'A' + pin - 1) as char,
+                                line
+                        );
+                }
+                for (i, bar) in function.bars.iter().enumerate() {
+                        match bar {
+                                Some(Bar::Io { port, size }) => {
+                                        println!(
+                                                "        Region {}: I/O ports at {:04x} [size={}]",
+                                                i, port, size
+                                        )
+                                }
+                                Some(Bar::Memory {
+                                        address,
+                                        size,
+                                        prefetchable,
+                                        wide,
+                                }) => println!(
+                                        "        Region {}: Memory at {:08x} ({}-bit, \
+                                         {}prefetchable) [size={:#x}]",
+                                        i,
+                                        address,
+                                        match wide {
+                                                true => 64,
+                                                false => 32,
+                                        },
+                                        match prefetchable {
+                                                true => "",
+                                                false => "non-",
+                                        },
+                                        size
+                                ),
+                                None => {}
+                        }
+                }
+        });
+}
+
+#[cfg(test)]
+mod tests
+{
+        use super::*;
+
+        #[test_case]
+        fn finds_qemu_devices()
+        {
+                assert!(init() >= 4);
+
+                let host = find(&Match::class(0x06, 0x00)).unwrap();
+                assert_eq!(host.address, Address::new(0, 0, 0));
+                assert_eq!(read(host.address, 0) as u16, host.vendor_id);
+
+                // The PIIX IDE controller has its bus master registers in
+                // BAR 4.
+                let ide = find(&Match::class(0x01, 0x01)).unwrap();
+                assert!(matches!(ide.bars[4], Some(Bar::Io { size: 16, .. })));
+        }
+
+        #[test_case]
+        fn host_bridge_keeps_decoding()
+        {
+                let address = Address::new(0, 0, 0);
+                let before = (command(address), read(address, BAR_OFFSET));
+
+                init();
+                assert_eq!((command(address), read(address, BAR_OFFSET)), before);
+        }
+
+        #[test_case]
+        fn probing_keeps_bars()
+        {
+                init();
+                let vga = find(&Match::class(0x03, 0x00)).unwrap();
+                let before = read(vga.address, BAR_OFFSET);
+
+                init();
+                assert_eq!(read(vga.address, BAR_OFFSET), before);
+                assert!(matches!(
+                        vga.bars[0],
+                        Some(Bar::Memory {
+                                prefetchable: true,
+                                ..
+                        })
+                ));
+        }
+}
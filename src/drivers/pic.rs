@@ -0,0 +1,83 @@
+//! 8259 Programmable Interrupt Controllers.
+//!
+//! Two cascaded 8259s deliver the 16 ISA IRQs, the slave on IRQ2 of the
+//! master. The BIOS leaves their vectors over the CPU exceptions, so
+//! [`remap`] moves them to [`VECTOR_BASE`] onward.
+//!
+//! Reference: https://wiki.osdev.org/8259_PIC
+
+use crate::instructions::io::{inb, outb};
+
+const MASTER_COMMAND: u16 = 0x20;
+const MASTER_DATA: u16 = 0x21;
+const SLAVE_COMMAND: u16 = 0xa0;
+const SLAVE_DATA: u16 = 0xa1;
+
+/// ICW1: initialization, ICW4 follows.
+const ICW1_INIT: u8 = 0x11;
+/// ICW4: 8086 mode.
+const ICW4_8086: u8 = 0x01;
+/// End of interrupt command.
+const EOI: u8 = 0x20;
+
+/// IRQ of the master the slave is cascaded on.
+pub(crate) const CASCADE_IRQ: u8 = 2;
+
+/// Vector of IRQ0, IRQ8 being at `VECTOR_BASE + 8`.
+pub(crate) const VECTOR_BASE: u8 = 0x20;
+
+/// Reinitializes both PICs with their vectors from [`VECTOR_BASE`], and
+/// every IRQ masked.
+pub(crate) fn remap()
+{
+        // SAFETY: The initialization sequence leaves the PICs working, and
+        // every IRQ masked.
+        unsafe {
+                outb(MASTER_COMMAND, ICW1_INIT);
+                outb(SLAVE_COMMAND, ICW1_INIT);
+                outb(MASTER_DATA, VECTOR_BASE);
+                outb(SLAVE_DATA, VECTOR_BASE + 8);
+                outb(MASTER_DATA, 1 << CASCADE_IRQ);
+                outb(SLAVE_DATA, CASCADE_IRQ);
+                outb(MASTER_DATA, ICW4_8086);
+                outb(SLAVE_DATA, ICW4_8086);
+        }
+        set_masks(u16::MAX);
+}
+
+/// Returns the IRQ masks, bit `n` masking IRQ `n`.
+pub(crate) fn masks() -> u16
+{
+        // SAFETY: Reading the masks has no side effect.
+        unsafe { inb(MASTER_DATA) as u16 | (inb(SLAVE_DATA) as u16) << 8 }
+}
+
+/// Sets the IRQ masks, bit `n` masking IRQ `n`.
+///
+/// The cascade stays unmasked while an IRQ of the slave is.
+pub(crate) fn set_masks(masks: u16)
+{
+        let mut masks = masks;
+
+        if masks >> 8 != 0xff {
+                masks &= !(1 << CASCADE_IRQ);
+        }
+
+        // SAFETY: Masks only change which IRQs are delivered.
+        unsafe {
+                outb(MASTER_DATA, masks as u8);
+                outb(SLAVE_DATA, (masks >> 8) as u8);
+        }
+}
+
+/// Signals the end of the handling of `irq`.
+pub(crate) fn eoi(irq: u8)
+{
+        // SAFETY: The handler of `irq` is done.
+        unsafe {
+                if irq >= 8 {
+                        outb(SLAVE_COMMAND, EOI);
+                }
+                outb(MASTER_COMMAND, EOI);
+        }
+}
@@ -0,0 +1,115 @@
+//! Programmable Interval Timer (PIT), channel 2.
+//!
+//! Channel 2 is the only PIT channel whose output the CPU can poll: bit 0 of
+//! the System Control Port B (`0x61`) gates it, bit 1 connects its output to
+//! the PC speaker and bit 5 reads the output back. Without timer interrupts,
+//! counting its periods is how the kernel measures time, for the speaker
+//! tones as well as to calibrate other timers.
+//!
+//! Reference: https://wiki.osdev.org/Programmable_Interval_Timer
+
+use spin::Mutex;
+
+use crate::instructions::io::{inb, outb};
+
+/// Frequency of the PIT input clock, in Hz.
+pub(crate) const FREQUENCY: u32 = 1_193_182;
+
+const CHANNEL_2_PORT: u16 = 0x42;
+const COMMAND_PORT: u16 = 0x43;
+const CONTROL_PORT: u16 = 0x61;
+
+/// PIT command selecting channel 2, low then high byte access, mode 3
+/// (square wave generator) and binary counting.
+const CHANNEL_2_SQUARE_WAVE: u8 = 0b1011_0110;
+
+/// Bits of the System Control Port B.
+const GATE: u8 = 0x01;
+const SPEAKER_DATA: u8 = 0x02;
+const OUTPUT: u8 = 0x20;
+
+/// Frequency of the silent wave timing [`wait_ms`], in Hz.
+const WAIT_FREQUENCY: u32 = 1000;
+
+/// Reads of the output before giving up on an edge, well over the longest
+/// half period, 27 ms at the lowest frequency.
+const EDGE_TIMEOUT: u32 = 1_000_000;
+
+/// Taken while channel 2 is in use, so that waits and sounds do not mix.
+///
+/// Waits and sounds last long, so the lock leaves interrupts enabled, and
+/// interrupt handlers must not wait for it.
+pub(crate) static CHANNEL_2: Mutex<()> = Mutex::new(());
+
+/// Programs channel 2 to the closest frequency to `frequency` it can
+/// produce, and returns that frequency.
+pub(crate) fn set_square_wave(frequency: u32) -> u32
+{
+        let divisor = (FREQUENCY / frequency.max(1)).clamp(1, u16::MAX as u32);
+
+        // SAFETY: Channel 2 is only used through this module.
+        unsafe {
+                outb(COMMAND_PORT, CHANNEL_2_SQUARE_WAVE);
+                outb(CHANNEL_2_PORT, divisor as u8);
+                outb(CHANNEL_2_PORT, (divisor >> 8) as u8);
+        }
+        FREQUENCY / divisor
+}
+
+/// Reads the System Control Port B.
+fn control() -> u8
+{
+        // SAFETY: Reading the port has no side effect.
+        unsafe { inb(CONTROL_PORT) }
+}
+
+/// Starts or stops channel 2, and connects its output to the speaker or
+/// not.
+pub(crate) fn set_channel_2(
+        gate: bool,
+        speaker: bool,
+)
+{
+        let bits = match (gate, speaker) {
+                (false, _) => 0,
+                (true, false) => GATE,
+                (true, true) => GATE | SPEAKER_DATA,
+        };
+        let value = (control() & !(GATE | SPEAKER_DATA)) | bits;
+
+        // SAFETY: Only the channel 2 bits change.
+        unsafe { outb(CONTROL_PORT, value) };
+}
+
+/// Returns whether channel 2 runs, and whether it drives the speaker.
+pub(crate) fn channel_2() -> (bool, bool)
+{
+        let control = control();
+
+        (control & GATE != 0, control & SPEAKER_DATA != 0)
+}
+
+/// Waits for the channel 2 output to be `high`, returning `false` if it
+/// does not change in time.
+fn wait_output(high: bool) -> bool { (0..EDGE_TIMEOUT).any(|_| (control() & OUTPUT != 0) == high) }
+
+/// Waits for `periods` periods of the channel 2 output, returning `false`
+/// if the output stops toggling, as when the channel is not running.
+pub(crate) fn wait_periods(periods: u32) -> bool
+{
+        // Waits for the output to fall, then to rise.
+        (0..periods).all(|_| wait_output(false) && wait_output(true))
+}
+
+/// Waits for `ms` milliseconds, running channel 2 silently, and returns
+/// `false` if channel 2 could not time it.
+pub(crate) fn wait_ms(ms: u32) -> bool
+{
+        let _channel = CHANNEL_2.lock();
+        let frequency = set_square_wave(WAIT_FREQUENCY);
+
+        set_channel_2(true, false);
+        let waited = wait_periods((frequency as u64 * ms as u64 / 1000) as u32);
+        set_channel_2(false, false);
+        waited
+}
@@ -0,0 +1,262 @@
+//! CMOS real-time clock.
+//!
+//! The RTC is read through the CMOS index/data port pair `0x70`/`0x71`.
+//! Bit 7 of the index disables NMIs, and is kept set while a register is
+//! accessed so that no NMI handler finds the index changed. Each access ends
+//! by selecting Status Register D with the bit clear, which enables NMIs
+//! again. The values are decoded by [`kfs::rtc`].
+//!
+//! The clock updates its registers once per second, Status Register A
+//! flagging the update in progress: the registers are read again until two
+//! reads outside an update agree.
+//!
+//! The RTC raises IRQ8 for the periodic, alarm and update-ended interrupts
+//! enabled in Status Register B. Status Register C must then be read with
+//! [`acknowledge`], or no other interrupt is raised. The kernel has no
+//! interrupt handling yet, so nothing unmasks IRQ8 on the PIC.
+//!
+//! Reference: https://wiki.osdev.org/CMOS
+
+use core::hint;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use kfs::rtc::{self, Interrupts, Registers, StatusB};
+use kfs::time::DateTime;
+
+use crate::instructions::io::{inb, outb};
+use crate::sync::IrqSafeMutex;
+
+const INDEX_PORT: u16 = 0x70;
+const DATA_PORT: u16 = 0x71;
+
+/// Bit of the index disabling NMIs.
+const NMI_DISABLE: u8 = 0x80;
+
+/// CMOS register indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Register
+{
+        Seconds      = 0x00,
+        SecondsAlarm = 0x01,
+        Minutes      = 0x02,
+        MinutesAlarm = 0x03,
+        Hours        = 0x04,
+        HoursAlarm   = 0x05,
+        Day          = 0x07,
+        Month        = 0x08,
+        Year         = 0x09,
+        /// Update in progress flag and periodic interrupt rate
+        StatusA      = 0x0a,
+        /// Data format and enabled interrupts
+        StatusB      = 0x0b,
+        /// Pending interrupts, cleared on read
+        StatusC      = 0x0c,
+        /// Valid RAM flag, read only
+        StatusD      = 0x0d,
+}
+
+/// Bit of Status Register A set while the registers are updated.
+const UPDATE_IN_PROGRESS: u8 = 0x80;
+
+/// Bits of Status Register A selecting the periodic interrupt rate.
+const RATE_MASK: u8 = 0x0f;
+
+/// IRQ line of the RTC interrupts.
+pub(crate) const IRQ: u8 = 8;
+
+/// Frequency the periodic interrupt rate divides, in Hz.
+const BASE_FREQUENCY: u32 = 32_768;
+
+/// CMOS register holding the century, 0 if there is none.
+///
+/// The ACPI FADT gives its index, and most firmwares use `0x32`.
+static CENTURY_REGISTER: AtomicU8 = AtomicU8::new(0x32);
+
+/// Taken while the CMOS index is selected.
+static CMOS: IrqSafeMutex<()> = IrqSafeMutex::new(());
+
+/// Selects Status Register D, which no write can change, with NMIs enabled.
+///
+/// # Safety
+/// No other CMOS access may be in progress.
+unsafe fn deselect() { outb(INDEX_PORT, Register::StatusD as u8); }
+
+/// Reads CMOS register `index`.
+fn read_index(index: u8) -> u8
+{
+        // SAFETY: The index is a CMOS register, reading it has no side
+        // effect besides Status Register C, which `acknowledge` reads.
+        unsafe {
+                outb(INDEX_PORT, NMI_DISABLE | index);
+                let value = inb(DATA_PORT);
+                deselect();
+                value
+        }
+}
+
+fn read(reg: Register) -> u8 { read_index(reg as u8) }
+
+/// Writes `value` to register `reg`.
+///
+/// # Safety
+/// The value must be valid for the register, in the format of Status
+/// Register B.
+unsafe fn write(
+        reg: Register,
+        value: u8,
+)
+{
+        outb(INDEX_PORT, NMI_DISABLE | reg as u8);
+        outb(DATA_PORT, value);
+        deselect();
+}
+
+/// Reads the date and time registers once no update is in progress.
+fn read_time() -> Registers
+{
+        while read(Register::StatusA) & UPDATE_IN_PROGRESS != 0 {
+                hint::spin_loop();
+        }
+
+        let century = match CENTURY_REGISTER.load(Ordering::Relaxed) {
+                0 => None,
+                index => Some(read_index(index)),
+        };
+        Registers {
+                second: read(Register::Seconds),
+                minute: read(Register::Minutes),
+                hour: read(Register::Hours),
+                day: read(Register::Day),
+                month: read(Register::Month),
+                year: read(Register::Year),
+                century,
+                status_b: StatusB::from_bits_retain(read(Register::StatusB)),
+        }
+}
+
+/// Returns the current date and time, or `None` if the clock holds an
+/// invalid date.
+pub(crate) fn now() -> Option<DateTime>
+{
+        let _cmos = CMOS.lock();
+        let mut last = read_time();
+
+        loop {
+                let regs = read_time();
+                if regs == last {
+                        return regs.to_datetime();
+                }
+                last = regs;
+        }
+}
+
+/// Selects the CMOS register holding the century, `None` if the firmware
+/// has none and the 21st century is assumed.
+pub(crate) fn set_century_register(index: Option<u8>)
+{
+        CENTURY_REGISTER.store(index.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Changes the interrupts the RTC raises on IRQ8, among
+/// [`StatusB::PERIODIC`], [`StatusB::ALARM`] and [`StatusB::UPDATE_ENDED`].
+pub(crate) fn enable_interrupts(interrupts: StatusB)
+{
+        let mask = StatusB::PERIODIC | StatusB::ALARM | StatusB::UPDATE_ENDED;
+        let _cmos = CMOS.lock();
+        let status = StatusB::from_bits_retain(read(Register::StatusB));
+
+        // SAFETY: Only the interrupt enable bits change.
+        unsafe {
+                write(
+                        Register::StatusB,
+                        ((status - mask) | (interrupts & mask)).bits(),
+                )
+        };
+}
+
+/// Sets the periodic interrupt frequency to `32768 >> (rate - 1)` Hz, `rate`
+/// going from 3 (8192 Hz) to 15 (2 Hz).
+///
+/// Returns the frequency, in Hz.
+pub(crate) fn set_periodic_rate(rate: u8) -> u32
+{
+        let rate = rate.clamp(3, 15);
+        let _cmos = CMOS.lock();
+        let status = read(Register::StatusA);
+
+        // SAFETY: Only the rate bits change, the divider keeps running.
+        unsafe { write(Register::StatusA, (status & !RATE_MASK) | rate) };
+        BASE_FREQUENCY >> (rate - 1)
+}
+
+/// Sets the time of the alarm interrupt, `None` matching every value.
+pub(crate) fn set_alarm(
+        hour: Option<u8>,
+        minute: Option<u8>,
+        second: Option<u8>,
+)
+{
+        let _cmos = CMOS.lock();
+        let format = StatusB::from_bits_retain(read(Register::StatusB));
+        let hour = hour.map_or(rtc::ALARM_ANY, |hour| format.encode_hour(hour % 24));
+        let minute = minute.map_or(rtc::ALARM_ANY, |minute| format.encode(minute % 60));
+        let second = second.map_or(rtc::ALARM_ANY, |second| format.encode(second % 60));
+
+        // SAFETY: The values are in the format of Status Register B.
+        unsafe {
+                write(Register::HoursAlarm, hour);
+                write(Register::MinutesAlarm, minute);
+                write(Register::SecondsAlarm, second);
+        }
+}
+
+/// Reads and clears the pending RTC interrupts, which the IRQ8 handler must
+/// do for the next interrupt to be raised.
+pub(crate) fn acknowledge() -> Interrupts
+{
+        let _cmos = CMOS.lock();
+
+        Interrupts::from_bits_retain(read(Register::StatusC))
+}
+
+#[cfg(test)]
+mod tests
+{
+        use super::*;
+
+        #[test_case]
+        fn clock_holds_a_date()
+        {
+                let now = now().unwrap();
+
+                assert!(now.year >= 2024);
+        }
+
+        #[test_case]
+        fn alarm_round_trips()
+        {
+                set_alarm(Some(13), None, Some(5));
+
+                let format = StatusB::from_bits_retain(read(Register::StatusB));
+                assert_eq!(format.decode_hour(read(Register::HoursAlarm)), 13);
+                assert_eq!(read(Register::MinutesAlarm), rtc::ALARM_ANY);
+                assert_eq!(format.decode(read(Register::SecondsAlarm)), 5);
+        }
+
+        #[test_case]
+        fn interrupts_are_enabled_in_status_b()
+        {
+                assert_eq!(set_periodic_rate(6), 1024);
+                enable_interrupts(StatusB::PERIODIC);
+
+                let status = StatusB::from_bits_retain(read(Register::StatusB));
+                assert!(status.contains(StatusB::PERIODIC));
+                assert!(!status.contains(StatusB::ALARM));
+
+                enable_interrupts(StatusB::empty());
+                acknowledge();
+                let status = StatusB::from_bits_retain(read(Register::StatusB));
+                assert!(!status.intersects(StatusB::PERIODIC | StatusB::ALARM));
+        }
+}
@@ -0,0 +1,166 @@
+//! PC speaker.
+//!
+//! The speaker is driven by the square wave of channel 2 of the Programmable
+//! Interval Timer, see [`super::pit`].
+//!
+//! The kernel has no timer interrupt yet, so durations are measured by
+//! counting the periods of the channel 2 output. Rests keep the channel
+//! running with the speaker disconnected. Interrupts stay enabled meanwhile.
+//!
+//! The console bell is requested while the console is locked, and only rung
+//! once it is unlocked, so that output does not stall for the bell.
+//!
+//! In QEMU, the speaker needs an audio backend, `-audiodev none` being
+//! enough for the timing to work without sound.
+//!
+//! Reference: https://wiki.osdev.org/PC_Speaker
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kfs::melody::{self, MelodyError};
+
+use super::pit::{self, CHANNEL_2};
+
+/// Lowest frequency the PIT divisor can produce, in Hz.
+pub(crate) const MIN_FREQUENCY: u32 = pit::FREQUENCY / u16::MAX as u32 + 1;
+
+/// Highest frequency the periods can be counted at, in Hz.
+pub(crate) const MAX_FREQUENCY: u32 = 20_000;
+
+/// Frequency of the silent wave timing rests, in Hz.
+const REST_FREQUENCY: u32 = 1000;
+
+/// Bell of the console, as the Linux console sounds it.
+const BELL_FREQUENCY: u32 = 750;
+const BELL_DURATION_MS: u32 = 125;
+
+/// Whether the console bell was requested and has not rung yet.
+static BELL_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Programs channel 2 to the closest frequency to `frequency` it can
+/// produce, and returns that frequency.
+fn set_frequency(frequency: u32) -> u32
+{
+        pit::set_square_wave(frequency.clamp(MIN_FREQUENCY, MAX_FREQUENCY))
+}
+
+/// Starts a tone at `frequency` Hz, until [`stop`] is called.
+///
+/// The frequency is clamped between [`MIN_FREQUENCY`] and [`MAX_FREQUENCY`].
+pub(crate) fn start(frequency: u32)
+{
+        set_frequency(frequency);
+        pit::set_channel_2(true, true);
+}
+
+/// Silences the speaker.
+pub(crate) fn stop() { pit::set_channel_2(false, false); }
+
+/// Plays a tone at `frequency` Hz for `duration_ms` milliseconds, `None`
+/// being silence.
+fn sound(
+        frequency: Option<u32>,
+        duration_ms: u32,
+)
+{
+        let (frequency, speaker) = match frequency {
+                Some(frequency) => (frequency, true),
+                None => (REST_FREQUENCY, false),
+        };
+        let frequency = set_frequency(frequency);
+
+        pit::set_channel_2(true, speaker);
+        // A channel that stops toggling only cuts the sound short.
+        pit::wait_periods((frequency as u64 * duration_ms as u64 / 1000) as u32);
+        pit::set_channel_2(false, false);
+}
+
+/// Plays a tone at `frequency` Hz for `duration_ms` milliseconds.
+///
+/// The frequency is clamped between [`MIN_FREQUENCY`] and [`MAX_FREQUENCY`].
+pub(crate) fn beep(
+        frequency: u32,
+        duration_ms: u32,
+)
+{
+        let _speaker = CHANNEL_2.lock();
+
+        sound(Some(frequency), duration_ms);
+}
+
+/// Sounds the console bell, unless channel 2 is busy.
+pub(crate) fn bell()
+{
+        if let Some(_speaker) = CHANNEL_2.try_lock() {
+                sound(Some(BELL_FREQUENCY), BELL_DURATION_MS);
+        }
+}
+
+/// Requests the console bell, rung by [`ring_pending_bell`].
+pub(crate) fn request_bell() { BELL_PENDING.store(true, Ordering::Relaxed); }
+
+/// Rings the console bell if it was requested.
+pub(crate) fn ring_pending_bell()
+{
+        if BELL_PENDING.swap(false, Ordering::Relaxed) {
+                bell();
+        }
+}
+
+/// Plays `melody`, written as described in [`kfs::melody`].
+///
+/// The melody is checked before the first note plays.
+pub(crate) fn play(melody: &str) -> Result<(), MelodyError>
+{
+        let _speaker = CHANNEL_2.lock();
+
+        melody::notes(melody).try_for_each(|note| note.map(|_| ()))?;
+        for note in melody::notes(melody).flatten() {
+                sound(note.frequency, note.duration_ms);
+        }
+        Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+        use super::*;
+
+        #[test_case]
+        fn tone_drives_speaker()
+        {
+                start(440);
+                assert_eq!(pit::channel_2(), (true, true));
+
+                stop();
+                assert_eq!(pit::channel_2(), (false, false));
+        }
+
+        #[test_case]
+        fn melody_plays_to_the_end()
+        {
+                assert_eq!(play("T960 C6:16 R:16 G5:16"), Ok(()));
+                assert_eq!(pit::channel_2(), (false, false));
+
+                assert_eq!(play("C6:16 H2"), Err(MelodyError::InvalidNote));
+        }
+
+        #[test_case]
+        fn bell_waits_for_request()
+        {
+                request_bell();
+                assert!(BELL_PENDING.load(Ordering::Relaxed));
+                ring_pending_bell();
+                assert!(!BELL_PENDING.load(Ordering::Relaxed));
+                assert_eq!(pit::channel_2(), (false, false));
+        }
+
+        #[test_case]
+        fn stopped_channel_times_out()
+        {
+                let _channel = CHANNEL_2.lock();
+
+                pit::set_channel_2(false, false);
+                assert!(!pit::wait_periods(1));
+        }
+}
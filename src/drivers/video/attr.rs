@@ -0,0 +1,128 @@
+//! VGA Attribute Controller access.
+//!
+//! The Attribute Controller is the VGA hardware block that turns the value
+//! read from video memory into a color index for the DAC.
+//!
+//! In text mode it is mainly used to:
+//! - map the 16 attribute colors to DAC entries through the palette registers,
+//! - choose between blinking and bright backgrounds for attribute bit 7,
+//! - duplicate the 8th column of line drawing characters in 9 dots modes.
+//!
+//! Unlike the other VGA controllers, index and data share the same write port
+//! `0x3C0`, and an internal flip-flop tells whether the next write is an index
+//! or a value. Reading Input Status #1 (`0x3DA`) resets the flip-flop to the
+//! index state, so every access starts with that read:
+//! - `0x3C0`: register index and data write port,
+//! - `0x3C1`: register data read port.
+//!
+//! Bit 5 of the index (Palette Address Source) must be set for the display to
+//! be enabled: while it is clear, the palette registers can be changed but the
+//! screen is blank.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/attrreg.htm
+
+use super::misc;
+use crate::instructions::io::{inb, outb};
+
+const INDEX_PORT: u16 = 0x3C0;
+const DATA_READ_PORT: u16 = 0x3C1;
+
+/// Palette Address Source bit of the index, enables the display.
+const PAS: u8 = 0x20;
+
+/// Number of palette registers.
+pub(super) const PALETTE_SIZE: u8 = 16;
+
+/// VGA Attribute Controller register indices.
+///
+/// The palette registers `0x00` to `0x0F` are accessed through
+/// [`write_palette`] and [`read_palette`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(super) enum Register
+{
+        /// Selects text or graphics mode, blink and line graphics.
+        ModeControl            = 0x10,
+        /// Color of the screen border.
+        OverscanColor          = 0x11,
+        /// Enables each of the 4 color planes.
+        ColorPlaneEnable       = 0x12,
+        /// Shifts the display left by a number of pixels.
+        HorizontalPixelPanning = 0x13,
+        /// Provides the high bits of DAC indices.
+        ColorSelect            = 0x14,
+}
+
+impl Register
+{
+        /// Every register, in index order.
+        pub(super) const ALL: [Register; 5] = [
+                Register::ModeControl,
+                Register::OverscanColor,
+                Register::ColorPlaneEnable,
+                Register::HorizontalPixelPanning,
+                Register::ColorSelect,
+        ];
+}
+
+#[inline(always)]
+unsafe fn write_index(
+        index: u8,
+        value: u8,
+)
+{
+        misc::input_status();
+        outb(INDEX_PORT, index | PAS);
+        outb(INDEX_PORT, value);
+}
+
+#[inline(always)]
+fn read_index(index: u8) -> u8
+{
+        misc::input_status();
+        // SAFETY: The flip-flop has been reset, so this write selects the
+        // register and keeps the display enabled.
+        unsafe {
+                outb(INDEX_PORT, index | PAS);
+                let value = inb(DATA_READ_PORT);
+                misc::input_status();
+                value
+        }
+}
+
+/// Writes `value` to the selected Attribute Controller register.
+///
+/// # Safety
+/// Callers must ensure that the value being written is valid for the selected
+/// register, cause this value may cause undefined behavior in the VGA hardware
+/// if invalid.
+#[inline(always)]
+pub(super) unsafe fn write(
+        reg: Register,
+        value: u8,
+)
+{
+        write_index(reg as u8, value);
+}
+
+/// Reads the value of the selected Attribute Controller register.
+#[inline(always)]
+pub(super) fn read(reg: Register) -> u8 { read_index(reg as u8) }
+
+/// Maps the attribute color `index` to the 6-bit DAC index `value`.
+///
+/// # Safety
+/// Callers must ensure that `index` is lower than [`PALETTE_SIZE`].
+#[inline(always)]
+pub(super) unsafe fn write_palette(
+        index: u8,
+        value: u8,
+)
+{
+        debug_assert!(index < PALETTE_SIZE);
+        write_index(index, value & 0x3f);
+}
+
+/// Reads the DAC index the attribute color `index` is mapped to.
+#[inline(always)]
+pub(super) fn read_palette(index: u8) -> u8 { read_index(index & (PALETTE_SIZE - 1)) }
@@ -69,6 +69,38 @@ pub(super) enum Register
         LineCompare             = 0x18,
 }
 
+impl Register
+{
+        /// Every register, in index order.
+        pub(super) const ALL: [Register; 25] = [
+                Register::HorizontalTotal,
+                Register::HorizontalDisplayEnd,
+                Register::HorizontalBlankingStart,
+                Register::HorizontalBlankingEnd,
+                Register::HorizontalRetraceStart,
+                Register::HorizontalRetraceEnd,
+                Register::VerticalTotal,
+                Register::Overflow,
+                Register::PresetRowScan,
+                Register::MaximumScanLine,
+                Register::CursorStart,
+                Register::CursorEnd,
+                Register::StartAddressHigh,
+                Register::StartAddressLow,
+                Register::CursorLocationHigh,
+                Register::CursorLocationLow,
+                Register::VerticalRetraceStart,
+                Register::VerticalRetraceEnd,
+                Register::VerticalDisplayEnd,
+                Register::Offset,
+                Register::UnderlineLocation,
+                Register::VerticalBlankingStart,
+                Register::VerticalBlankingEnd,
+                Register::ModeControl,
+                Register::LineCompare,
+        ];
+}
+
 /// Writes `value` to the selected VGA controller register.
 ///
 /// # Safety
@@ -0,0 +1,54 @@
+//! VGA DAC palette access.
+//!
+//! The DAC (Digital to Analog Converter) turns the 8-bit color index produced
+//! by the Attribute Controller into the RGB signal sent to the display, using
+//! a table of 256 entries of 6 bits per component.
+//!
+//! Entries are accessed through an auto-incrementing address:
+//! - `0x3C7`: selects the entry to read,
+//! - `0x3C8`: selects the entry to write,
+//! - `0x3C9`: data port, three accesses (red, green then blue) per entry.
+//!
+//! As with the other controllers, accesses are stateful and must not be
+//! interleaved.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/colorreg.htm
+
+use kfs::vga::palette::Rgb;
+
+use crate::instructions::io::{inb, outb};
+
+const READ_INDEX_PORT: u16 = 0x3C7;
+const WRITE_INDEX_PORT: u16 = 0x3C8;
+const DATA_PORT: u16 = 0x3C9;
+
+/// Sets DAC entry `index` to `color`.
+#[inline(always)]
+pub(super) fn write(
+        index: u8,
+        color: Rgb,
+)
+{
+        let (r, g, b) = color.to_dac();
+
+        // SAFETY: Any value is valid for the DAC, writes only change the
+        // displayed colors.
+        unsafe {
+                outb(WRITE_INDEX_PORT, index);
+                outb(DATA_PORT, r);
+                outb(DATA_PORT, g);
+                outb(DATA_PORT, b);
+        }
+}
+
+/// Reads DAC entry `index`.
+#[inline(always)]
+pub(super) fn read(index: u8) -> Rgb
+{
+        // SAFETY: Reading the DAC has no side effect besides moving its read
+        // address.
+        unsafe {
+                outb(READ_INDEX_PORT, index);
+                Rgb::from_dac(inb(DATA_PORT), inb(DATA_PORT), inb(DATA_PORT))
+        }
+}
@@ -49,6 +49,22 @@ pub(super) enum Register
         BitMask           = 0x08,
 }
 
+impl Register
+{
+        /// Every register, in index order.
+        pub(super) const ALL: [Register; 9] = [
+                Register::SetResetValue,
+                Register::SetResetEnable,
+                Register::ColorCompareValue,
+                Register::DataRotate,
+                Register::ReadMapSelect,
+                Register::GraphicsMode,
+                Register::Miscellaneous,
+                Register::ColorDontCareMask,
+                Register::BitMask,
+        ];
+}
+
 /// Indexed register write primitive.
 ///
 /// # Safety
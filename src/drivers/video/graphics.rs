@@ -0,0 +1,114 @@
+//! VGA pixel graphics surfaces.
+//!
+//! Both graphics modes map their framebuffer at `0xA0000`:
+//! - in 320×200×256 (mode 13h), chain-4 addressing spreads consecutive bytes
+//!   over the planes, so each pixel is one byte of a linear framebuffer,
+//! - in 640×480×16 (mode 12h), each pixel is one bit in each of the 4 planes.
+//!   Pixels are written with write mode 2: the Bit Mask register selects the
+//!   pixel within the byte, and the written value is the color, expanded by the
+//!   hardware to the 4 planes.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/vgamem.htm
+
+use core::ptr;
+
+use kfs::vga::graphics::Surface;
+
+use super::gfxc;
+
+/// Physical address of the graphics framebuffer.
+const FRAMEBUFFER: usize = 0xA0000;
+
+/// Surface of the 320×200×256 mode.
+#[derive(Debug)]
+pub(super) struct LinearSurface;
+
+impl Surface for LinearSurface
+{
+        fn width(&self) -> usize { 320 }
+
+        fn height(&self) -> usize { 200 }
+
+        fn write_pixel(
+                &mut self,
+                x: usize,
+                y: usize,
+                color: u8,
+        )
+        {
+                let pixel = (FRAMEBUFFER + y * 320 + x) as *mut u8;
+
+                // SAFETY: The pixel is within the framebuffer mapped by the mode.
+                unsafe { ptr::write_volatile(pixel, color) };
+        }
+}
+
+/// Surface of the 640×480×16 mode.
+#[derive(Debug)]
+pub(super) struct PlanarSurface;
+
+impl PlanarSurface
+{
+        /// Bytes per line in each plane.
+        const PITCH: usize = 640 / 8;
+
+        /// Selects write mode 2, which every access of the surface relies on.
+        ///
+        /// # Safety
+        /// The 640×480×16 mode must be set.
+        pub(super) unsafe fn new() -> Self
+        {
+                let mode = gfxc::read(gfxc::Register::GraphicsMode);
+                gfxc::write(gfxc::Register::GraphicsMode, (mode & !0x03) | 0x02);
+
+                Self
+        }
+
+        /// Writes `color` to the pixels of the byte at `offset` selected by
+        /// `mask`.
+        fn write_byte(
+                offset: usize,
+                mask: u8,
+                color: u8,
+        )
+        {
+                let byte = (FRAMEBUFFER + offset) as *mut u8;
+
+                // SAFETY: The offset is within the framebuffer mapped by the
+                // mode, and reading it only loads the latches so the pixels
+                // outside of the mask are written back unchanged.
+                unsafe {
+                        gfxc::write(gfxc::Register::BitMask, mask);
+                        ptr::read_volatile(byte);
+                        ptr::write_volatile(byte, color);
+                }
+        }
+}
+
+impl Surface for PlanarSurface
+{
+        fn width(&self) -> usize { 640 }
+
+        fn height(&self) -> usize { 480 }
+
+        fn write_pixel(
+                &mut self,
+                x: usize,
+                y: usize,
+                color: u8,
+        )
+        {
+                Self::write_byte(y * Self::PITCH + x / 8, 0x80 >> (x & 7), color);
+        }
+
+        /// Writes 8 pixels at once instead of one per access.
+        fn clear(
+                &mut self,
+                color: u8,
+        )
+        {
+                for offset in 0..Self::PITCH * self.height() {
+                        Self::write_byte(offset, 0xff, color);
+                }
+        }
+}
@@ -0,0 +1,82 @@
+//! VGA external registers access.
+//!
+//! These registers are not indexed, each one has its own I/O port:
+//! - the Miscellaneous Output register, written through `0x3C2` and read
+//!   through `0x3CC`, selects the dot clock, the sync polarities (which give
+//!   the number of scanlines) and the CRTC I/O address (`0x3Dx` or `0x3Bx`),
+//! - the Input Status #1 register, read through `0x3DA`, reports the display
+//!   and vertical retrace states. Reading it also resets the Attribute
+//!   Controller flip-flop.
+//!
+//! This module assumes a color VGA, where the CRTC and Input Status #1 are
+//! mapped at `0x3Dx`.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/extreg.htm
+
+use crate::instructions::io::{inb, outb};
+
+const MISC_OUTPUT_WRITE_PORT: u16 = 0x3C2;
+const MISC_OUTPUT_READ_PORT: u16 = 0x3CC;
+const INPUT_STATUS_1_PORT: u16 = 0x3DA;
+
+/// Writes `value` to the Miscellaneous Output register.
+///
+/// # Safety
+/// Callers must ensure that the value is valid for the connected display, and
+/// keeps bit 0 set as the rest of the driver expects the CRTC at `0x3D4`.
+#[inline(always)]
+pub(super) unsafe fn write(value: u8) { outb(MISC_OUTPUT_WRITE_PORT, value); }
+
+/// Reads the value of the Miscellaneous Output register.
+#[inline(always)]
+pub(super) fn read() -> u8
+{
+        // SAFETY: Reading this port has no side effect.
+        unsafe { inb(MISC_OUTPUT_READ_PORT) }
+}
+
+/// Reads the Input Status #1 register.
+///
+/// As a side effect, the Attribute Controller flip-flop is reset to the
+/// index state.
+#[inline(always)]
+pub(super) fn input_status() -> u8
+{
+        // SAFETY: Reading this port only resets the Attribute Controller
+        // flip-flop, which every access to that controller expects.
+        unsafe { inb(INPUT_STATUS_1_PORT) }
+}
+
+/// Vertical Retrace bit of the Input Status #1 register.
+const VERTICAL_RETRACE: u8 = 0x08;
+
+/// Waits for the beginning of the next vertical retrace.
+///
+/// Palette changes made during the retrace are not visible mid-frame, and
+/// video memory written during the retrace is displayed from the top of the
+/// next frame.
+///
+/// The retrace is polled: the CRTC can raise IRQ2 at its beginning, but
+/// most VGA compatible adapters do not wire it, and the kernel does not
+/// handle interrupts yet.
+pub(super) fn wait_vertical_retrace()
+{
+        while input_status() & VERTICAL_RETRACE != 0 {
+                core::hint::spin_loop();
+        }
+        while input_status() & VERTICAL_RETRACE == 0 {
+                core::hint::spin_loop();
+        }
+}
+
+/// Waits for the end of the current vertical retrace, if any.
+///
+/// The CRTC latches the Start Address when the retrace begins, so writing
+/// its two bytes outside of it shows either the old or the new value for a
+/// whole frame.
+pub(super) fn wait_vertical_display()
+{
+        while input_status() & VERTICAL_RETRACE != 0 {
+                core::hint::spin_loop();
+        }
+}
@@ -1,36 +1,644 @@
-use core::fmt;
-use lazy_static::lazy_static;
-use spin::Mutex;
-use vgac::VgaConsole;
+use core::fmt::{self, Write};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::{ptr, slice};
 
+use graphics::{LinearSurface, PlanarSurface};
+use kfs::framebuffer::FramebufferBackend;
+use kfs::multiboot::FramebufferInfo;
+use kfs::splash;
+use kfs::vga::attribute::Attribute;
+use kfs::vga::backend::RamBackend;
+use kfs::vga::config::Config;
+use kfs::vga::console::VgaConsole;
+use kfs::vga::font::{FontError, Psf};
+use kfs::vga::graphics::Surface;
+use kfs::vga::mode::{GraphicsMode, ModeRegisters, TextMode};
+use kfs::vga::palette::{self, Theme};
+use kfs::vga::snapshot::Snapshot;
+use kfs::vga::text::ScrollDir;
+use kfs::vga::{CursorTypes, MemoryRanges, Resolution, VGAColor};
+use screen::Screen;
+use state::VgaState;
+use vgac::VgaBackend;
+
+use crate::drivers::{rtc, speaker};
+use crate::sync::{IrqSafeMutex, IrqSafeMutexGuard};
+
+mod attr;
 mod crtc;
+mod dac;
 mod gfxc;
+mod graphics;
+mod misc;
+mod modeset;
+mod screen;
+mod seq;
+mod state;
 mod vgac;
 
-lazy_static! {
-        static ref LOGGER: Mutex<VgaConsole> = Mutex::new(VgaConsole::new(
-                vgac::VGAColor::White,
-                vgac::VGAColor::Black,
-                vgac::Resolution::R80_25,
-                vgac::MemoryRanges::Small,
-                Some(vgac::CursorTypes::Full),
-        ));
+/// Console all the output goes to, created by [`lock`] or [`init`].
+static LOGGER: IrqSafeMutex<Option<VgaConsole<Screen>>> = IrqSafeMutex::new(None);
+
+/// Set once [`init`] has set the display up.
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// Locked console, which always exists.
+///
+/// The bell rung by the text is sounded once the console is unlocked.
+struct Logger(ManuallyDrop<IrqSafeMutexGuard<'static, Option<VgaConsole<Screen>>>>);
+
+impl Logger
+{
+        fn new(mut guard: IrqSafeMutexGuard<'static, Option<VgaConsole<Screen>>>) -> Self
+        {
+                guard.get_or_insert_with(early_console);
+                Self(ManuallyDrop::new(guard))
+        }
+}
+
+impl Drop for Logger
+{
+        fn drop(&mut self)
+        {
+                // SAFETY: The guard is not used after being dropped.
+                unsafe { ManuallyDrop::drop(&mut self.0) };
+                speaker::ring_pending_bell();
+        }
+}
+
+impl Deref for Logger
+{
+        type Target = VgaConsole<Screen>;
+
+        fn deref(&self) -> &Self::Target { self.0.as_ref().expect("console created on lock") }
+}
+
+impl DerefMut for Logger
+{
+        fn deref_mut(&mut self) -> &mut Self::Target
+        {
+                self.0.as_mut().expect("console created on lock")
+        }
+}
+
+/// Locks the console, writing to the BIOS text memory if [`init`] has not
+/// been called yet.
+fn lock() -> Logger { Logger::new(LOGGER.lock()) }
+
+/// Locks the console, unless it is already locked.
+fn try_lock() -> Option<Logger> { LOGGER.try_lock().map(Logger::new) }
+
+/// Creates the console used before [`init`], on the 80x25 text mode the
+/// BIOS left.
+///
+/// No register is touched, so the hardware cursor stays where the BIOS left
+/// it.
+fn early_console() -> VgaConsole<Screen>
+{
+        let (cols, rows) = Resolution::R80_25.dimensions();
+        // SAFETY: The BIOS text memory is mapped, and the driver does not
+        // use it before `init`, which drops the early console.
+        let cells = unsafe {
+                slice::from_raw_parts_mut(
+                        MemoryRanges::Small.base() as *mut u16,
+                        cols as usize * rows as usize,
+                )
+        };
+
+        VgaConsole::new(
+                Screen::Early(RamBackend::new(cells)),
+                VGAColor::LightGray,
+                VGAColor::Black,
+                Resolution::R80_25,
+                None,
+        )
+}
+
+/// Sets the display up as described by `config`, and moves the text printed
+/// so far to it.
+///
+/// The console uses the framebuffer when `config` has one with a font, and
+/// the VGA hardware otherwise. Nothing is done if the display is already set
+/// up.
+///
+/// The VGA console expects the text mode left by the bootloader, whose plane
+/// 2 holds the BIOS font, so the boot menu only offers a graphics mode when a
+/// font is packaged for the framebuffer console.
+///
+/// # Safety
+/// The framebuffer of `config` must be mapped at its physical address, and
+/// nothing else may access it.
+pub(crate) unsafe fn init(config: &Config<'static>)
+{
+        let mut logger = LOGGER.lock();
+        if READY.load(Ordering::Acquire) {
+                return;
+        }
+
+        let framebuffer = match (config.framebuffer, config.font) {
+                (Some(info), Some(font)) => framebuffer_console(&info, font),
+                _ => None,
+        };
+        let mut console = framebuffer.unwrap_or_else(|| vga_console(config.mode));
+        if let Some(early) = logger.as_mut() {
+                replay(early, &mut console);
+        }
+        if let Some(theme) = config.theme {
+                apply_theme(console.backend_mut(), theme);
+        }
+        console.set_smooth_scroll(scroll_step(config.smooth_scroll));
+
+        *logger = Some(console);
+        READY.store(true, Ordering::Release);
+}
+
+/// Returns whether [`init`] has set the display up.
+///
+/// Before that, the output goes to the text memory left by the BIOS, and
+/// display features such as themes, fonts or graphics modes do nothing.
+pub(crate) fn is_ready() -> bool { READY.load(Ordering::Acquire) }
+
+/// Creates the console on the VGA hardware, in text `mode`.
+fn vga_console(mode: TextMode) -> VgaConsole<Screen>
+{
+        let mut console = VgaConsole::new(
+                Screen::Vga(VgaBackend::new(MemoryRanges::Small)),
+                VGAColor::White,
+                VGAColor::Black,
+                Resolution::R80_25,
+                Some(CursorTypes::Full),
+        );
+
+        if mode != TextMode::T80x25 {
+                console.set_mode(mode);
+        }
+        console
+}
+
+/// Writes the lines of `early` up to its cursor to `console`, with their
+/// colors.
+fn replay(
+        early: &mut VgaConsole<Screen>,
+        console: &mut VgaConsole<Screen>,
+)
+{
+        let grid = early.grid();
+        let (cols, last, column) = (grid.cols() as usize, grid.row(), grid.column());
+
+        for (row, line) in early.visible().chunks(cols).take(last + 1).enumerate() {
+                let len = match row == last {
+                        true => column,
+                        false => line
+                                .iter()
+                                .rposition(|&cell| cell as u8 != b' ')
+                                .map_or(0, |end| end + 1),
+                };
+
+                for &cell in &line[..len] {
+                        let (foreground, background) =
+                                ((cell >> 8) as u8 & 0xf, (cell >> 12) as u8);
+                        console.cputc(cell as u8, Some(foreground), Some(background));
+                }
+                if row != last {
+                        console.scroll(ScrollDir::Down, 1);
+                }
+        }
 }
 
 #[doc(hidden)]
 pub(crate) fn _print(args: fmt::Arguments)
 {
-        let mut logger = LOGGER.lock();
+        let mut logger = lock();
+        fmt::write(&mut *logger, args).ok();
+}
+
+#[doc(hidden)]
+pub(crate) fn _cprint(
+        attribute: Attribute,
+        args: fmt::Arguments,
+)
+{
+        let mut logger = lock();
+        let previous = logger.attribute();
+
+        logger.set_attribute(attribute);
+        fmt::write(&mut *logger, args).ok();
+        logger.set_attribute(previous);
+}
+
+/// Colors of the log timestamps.
+const TIMESTAMP_ATTRIBUTE: Attribute = Attribute::new(VGAColor::DarkGray, VGAColor::Black);
+
+#[doc(hidden)]
+pub(crate) fn _log(args: fmt::Arguments)
+{
+        let now = rtc::now();
+        let mut logger = lock();
+        let previous = logger.attribute();
+
+        logger.set_attribute(TIMESTAMP_ATTRIBUTE);
+        match now {
+                Some(now) => write!(
+                        logger,
+                        "[{:02}:{:02}:{:02}] ",
+                        now.hour, now.minute, now.second
+                ),
+                None => write!(logger, "[--:--:--] "),
+        }
+        .ok();
+        logger.set_attribute(previous);
         fmt::write(&mut *logger, args).ok();
 }
 
+/// Brings the console back to a known-good text mode, so that the panic
+/// message can be read whatever state the display was in.
+///
+/// The console lock is released if the code that panicked held it.
+pub(crate) fn _panic_reset()
+{
+        if LOGGER.is_locked() {
+                // SAFETY: The code that panicked never runs again, and nothing
+                // else holds the lock.
+                unsafe { LOGGER.force_unlock() };
+        }
+
+        let mut logger = lock();
+        if let Some(vga) = logger.backend_mut().vga() {
+                vga.restore_bios_state();
+                logger.set_mode(TextMode::T80x25);
+        }
+}
+
+/// Writes `args` at the top of VGA text memory, without going through the
+/// console.
+///
+/// This is the last resort when reporting a panic panicked, the console
+/// state not being trusted anymore. Nothing is written on a framebuffer.
+pub(crate) fn _emergency_print(args: fmt::Arguments)
+{
+        /// Raw writer to the 80x25 text memory, white on red.
+        struct Emergency(usize);
+
+        impl fmt::Write for Emergency
+        {
+                fn write_str(
+                        &mut self,
+                        s: &str,
+                ) -> fmt::Result
+                {
+                        let vram = MemoryRanges::Small.base() as *mut u16;
+
+                        for byte in s.bytes().take(80 * 25 - self.0.min(80 * 25)) {
+                                // SAFETY: The text memory is mapped, and the
+                                // position is inside the 80x25 screen.
+                                unsafe {
+                                        ptr::write_volatile(vram.add(self.0), 0x4f00 | byte as u16)
+                                };
+                                self.0 += 1;
+                        }
+                        Ok(())
+                }
+        }
+
+        if FB_TAKEN.load(Ordering::Acquire) {
+                return;
+        }
+        // SAFETY: Nothing else runs, the console lock can not be trusted.
+        unsafe {
+                crtc::write(crtc::Register::StartAddressLow, 0);
+                crtc::write(crtc::Register::StartAddressHigh, 0);
+        }
+        fmt::write(&mut Emergency(0), args).ok();
+}
+
 pub(crate) fn _panic_print(args: fmt::Arguments)
 {
-        if let Some(mut logger) = LOGGER.try_lock() {
+        if let Some(mut logger) = try_lock() {
                 fmt::write(&mut *logger, args).ok();
         }
 }
 
+/// Number of text cells of the framebuffer console memory.
+const FB_CELLS: usize = 0x8000;
+
+/// Text memory of the framebuffer console, and the cells it has drawn.
+static mut FB_TEXT: [[u16; FB_CELLS]; 2] = [[0; FB_CELLS]; 2];
+
+/// Set once [`FB_TEXT`] is handed over to the framebuffer console.
+static FB_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Creates the console on the framebuffer described by `info`, drawing text
+/// with `font`.
+///
+/// Returns `None` if the framebuffer pixel format is not supported, or if a
+/// console already uses the framebuffer memory.
+///
+/// # Safety
+/// `info` must describe a framebuffer mapped at its physical address, which
+/// nothing else accesses.
+unsafe fn framebuffer_console(
+        info: &FramebufferInfo,
+        font: Psf<'static>,
+) -> Option<VgaConsole<Screen>>
+{
+        let addr = usize::try_from(info.addr).ok()?;
+        if FB_TAKEN.swap(true, Ordering::AcqRel) {
+                return None;
+        }
+
+        let len = info.pitch as usize * info.height as usize;
+        let pixels = slice::from_raw_parts_mut(addr as *mut u8, len);
+        // SAFETY: `FB_TAKEN` ensures the buffers are only borrowed once.
+        let [cells, shown] = &mut *ptr::addr_of_mut!(FB_TEXT);
+        let Some(fb) = FramebufferBackend::new(info, pixels, font, cells, shown) else {
+                FB_TAKEN.store(false, Ordering::Release);
+                return None;
+        };
+        let (cols, rows) = fb.max_geometry();
+
+        Some(VgaConsole::with_geometry(
+                Screen::Framebuffer(fb),
+                VGAColor::White,
+                VGAColor::Black,
+                cols,
+                rows,
+                Some(CursorTypes::Full),
+        ))
+}
+
+/// Clears the screen and moves the cursor to the top left corner.
+pub(crate) fn clear() { lock().blank(); }
+
+/// Number of frames of the splash fade in.
+const SPLASH_FADE_FRAMES: u8 = 32;
+
+/// Clears the screen and fades the boot banner in.
+pub(crate) fn splash()
+{
+        let mut logger = lock();
+        let fade = match logger.backend_mut().vga() {
+                Some(vga) => {
+                        let palette = *vga.palette();
+                        vga.show_palette(&palette::fade(&palette, 0, 1));
+                        true
+                }
+                None => false,
+        };
+
+        splash::draw_banner(&mut logger, splash::BANNER);
+        drop(logger);
+
+        if fade {
+                fade_in(SPLASH_FADE_FRAMES);
+        }
+}
+
+/// Changes the console colors to `theme`.
+pub(crate) fn set_theme(theme: &Theme) { apply_theme(lock().backend_mut(), theme); }
+
+/// Loads the colors of `theme` in `screen`.
+///
+/// The BIOS text mode colors are left alone.
+fn apply_theme(
+        screen: &mut Screen,
+        theme: &Theme,
+)
+{
+        match screen {
+                Screen::Early(_) => {}
+                Screen::Vga(vga) => vga.set_palette(&theme.palette),
+                Screen::Framebuffer(fb) => fb.set_palette(&theme.palette),
+        }
+}
+
+/// Fades the screen from black to the console colors over `frames` frames.
+///
+/// Fades need the DAC, so they do nothing on a framebuffer.
+pub(crate) fn fade_in(frames: u8)
+{
+        for level in 0..=frames {
+                fade_to(level, frames);
+        }
+}
+
+/// Fades the screen from the console colors to black over `frames` frames.
+///
+/// The text stays invisible until [`fade_in`] or [`set_theme`] is called.
+pub(crate) fn fade_out(frames: u8)
+{
+        for level in (0..=frames).rev() {
+                fade_to(level, frames);
+        }
+}
+
+/// Displays the console colors dimmed to `level` out of `levels` on the next
+/// frame.
+fn fade_to(
+        level: u8,
+        levels: u8,
+)
+{
+        let mut logger = lock();
+        let Some(vga) = logger.backend_mut().vga() else {
+                return;
+        };
+        let faded = palette::fade(vga.palette(), level, levels);
+
+        misc::wait_vertical_retrace();
+        vga.show_palette(&faded);
+}
+
+/// Runs `init` as a boot stage, displaying its progress and whether it
+/// returned `true`.
+///
+/// The console is not locked while `init` runs, so it can print.
+pub(crate) fn boot_stage(
+        name: &str,
+        init: impl FnOnce() -> bool,
+) -> bool
+{
+        let stage = splash::begin_stage(&mut lock(), name);
+        let ok = init();
+
+        stage.finish(&mut lock(), ok);
+        ok
+}
+
+/// Hardware state of the text mode, while in graphics mode.
+static TEXT_STATE: IrqSafeMutex<VgaState> = IrqSafeMutex::new(VgaState::new());
+
+/// Switches the screen to the graphics `mode`, and runs `draw` with the
+/// surface to draw on and the BIOS font, before switching back to the text
+/// console.
+///
+/// On a framebuffer, `mode` is ignored: `draw` runs on the whole framebuffer
+/// with the console font, colors being the 16 attribute colors.
+///
+/// Returns what `draw` returns, or `None` without running it before
+/// [`init`].
+///
+/// The console is locked until `draw` returns, so it must not print.
+pub(crate) fn with_graphics<R>(
+        mode: GraphicsMode,
+        draw: impl FnOnce(&mut dyn Surface, &Psf) -> R,
+) -> Option<R>
+{
+        let mut logger = lock();
+        let backend = match logger.backend_mut() {
+                Screen::Early(_) => return None,
+                Screen::Vga(vga) => vga,
+                Screen::Framebuffer(fb) => {
+                        let font = fb.font();
+
+                        fb.clear(0);
+                        let result = draw(fb, &font);
+                        fb.redraw();
+                        return Some(result);
+                }
+        };
+        let mut text = TEXT_STATE.lock();
+        backend.enter_graphics(mode, &mut text);
+
+        let font = backend.bios_font();
+        let result = match mode {
+                GraphicsMode::G320x200x256 => {
+                        let mut surface = LinearSurface;
+                        surface.clear(0);
+                        draw(&mut surface, &font)
+                }
+                GraphicsMode::G640x480x16 => {
+                        // SAFETY: The mode has just been set.
+                        let mut surface = unsafe { PlanarSurface::new() };
+                        surface.clear(0);
+                        draw(&mut surface, &font)
+                }
+        };
+
+        // The text cells are kept in RAM, and written back to VGA memory.
+        backend.leave_graphics(&text);
+        logger.redisplay();
+
+        Some(result)
+}
+
+/// Switches the screen to `mode`, keeping the lines up to the cursor.
+///
+/// Does nothing on a framebuffer, whose geometry is fixed.
+pub(crate) fn set_mode(mode: TextMode)
+{
+        let mut logger = lock();
+
+        if logger.backend_mut().vga().is_some() {
+                logger.set_mode(mode);
+        }
+}
+
+/// Replaces the displayed font with `font`, which must have as many
+/// scanlines as the current mode characters.
+///
+/// In VGA text mode, the font stays until the mode changes or
+/// [`restore_bios_font`] is called.
+pub(crate) fn load_font(font: Psf<'static>) -> Result<(), FontError>
+{
+        match lock().backend_mut() {
+                Screen::Early(_) => Err(FontError::Unsupported),
+                Screen::Vga(vga) => vga.load_font(&font),
+                Screen::Framebuffer(fb) => fb.set_font(font),
+        }
+}
+
+/// Redefines the glyph of character `c`, one byte per scanline.
+///
+/// Only the VGA text mode font can be edited.
+pub(crate) fn set_glyph(
+        c: u8,
+        bitmap: &[u8],
+) -> Result<(), FontError>
+{
+        let mut logger = lock();
+        let vga = logger.backend_mut().vga().ok_or(FontError::Unsupported)?;
+
+        vga.set_glyph(c, bitmap)
+}
+
+/// Loads the font set up by the BIOS back, in VGA text mode.
+pub(crate) fn restore_bios_font()
+{
+        if let Some(vga) = lock().backend_mut().vga() {
+                vga.restore_bios_font();
+        }
+}
+
+/// Reads the current value of every VGA register, or `None` if the console
+/// is not displayed in VGA text mode.
+///
+/// The result can be printed to inspect the hardware state, or given back to
+/// [`restore_registers`].
+pub(crate) fn dump_registers() -> Option<ModeRegisters>
+{
+        let mut logger = lock();
+
+        logger.backend_mut().vga()?;
+        Some(modeset::registers())
+}
+
+/// Loads every VGA register from `regs`, as returned by [`dump_registers`].
+///
+/// # Safety
+/// `regs` must describe a mode the display supports, and keep the current
+/// text mode memory map and geometry, as the console is not updated.
+pub(crate) unsafe fn restore_registers(regs: &ModeRegisters)
+{
+        let mut logger = lock();
+
+        if logger.backend_mut().vga().is_some() {
+                modeset::set_registers(regs);
+        }
+}
+
+/// Colors of the status rows.
+const STATUS_ATTRIBUTE: Attribute = Attribute::new(VGAColor::Black, VGAColor::LightGray);
+
+/// Keeps the last `rows` rows of the screen for status lines, which stay in
+/// place while the text scrolls.
+pub(crate) fn set_status_rows(rows: u8) { lock().set_split(rows); }
+
+/// Replaces status row `row` with the formatted `args`.
+pub(crate) fn set_status(
+        row: usize,
+        args: fmt::Arguments,
+)
+{
+        lock().write_status(row, STATUS_ATTRIBUTE, args);
+}
+
+/// Number of scanlines the text moves by each frame in smooth scroll mode.
+const SMOOTH_SCROLL_SCANLINES: u8 = 4;
+
+/// Turns smooth scrolling of the console on or off.
+///
+/// Smooth scrolling needs the CRTC, so the framebuffer console always
+/// scrolls whole rows.
+pub(crate) fn set_smooth_scroll(enabled: bool) { lock().set_smooth_scroll(scroll_step(enabled)); }
+
+/// Returns the number of scanlines to scroll by each frame.
+const fn scroll_step(smooth: bool) -> u8
+{
+        match smooth {
+                true => SMOOTH_SCROLL_SCANLINES,
+                false => 0,
+        }
+}
+
+/// Gives bit 7 of the text attributes to the background intensity instead of
+/// blinking.
+pub(crate) fn set_bright_background(enabled: bool) { lock().set_bright_background(enabled); }
+
+/// Captures the characters currently displayed on the 80x25 screen.
+pub(crate) fn snapshot() -> Snapshot<80, 25> { Snapshot::capture(lock().visible()) }
+
 #[macro_export]
 macro_rules! print {
 	($($arg:tt)*) => {{
@@ -45,3 +653,230 @@ macro_rules! println {
 		$crate::drivers::video::_print(format_args_nl!($($arg)*));
 	}};
 }
+
+/// Prints with the given [`Attribute`], then goes back to the current one.
+#[macro_export]
+macro_rules! cprint {
+	($attr:expr, $($arg:tt)*) => {{
+		$crate::drivers::video::_cprint($attr, format_args!($($arg)*));
+	}};
+}
+
+#[macro_export]
+macro_rules! cprintln {
+	($attr:expr) => ($crate::cprint!($attr, "\n"));
+	($attr:expr, $($arg:tt)*) => {{
+		$crate::drivers::video::_cprint($attr, format_args_nl!($($arg)*));
+	}};
+}
+
+/// Prints a line after the time of the real-time clock.
+#[macro_export]
+macro_rules! log {
+	($($arg:tt)*) => {{
+		$crate::drivers::video::_log(format_args_nl!($($arg)*));
+	}};
+}
+
+#[cfg(test)]
+mod tests
+{
+        use kfs::vga::attribute::{Attribute, AttributeFlags};
+        use kfs::vga::backend::TextBackend;
+        use kfs::vga::config::Config;
+        use kfs::vga::font::FontError;
+        use kfs::vga::mode::{GraphicsMode, TextMode};
+        use kfs::vga::palette;
+        use kfs::vga::{MemoryRanges, VGAColor};
+
+        use crate::assert_screen;
+
+        #[test_case]
+        fn print_matches_golden_screen()
+        {
+                super::clear();
+                print!("\nHello");
+                assert_screen!("../../.assets/basic_a_80_25.txt");
+        }
+
+        #[test_case]
+        fn register_dump_round_trips()
+        {
+                let regs = super::dump_registers().unwrap();
+                unsafe { super::restore_registers(&regs) };
+
+                assert_eq!(super::dump_registers(), Some(regs));
+                assert_eq!(regs.gc[0x06] & 0x0c, (MemoryRanges::Small as u8) << 2);
+        }
+
+        #[test_case]
+        fn glyph_height_must_match_mode()
+        {
+                assert_eq!(
+                        super::set_glyph(b'A', &[0xff; 8]),
+                        Err(FontError::HeightMismatch)
+                );
+                assert_eq!(super::set_glyph(0x80, &[0x18; 16]), Ok(()));
+                super::restore_bios_font();
+        }
+
+        #[test_case]
+        fn theme_survives_mode_switch()
+        {
+                super::set_theme(&palette::GRUVBOX);
+                super::set_mode(TextMode::T80x50);
+                super::set_mode(TextMode::T80x25);
+
+                assert_eq!(
+                        super::dac::read(1).to_dac(),
+                        palette::GRUVBOX.palette[1].to_dac()
+                );
+                assert_eq!(super::attr::read_palette(6), 6);
+                super::set_theme(&palette::DEFAULT);
+        }
+
+        #[test_case]
+        fn graphics_restore_text()
+        {
+                super::clear();
+                print!("\nHello");
+
+                for mode in [GraphicsMode::G320x200x256, GraphicsMode::G640x480x16] {
+                        let corner = super::with_graphics(mode, |surface, font| {
+                                surface.fill_rect((0, 0), (16, 16), 15);
+                                surface.draw_str((20, 20), "kfs", font, 4, None);
+                                unsafe { core::ptr::read_volatile(0xA0000 as *const u8) }
+                        });
+                        assert!(corner.is_some_and(|c| c != 0), "{:?}", mode);
+                }
+
+                assert_screen!("../../.assets/basic_a_80_25.txt");
+        }
+
+        #[test_case]
+        fn mode_switch_keeps_text()
+        {
+                super::clear();
+                print!("\nHello");
+                super::set_mode(TextMode::T80x50);
+                super::set_mode(TextMode::T80x25);
+                assert_screen!("../../.assets/basic_a_80_25.txt");
+        }
+
+        #[test_case]
+        fn status_row_survives_scrolling()
+        {
+                super::clear();
+                super::set_status_rows(1);
+                super::set_status(0, format_args!("status"));
+                for _ in 0..30 {
+                        println!("scroll");
+                }
+
+                let mut logger = super::lock();
+                let cells = logger.backend_mut().cells();
+                assert!(cells.iter().zip(b"status").all(|(&c, &b)| c as u8 == b));
+                drop(logger);
+                // Scanline 383, its bit 8 is in the Overflow register.
+                assert_eq!(super::crtc::read(super::crtc::Register::LineCompare), 127);
+
+                super::set_status_rows(0);
+                assert_eq!(super::crtc::read(super::crtc::Register::LineCompare), 0xff);
+                super::clear();
+        }
+
+        #[test_case]
+        fn smooth_scroll_ends_on_row()
+        {
+                super::clear();
+                super::set_smooth_scroll(true);
+                for _ in 0..26 {
+                        println!("scroll");
+                }
+                super::set_smooth_scroll(false);
+
+                assert_eq!(
+                        super::crtc::read(super::crtc::Register::PresetRowScan) & 0x1f,
+                        0
+                );
+                assert_eq!(
+                        super::crtc::read(super::crtc::Register::StartAddressLow),
+                        2 * 80
+                );
+                super::clear();
+        }
+
+        #[test_case]
+        fn panic_reset_restores_text_mode()
+        {
+                super::set_mode(TextMode::T90x60);
+                super::set_glyph(b'H', &[0xff; 8]).unwrap();
+                super::_panic_reset();
+
+                let regs = super::dump_registers().unwrap();
+                let bios = super::state::BIOS_STATE.lock();
+                assert_eq!(regs.crtc[0x01], 79);
+                assert_eq!(regs.crtc[0x09] & 0x1f, 15);
+                assert_eq!(regs.misc, bios.registers().unwrap().misc);
+
+                super::clear();
+                print!("\nHello");
+                assert_screen!("../../.assets/basic_a_80_25.txt");
+        }
+
+        #[test_case]
+        fn attributes_reach_hardware()
+        {
+                let mut attr = Attribute::new(VGAColor::Yellow, VGAColor::Red);
+                attr.flags = AttributeFlags::BLINK | AttributeFlags::UNDERLINE;
+
+                super::clear();
+                cprint!(attr, "A");
+                assert_eq!(super::lock().visible()[0], 0xc941);
+                assert_eq!(
+                        super::crtc::read(super::crtc::Register::UnderlineLocation) & 0x1f,
+                        15
+                );
+
+                super::set_bright_background(true);
+                assert_eq!(
+                        super::attr::read(super::attr::Register::ModeControl) & 0x08,
+                        0
+                );
+                super::set_mode(TextMode::T80x25);
+                assert_eq!(
+                        super::attr::read(super::attr::Register::ModeControl) & 0x08,
+                        0
+                );
+                super::set_bright_background(false);
+        }
+
+        #[test_case]
+        fn init_only_runs_once()
+        {
+                let config = Config {
+                        mode: TextMode::T80x50,
+                        ..Config::default()
+                };
+                let regs = super::dump_registers();
+
+                assert!(super::is_ready());
+                unsafe { super::init(&config) };
+                assert_eq!(super::dump_registers(), regs);
+                assert!(super::lock().backend_mut().vga().is_some());
+        }
+
+        #[test_case]
+        fn log_lines_are_timestamped()
+        {
+                super::clear();
+                log!("x");
+
+                let mut logger = super::lock();
+                let cells = logger.visible();
+                let row: [u8; 12] = core::array::from_fn(|i| cells[i] as u8);
+                assert_eq!(row[0], b'[');
+                assert_eq!(row[3], b':');
+                assert_eq!(&row[9..], b"] x");
+        }
+}
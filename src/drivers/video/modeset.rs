@@ -0,0 +1,208 @@
+//! VGA mode setting and font loading.
+//!
+//! A mode is set by loading the full register table of a [`ModeRegisters`]
+//! while the Sequencer is held in reset, in the order given by the FreeVGA
+//! documentation: Miscellaneous Output, Sequencer, CRTC, Graphics Controller
+//! and finally Attribute Controller.
+//!
+//! Text mode fonts live in plane 2, which is hidden from the CPU while the
+//! display runs in odd/even text mode. Loading or saving a font temporarily
+//! switches the Sequencer and Graphics Controller to planar access through
+//! the `0xA0000` window, then restores them.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/vgareg.htm
+
+use core::ptr;
+
+use kfs::vga::MemoryRanges;
+use kfs::vga::font::{GLYPH_SLOT, GLYPHS};
+use kfs::vga::mode::ModeRegisters;
+
+use super::{attr, crtc, gfxc, misc, seq};
+
+/// Physical address of the window used to access plane 2.
+const FONT_WINDOW: usize = 0xA0000;
+
+/// Reads every register loaded by [`set_registers`].
+///
+/// The caller should have exclusive access to the VGA registers, as the
+/// controllers are read through their shared index ports.
+pub(super) fn registers() -> ModeRegisters
+{
+        let mut regs = ModeRegisters {
+                misc: misc::read(),
+                seq:  [0; 5],
+                crtc: [0; 25],
+                gc:   [0; 9],
+                ac:   [0; 21],
+        };
+
+        for (&reg, value) in seq::Register::ALL.iter().zip(regs.seq.iter_mut()) {
+                *value = seq::read(reg);
+        }
+        for (&reg, value) in crtc::Register::ALL.iter().zip(regs.crtc.iter_mut()) {
+                *value = crtc::read(reg);
+        }
+        for (&reg, value) in gfxc::Register::ALL.iter().zip(regs.gc.iter_mut()) {
+                *value = gfxc::read(reg);
+        }
+        let (palette, attributes) = regs.ac.split_at_mut(attr::PALETTE_SIZE as usize);
+        for (index, value) in palette.iter_mut().enumerate() {
+                *value = attr::read_palette(index as u8);
+        }
+        for (&reg, value) in attr::Register::ALL.iter().zip(attributes.iter_mut()) {
+                *value = attr::read(reg);
+        }
+
+        regs
+}
+
+/// Loads every register of `regs`.
+///
+/// Used both to set a mode from its table, and to restore registers saved
+/// by [`registers`].
+///
+/// # Safety
+/// The caller must have exclusive access to the VGA registers, and `regs`
+/// must describe a mode supported by the display.
+pub(super) unsafe fn set_registers(regs: &ModeRegisters)
+{
+        /* Hold the sequencer in synchronous reset while clocks change */
+        seq::write(seq::Register::Reset, 0x01);
+        misc::write(regs.misc);
+        for (&reg, &value) in seq::Register::ALL.iter().zip(regs.seq.iter()).skip(1) {
+                seq::write(reg, value);
+        }
+        seq::write(seq::Register::Reset, regs.seq[0]);
+
+        /* Unlock CRTC registers 0x00 to 0x07 */
+        crtc::write(
+                crtc::Register::HorizontalBlankingEnd,
+                crtc::read(crtc::Register::HorizontalBlankingEnd) | 0x80,
+        );
+        crtc::write(
+                crtc::Register::VerticalRetraceEnd,
+                crtc::read(crtc::Register::VerticalRetraceEnd) & !0x80,
+        );
+        for (&reg, &value) in crtc::Register::ALL.iter().zip(regs.crtc.iter()) {
+                let value = match reg {
+                        crtc::Register::HorizontalBlankingEnd => value | 0x80,
+                        crtc::Register::VerticalRetraceEnd => value & !0x80,
+                        _ => value,
+                };
+                crtc::write(reg, value);
+        }
+        /* Restore the protection bit from the table */
+        crtc::write(crtc::Register::VerticalRetraceEnd, regs.crtc[0x11]);
+
+        for (&reg, &value) in gfxc::Register::ALL.iter().zip(regs.gc.iter()) {
+                gfxc::write(reg, value);
+        }
+
+        for (index, &value) in regs.ac[..attr::PALETTE_SIZE as usize].iter().enumerate() {
+                attr::write_palette(index as u8, value);
+        }
+        for (&reg, &value) in attr::Register::ALL
+                .iter()
+                .zip(regs.ac[attr::PALETTE_SIZE as usize..].iter())
+        {
+                attr::write(reg, value);
+        }
+}
+
+/// Registers changed while plane 2 is accessed.
+struct PlaneAccess
+{
+        map_mask:    u8,
+        memory_mode: u8,
+        read_map:    u8,
+        gfx_mode:    u8,
+        gfx_misc:    u8,
+}
+
+impl PlaneAccess
+{
+        /// Maps plane 2 alone at [`FONT_WINDOW`], returning the registers to
+        /// restore.
+        unsafe fn open() -> Self
+        {
+                let saved = Self {
+                        map_mask:    seq::read(seq::Register::MapMask),
+                        memory_mode: seq::read(seq::Register::MemoryMode),
+                        read_map:    gfxc::read(gfxc::Register::ReadMapSelect),
+                        gfx_mode:    gfxc::read(gfxc::Register::GraphicsMode),
+                        gfx_misc:    gfxc::read(gfxc::Register::Miscellaneous),
+                };
+
+                seq::write(seq::Register::Reset, 0x01);
+                seq::write(seq::Register::MapMask, 0x04);
+                /* Sequential addressing, disable odd/even */
+                seq::write(seq::Register::MemoryMode, saved.memory_mode | 0x04);
+                seq::write(seq::Register::Reset, 0x03);
+
+                gfxc::write(gfxc::Register::ReadMapSelect, 0x02);
+                gfxc::write(gfxc::Register::GraphicsMode, saved.gfx_mode & !0x10);
+                /* Map 0xA0000-0xAFFFF, disable odd/even chaining */
+                gfxc::write(
+                        gfxc::Register::Miscellaneous,
+                        (saved.gfx_misc & 0xf1) | (MemoryRanges::Medium as u8) << 2,
+                );
+
+                saved
+        }
+
+        /// Restores the registers saved by [`PlaneAccess::open`].
+        unsafe fn close(self)
+        {
+                seq::write(seq::Register::Reset, 0x01);
+                seq::write(seq::Register::MapMask, self.map_mask);
+                seq::write(seq::Register::MemoryMode, self.memory_mode);
+                seq::write(seq::Register::Reset, 0x03);
+
+                gfxc::write(gfxc::Register::ReadMapSelect, self.read_map);
+                gfxc::write(gfxc::Register::GraphicsMode, self.gfx_mode);
+                gfxc::write(gfxc::Register::Miscellaneous, self.gfx_misc);
+        }
+}
+
+/// Copies the first font of plane 2, `height` scanlines per glyph, into
+/// `font`.
+///
+/// # Safety
+/// The caller must have exclusive access to the VGA registers and memory.
+pub(super) unsafe fn save_font(
+        font: &mut [u8],
+        height: usize,
+)
+{
+        let access = PlaneAccess::open();
+        for (glyph, dst) in font.chunks_exact_mut(height).take(GLYPHS).enumerate() {
+                let slot = (FONT_WINDOW + glyph * GLYPH_SLOT) as *const u8;
+                for (line, byte) in dst.iter_mut().enumerate() {
+                        *byte = ptr::read_volatile(slot.add(line));
+                }
+        }
+        access.close();
+}
+
+/// Loads `glyphs`, `height` scanlines each, in the first font of plane 2,
+/// starting at character `first`.
+///
+/// # Safety
+/// The caller must have exclusive access to the VGA registers and memory.
+pub(super) unsafe fn load_glyphs(
+        first: usize,
+        glyphs: &[u8],
+        height: usize,
+)
+{
+        let access = PlaneAccess::open();
+        for (glyph, src) in glyphs.chunks_exact(height).take(GLYPHS - first).enumerate() {
+                let slot = (FONT_WINDOW + (first + glyph) * GLYPH_SLOT) as *mut u8;
+                for line in 0..GLYPH_SLOT {
+                        let byte = src.get(line).copied().unwrap_or(0);
+                        ptr::write_volatile(slot.add(line), byte);
+                }
+        }
+        access.close();
+}
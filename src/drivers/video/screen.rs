@@ -0,0 +1,153 @@
+//! Display the console is shown on.
+//!
+//! Until the video driver is initialized, the console writes straight to the
+//! text memory of the mode set up by the BIOS, without touching any register.
+//! It then drives the VGA hardware, or the bootloader framebuffer when one is
+//! available with a font to draw it.
+//! Features specific to the VGA hardware, such as text modes, register dumps
+//! or the DAC palette, are only available through [`Screen::vga`].
+
+use kfs::framebuffer::FramebufferBackend;
+use kfs::vga::CursorTypes;
+use kfs::vga::backend::{RamBackend, TextBackend};
+use kfs::vga::mode::TextMode;
+
+use super::vgac::VgaBackend;
+use crate::drivers::speaker;
+
+/// [`TextBackend`] of the display in use.
+// There is no heap to box the VGA backend, and the console holds a single
+// screen, so the size difference does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub(crate) enum Screen
+{
+        /// 80x25 text memory of the BIOS, before the driver is initialized
+        Early(RamBackend<'static>),
+        /// VGA text mode
+        Vga(VgaBackend),
+        /// Linear framebuffer set up by the bootloader
+        Framebuffer(FramebufferBackend<'static>),
+}
+
+impl Screen
+{
+        /// Returns the VGA backend, if the console is displayed in VGA text
+        /// mode.
+        pub(crate) fn vga(&mut self) -> Option<&mut VgaBackend>
+        {
+                match self {
+                        Screen::Vga(vga) => Some(vga),
+                        Screen::Early(_) | Screen::Framebuffer(_) => None,
+                }
+        }
+
+        fn backend(&mut self) -> &mut dyn TextBackend
+        {
+                match self {
+                        Screen::Early(early) => early,
+                        Screen::Vga(vga) => vga,
+                        Screen::Framebuffer(fb) => fb,
+                }
+        }
+}
+
+impl TextBackend for Screen
+{
+        fn cells(&mut self) -> &mut [u16] { self.backend().cells() }
+
+        fn set_start_address(
+                &mut self,
+                start: u16,
+        )
+        {
+                self.backend().set_start_address(start);
+        }
+
+        fn set_cursor_address(
+                &mut self,
+                pos: u16,
+        )
+        {
+                self.backend().set_cursor_address(pos);
+        }
+
+        fn set_cursor_shape(
+                &mut self,
+                shape: CursorTypes,
+        )
+        {
+                self.backend().set_cursor_shape(shape);
+        }
+
+        fn set_geometry(
+                &mut self,
+                cols: u8,
+                rows: u8,
+        )
+        {
+                self.backend().set_geometry(cols, rows);
+        }
+
+        fn flush(&mut self) { self.backend().flush(); }
+
+        fn set_split(
+                &mut self,
+                rows: u8,
+        )
+        {
+                self.backend().set_split(rows);
+        }
+
+        fn scanlines_per_row(&self) -> u8
+        {
+                match self {
+                        // The row scan register is left to the BIOS.
+                        Screen::Early(_) => 0,
+                        Screen::Vga(vga) => vga.scanlines_per_row(),
+                        Screen::Framebuffer(fb) => fb.scanlines_per_row(),
+                }
+        }
+
+        fn set_row_scan(
+                &mut self,
+                scan: u8,
+        )
+        {
+                self.backend().set_row_scan(scan);
+        }
+
+        fn wait_retrace(&mut self) { self.backend().wait_retrace(); }
+
+        fn set_blink(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.backend().set_blink(enabled);
+        }
+
+        fn set_underline(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.backend().set_underline(enabled);
+        }
+
+        /// Every display shares the PC speaker, rung once the console is
+        /// unlocked.
+        fn bell(&mut self) -> bool
+        {
+                speaker::request_bell();
+                true
+        }
+
+        fn set_text_mode(
+                &mut self,
+                mode: TextMode,
+        )
+        {
+                self.backend().set_text_mode(mode);
+        }
+}
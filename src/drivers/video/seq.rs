@@ -0,0 +1,86 @@
+//! VGA Sequencer access.
+//!
+//! The Sequencer is the VGA hardware block that generates the dot and
+//! character clocks, and decides which memory planes CPU writes reach.
+//!
+//! In text mode it is mainly used to:
+//! - select the character clock width (8 or 9 dots),
+//! - select the plane 2 regions holding the two active fonts,
+//! - open plane 2 to the CPU while a font is loaded.
+//!
+//! This module assumes a VGA-compatible Sequencer accessed through the I/O
+//! port pair `0x3C4`/`0x3C5`:
+//! - `0x3C4`: register index port,
+//! - `0x3C5`: register data port.
+//!
+//! Access is stateful: the register index must be written first, then the
+//! register value is read or written through the data port. Because of this,
+//! Sequencer accesses must not be interleaved with other VGA register
+//! accesses.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/seqreg.htm
+
+use crate::instructions::io::{inb, outb};
+
+const INDEX_PORT: u16 = 0x3C4;
+const DATA_PORT: u16 = 0x3C5;
+
+/// VGA Sequencer register indices.
+///
+/// These values select registers in the Sequencer indexed I/O interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(super) enum Register
+{
+        /// Stops the sequencer while clocks are being changed.
+        Reset              = 0x00,
+        /// Selects 8 or 9 dots characters and blanks the screen.
+        ClockingMode       = 0x01,
+        /// Selects which planes CPU writes are applied to.
+        MapMask            = 0x02,
+        /// Selects the font used for each value of attribute bit 3.
+        CharacterMapSelect = 0x03,
+        /// Controls chain-4, odd/even and extended memory addressing.
+        MemoryMode         = 0x04,
+}
+
+impl Register
+{
+        /// Every register, in index order.
+        pub(super) const ALL: [Register; 5] = [
+                Register::Reset,
+                Register::ClockingMode,
+                Register::MapMask,
+                Register::CharacterMapSelect,
+                Register::MemoryMode,
+        ];
+}
+
+/// Writes `value` to the selected Sequencer register.
+///
+/// # Safety
+/// Callers must ensure that the value being written is valid for the selected
+/// register, cause this value may cause undefined behavior in the VGA hardware
+/// if invalid.
+#[inline(always)]
+pub(super) unsafe fn write(
+        reg: Register,
+        value: u8,
+)
+{
+        outb(INDEX_PORT, reg as u8);
+        outb(DATA_PORT, value);
+}
+
+/// Reads the value of the selected Sequencer register.
+#[inline(always)]
+pub(super) fn read(reg: Register) -> u8
+{
+        // SAFETY: By using predefined register indices we unsure that unsafe functions
+        // are used correctly, and wont be used to write to ports that are not
+        // meant to be accessed.
+        unsafe {
+                outb(INDEX_PORT, reg as u8);
+                inb(DATA_PORT)
+        }
+}
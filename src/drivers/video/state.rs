@@ -0,0 +1,97 @@
+//! Complete VGA state snapshots.
+//!
+//! A [`VgaState`] holds everything a mode switch may overwrite: every
+//! register of the Sequencer, CRT Controller, Graphics Controller and
+//! Attribute Controller, the 256 DAC entries, and the font stored in plane 2.
+//! Text cells are not part of it, the console keeps them in RAM.
+//!
+//! The state the BIOS left is saved once at boot in [`BIOS_STATE`], so that a
+//! known-good text mode can be restored whatever the kernel did to the
+//! registers.
+use kfs::vga::font::{GLYPH_SLOT, GLYPHS};
+use kfs::vga::mode::ModeRegisters;
+use kfs::vga::palette::Rgb;
+
+use super::{dac, modeset};
+use crate::sync::IrqSafeMutex;
+
+/// Number of DAC entries.
+const DAC_SIZE: usize = 256;
+
+/// Size of the first font of plane 2, glyph slots included.
+const FONT_PLANE_SIZE: usize = GLYPHS * GLYPH_SLOT;
+
+/// Hardware state at boot, saved when the VGA backend is created.
+pub(super) static BIOS_STATE: IrqSafeMutex<VgaState> = IrqSafeMutex::new(VgaState::new());
+
+/// Snapshot of the VGA registers, DAC and font.
+pub(crate) struct VgaState
+{
+        /// Whether the state has been saved
+        vs_saved:     bool,
+        /// Every register
+        vs_registers: ModeRegisters,
+        /// DAC palette
+        vs_dac:       [Rgb; DAC_SIZE],
+        /// Font glyphs, [`GLYPH_SLOT`] bytes each
+        vs_font:      [u8; FONT_PLANE_SIZE],
+}
+
+impl VgaState
+{
+        /// Creates an empty state, which restores nothing.
+        pub(super) const fn new() -> Self
+        {
+                Self {
+                        vs_saved:     false,
+                        vs_registers: ModeRegisters {
+                                misc: 0,
+                                seq:  [0; 5],
+                                crtc: [0; 25],
+                                gc:   [0; 9],
+                                ac:   [0; 21],
+                        },
+                        vs_dac:       [Rgb::hex(0); DAC_SIZE],
+                        vs_font:      [0; FONT_PLANE_SIZE],
+                }
+        }
+
+        /// Returns the registers saved, if any.
+        pub(crate) fn registers(&self) -> Option<&ModeRegisters>
+        {
+                self.vs_saved.then_some(&self.vs_registers)
+        }
+
+        /// Reads the current state of the hardware.
+        ///
+        /// # Safety
+        /// The caller must have exclusive access to the VGA registers and
+        /// memory.
+        pub(super) unsafe fn save(&mut self)
+        {
+                self.vs_registers = modeset::registers();
+                for (index, color) in self.vs_dac.iter_mut().enumerate() {
+                        *color = dac::read(index as u8);
+                }
+                modeset::save_font(&mut self.vs_font, GLYPH_SLOT);
+                self.vs_saved = true;
+        }
+
+        /// Loads the saved state back in the hardware, if there is one.
+        ///
+        /// # Safety
+        /// The caller must have exclusive access to the VGA registers and
+        /// memory.
+        pub(super) unsafe fn restore(&self)
+        {
+                if !self.vs_saved {
+                        return;
+                }
+
+                modeset::set_registers(&self.vs_registers);
+                modeset::load_glyphs(0, &self.vs_font, GLYPH_SLOT);
+                for (index, &color) in self.vs_dac.iter().enumerate() {
+                        dac::write(index as u8, color);
+                }
+        }
+}
@@ -1,206 +1,89 @@
-//! TODO: Need to improve some calculations.
-//! TODO: Need to clear mutex lock.
-//! BUG: Panic when scrolling down cause vc_index is sometimes higher than
-//! vc_end_screen.
+//! VGA text mode hardware backend.
 //!
-//! Ideas for implementation:
-//! - Create split screen mode.
-//! - Add helper functions for CRTC and GFX controllers to dump registers.
-use core::fmt;
+//! The console writes to a shadow copy of the text memory, in RAM. Flushes
+//! copy the cells displayed that differ from what VGA memory holds, which a
+//! second copy keeps track of, so that reading slow video memory is never
+//! needed and bulk redraws can be written in one go during the vertical
+//! retrace.
 use core::ptr;
-use core::{cmp, slice};
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use super::{crtc, gfxc};
+use kfs::vga::backend::TextBackend;
+use kfs::vga::font::{self, FONT_8X8_SIZE, FONT_8X16_SIZE, FontError, Psf};
+use kfs::vga::mode::{GraphicsMode, TextMode};
+use kfs::vga::palette::Palette;
+use kfs::vga::{CursorTypes, MemoryRanges};
 
-/// Default 16-bit word for clearing VGA text mode memory.
-/// Represents a space character (0x20) with light gray foreground (0x07).
-/// Format: [15:12]=background color, [11:8]=foreground color, [7:0]=ASCII
-/// character
-const BLANK: u16 = 0x0720;
+use super::state::{BIOS_STATE, VgaState};
+use super::{attr, crtc, dac, gfxc, misc, modeset};
 
-/// Standard 16-color VGA color palette.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub(crate) enum VGAColor
-{
-        Black      = 0x00,
-        Blue       = 0x01,
-        Green      = 0x02,
-        Cyan       = 0x03,
-        Red        = 0x04,
-        Magenta    = 0x05,
-        Brown      = 0x06,
-        LightGray  = 0x07,
-        DarkGray   = 0x08,
-        LightBlue  = 0x09,
-        LightGreen = 0x0a,
-        LightCyan  = 0x0b,
-        LightRed   = 0x0c,
-        Pink       = 0x0d,
-        Yellow     = 0x0e,
-        White      = 0x0f,
-}
-
-/// Types of text mode cursor shapes available in VGA.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub(crate) enum CursorTypes
-{
-        Underline,
-        LowerThird,
-        LowerHalf,
-        Full,
-        None,
-}
+/// Number of text cells of the shadow text memory.
+const SHADOW_CELLS: usize = MemoryRanges::Small.size() as usize / 2;
 
-/// VGA memory mapping ranges.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub(crate) enum MemoryRanges
-{
-        /// A0000h-BFFFFh (128K region)
-        Large  = 0,
-        /// A0000h-AFFFFh (64K region)
-        Medium = 1,
-        /// B8000h-BFFFFh (32K region)
-        Small  = 3,
-}
+/// Text memory written by the console, and the cells VGA memory holds.
+static mut SHADOW: [[u16; SHADOW_CELLS]; 2] = [[0; SHADOW_CELLS]; 2];
 
-/// Standard VGA text mode resolutions.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum Resolution
-{
-        /// 40 columns × 10 rows text mode
-        R40_10,
-        /// 40 columns × 25 rows text mode
-        R40_25,
-        /// 40 columns × 50 rows text mode
-        R40_50,
-        /// 80 columns × 10 rows text mode
-        R80_10,
-        /// 80 columns × 25 rows text mode (most common)
-        R80_25,
-        /// 80 columns × 50 rows text mode
-        R80_50,
-        /// 120 columns × 25 rows text mode
-        R120_25,
-        /// 120 columns × 50 rows text mode
-        R120_50,
-}
+/// Set once [`SHADOW`] is handed over to the backend.
+static SHADOW_TAKEN: AtomicBool = AtomicBool::new(false);
 
-/// Scrolling directions for VGA text mode.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ScrollDir
-{
-        /// Moves the visible_origin up.
-        VisualUp,
-        /// Moves the visible_origin down.
-        VisualDown,
-        /// Moves the index down and if there isn't enough space,
-        /// moves down both the origin and visible_origin.
-        Down,
-        /// Moves the visible_origin to the vram_base.
-        Top,
-        /// Moves the visible_origin to vram_end minus screen_size.
-        Bottom,
-}
+/// Blink Enable bit of the Attribute Mode Control register.
+const BLINK: u8 = 0x08;
 
-/// VGA text mode console driver that provides basic text output functionality
-///
-/// This structure manages a VGA text mode console by maintaining the state of
-/// the video memory and providing methods for text output and scrolling. It
-/// supports standard VGA text mode operations including cursor management,
-/// color control, and scrolling.
+/// [`TextBackend`] driving the VGA hardware.
 ///
-/// # Memory Layout
-///
-/// vc_vram_base ------> +---------------+-.
-///                      |               |  \
-///                      |               |   |
-///                      |               |    > area 1
-///                      |               |   |
-///                      |               |  /
-///                      +---------------+-:
-///                      |               |  \
-/// vc_visible_origin > ^| $> ls         |   |
-///                     || file          |    > area 2
-///           vc_rows  < | file2         |   |
-///                     || $> cat file   |  /
-/// vc_origin --------> |+---------------+-:
-///                     || Hello         |  \
-///                     v| $> uname      |   |
-///                      | Darwin        |    > area 3
-/// vc_index ------------|--------v      |   |
-///                      | $> echo       |  /
-/// vc_origin_end -----> +---------------+-'
-///                      |<-- vc_cols -->|
-///                      .               .
-///                      .               .
-///                      +---------------- <-- vram_end
-#[derive(Debug, Clone, Copy)]
-pub(crate) struct VgaConsole
+/// Text cells are written directly to the mapped video memory, and positions
+/// and cursor changes are forwarded to the CRT Controller.
+#[derive(Debug)]
+pub(crate) struct VgaBackend
 {
         /// Base address of VGA memory
-        vc_vram_base:        u32,
-        /// End address of VGA memory
-        vc_vram_end:         u32,
-        /// Current position in VGA memory where next character will be written
-        vc_index:            u32,
+        vb_vram_base:   u32,
         /// Total size of VGA memory in bytes
-        vc_vram_size:        u32,
-        /// Size of visible screen area in bytes
-        vc_screen_size:      u32,
-        /// Current foreground color for text output
-        vc_foreground_color: VGAColor,
-        /// Current background color for text output
-        vc_background_color: VGAColor,
-        /// Address of the first visible character on screen
-        vc_visible_origin:   u32,
-        /// Starting address of the current text buffer
-        vc_origin:           u32,
-        /// Ending address of the current text buffer
-        vc_origin_end:       u32,
-        /// Number of rows in the display
-        vc_rows:             u8,
-        /// Number of columns in the display
-        vc_cols:             u8,
-        /// Current cursor appearance type
-        vc_cursor_type:      CursorTypes,
+        vb_vram_size:   u32,
+        /// Memory range VGA memory is mapped to
+        vb_range:       MemoryRanges,
+        /// Text memory written by the console
+        vb_cells:       &'static mut [u16],
+        /// Cells written to VGA memory
+        vb_shown:       &'static mut [u16],
+        /// First cell displayed
+        vb_start:       usize,
+        /// Number of character columns displayed
+        vb_cols:        u8,
+        /// Text mode displayed, or restored when leaving graphics
+        vb_mode:        TextMode,
+        /// Number of scanlines of a character
+        vb_char_height: u8,
+        /// Number of character rows displayed
+        vb_rows:        u8,
+        /// Bottom rows displaying the beginning of VGA memory
+        vb_split:       u8,
+        /// 8×16 font loaded by the BIOS, kept to switch back from 8×8 modes
+        vb_font:        [u8; FONT_8X16_SIZE],
+        /// Colors of the 16 text attributes
+        vb_palette:     Palette,
+        /// Attribute bit 7 blinks instead of selecting bright backgrounds
+        vb_blink:       bool,
+        /// Characters with a blue foreground are underlined
+        vb_underline:   bool,
 }
 
-impl VgaConsole
+impl VgaBackend
 {
-        /// Creates a new VGA text mode console with the specified
-        /// configuration.
-        pub(crate) fn new(
-                foreground_color: VGAColor,
-                background_color: VGAColor,
-                resolution: Resolution,
-                memory_range: MemoryRanges,
-                cursor_type: Option<CursorTypes>,
-        ) -> Self
+        /// Maps VGA memory to `memory_range` through the Graphics Controller.
+        ///
+        /// # Panics
+        /// There is a single set of VGA registers, so the backend can only be
+        /// created once.
+        pub(crate) fn new(memory_range: MemoryRanges) -> Self
         {
-                let vram_base = match memory_range {
-                        MemoryRanges::Large | MemoryRanges::Medium => 0xa0000,
-                        MemoryRanges::Small => 0xb8000,
-                };
-
-                let vram_size = match memory_range {
-                        MemoryRanges::Large => 0x20000,
-                        MemoryRanges::Medium => 0xffff,
-                        MemoryRanges::Small => 0x8000,
-                };
-
-                let (cols, rows): (u8, u8) = match resolution {
-                        Resolution::R40_10 => (40, 10),
-                        Resolution::R40_25 => (40, 25),
-                        Resolution::R40_50 => (40, 50),
-                        Resolution::R80_10 => (80, 10),
-                        Resolution::R80_25 => (80, 25),
-                        Resolution::R80_50 => (80, 50),
-                        Resolution::R120_25 => (120, 25),
-                        Resolution::R120_50 => (120, 50),
-                };
+                assert!(
+                        !SHADOW_TAKEN.swap(true, Ordering::AcqRel),
+                        "the VGA backend is already created"
+                );
+                // SAFETY: The backend is created once, before anything else
+                // accesses the VGA registers.
+                unsafe { BIOS_STATE.lock().save() };
 
                 let misc: u8 = gfxc::read(gfxc::Register::Miscellaneous) & 0xf2;
                 unsafe {
@@ -210,330 +93,324 @@ impl VgaConsole
                         );
                 }
 
-                let screen_size: u32 =
-                        rows as u32 * cols as u32 * core::mem::size_of::<u16>() as u32;
-
-                let mut con = Self {
-                        vc_vram_base:        vram_base,
-                        vc_vram_end:         (vram_base + vram_size),
-                        vc_index:            vram_base,
-                        vc_vram_size:        vram_size,
-                        vc_screen_size:      screen_size,
-                        vc_foreground_color: foreground_color,
-                        vc_background_color: background_color,
-                        vc_visible_origin:   vram_base,
-                        vc_origin:           vram_base,
-                        vc_origin_end:       vram_base + screen_size,
-                        vc_rows:             rows,
-                        vc_cols:             cols,
-                        vc_cursor_type:      CursorTypes::None,
+                let mut bios_font = [0; FONT_8X16_SIZE];
+                // SAFETY: The backend is created once, before anything else
+                // accesses the VGA registers.
+                unsafe { modeset::save_font(&mut bios_font, 16) };
+                let palette = core::array::from_fn(|i| dac::read(attr::read_palette(i as u8)));
+                let len = SHADOW_CELLS.min(memory_range.size() as usize / 2);
+                // SAFETY: `SHADOW_TAKEN` ensures the buffers are only borrowed
+                // once.
+                let [cells, shown] = unsafe { &mut *ptr::addr_of_mut!(SHADOW) };
+
+                let mut vga = Self {
+                        vb_vram_base:   memory_range.base(),
+                        vb_vram_size:   memory_range.size(),
+                        vb_range:       memory_range,
+                        vb_cells:       &mut cells[..len],
+                        vb_shown:       &mut shown[..len],
+                        vb_start:       0,
+                        vb_cols:        80,
+                        vb_mode:        TextMode::T80x25,
+                        vb_char_height: 16,
+                        vb_rows:        25,
+                        vb_split:       0,
+                        vb_font:        bios_font,
+                        vb_palette:     palette,
+                        vb_blink:       attr::read(attr::Register::ModeControl) & BLINK != 0,
+                        vb_underline:   false,
                 };
-
-                con.blank();
-                con.cursor(cursor_type);
-                con.resize(cols, rows);
-
-                con
+                vga.invalidate();
+                vga
         }
 
-        /// Updates the CRT Controller's Start Address registers to set the
-        /// visible_origin
-        #[inline(always)]
-        fn set_mem_start(&mut self)
+        /// Forces every cell to be written to VGA memory on the next flush.
+        fn invalidate(&mut self)
         {
-                /*
-                 * The value if divided by 2 cause the Start Offset in CRT Controller
-                 * represent the offset between vram_base and the origin in word (2
-                 * bytes).
-                 */
-                let start: u16 = ((self.vc_visible_origin - self.vc_vram_base as u32) / 2) as _;
-
-                unsafe {
-                        crtc::write(crtc::Register::StartAddressLow, start as u8);
-                        crtc::write(crtc::Register::StartAddressHigh, (start >> 8) as u8)
+                for (shown, &cell) in self.vb_shown.iter_mut().zip(self.vb_cells.iter()) {
+                        *shown = !cell;
                 }
         }
 
-        /// Computes the position of the beginning of the line where the index
-        /// is located.
-        #[inline(always)]
-        fn start_of_line(
+        /// Writes the cells of `range` that changed to VGA memory.
+        fn flush_range(
                 &mut self,
-                mut pos: u32,
-        ) -> u32
+                range: core::ops::Range<usize>,
+        )
         {
-                pos -= self.vc_vram_base;
-                pos - (pos % (self.vc_cols as u32 * 2)) + self.vc_vram_base
+                let vram = self.vb_vram_base as *mut u16;
+                let end = range.end.min(self.vb_cells.len());
+
+                for i in range.start.min(end)..end {
+                        let cell = self.vb_cells[i];
+
+                        if self.vb_shown[i] != cell {
+                                // SAFETY: The memory range has been mapped by `new`
+                                // and holds at least as many cells as the shadow.
+                                unsafe { ptr::write_volatile(vram.add(i), cell) };
+                                self.vb_shown[i] = cell;
+                        }
+                }
         }
 
-        /// Writes a single character to the VGA text buffer using default
-        /// colors
-        #[inline(always)]
-        fn putc(
+        /// Sets the VGA text mode cursor size by configuring its start and end
+        /// scan lines
+        fn cursor_size(
                 &mut self,
-                c: u8,
+                from: u8,
+                to: u8,
         )
         {
-                self.cputc(c, None, None);
+                let mut c_start: u8 = crtc::read(crtc::Register::CursorStart);
+                let mut c_end: u8 = crtc::read(crtc::Register::CursorEnd);
+
+                c_start = (c_start & 0xc0) | from;
+                c_end = (c_end & 0xe0) | to;
+
+                unsafe {
+                        crtc::write(crtc::Register::CursorStart, c_start);
+                        crtc::write(crtc::Register::CursorEnd, c_end);
+                }
         }
 
-        /// Writes a single character to the VGA text buffer with optional
-        /// custom colors
-        fn cputc(
-                &mut self,
-                c: u8,
-                foreground: Option<u8>,
-                background: Option<u8>,
-        )
+        /// Returns the number of scanlines the CRTC counts for `rows` character
+        /// rows.
+        fn scanlines(
+                &self,
+                rows: u8,
+        ) -> u32
         {
-                let bg_color = background.unwrap_or(self.vc_background_color as u8) & 0xf;
-                let fg_color = foreground.unwrap_or(self.vc_foreground_color as u8) & 0xf;
-                let word = (c as u16) | ((bg_color as u16) << 12) | ((fg_color as u16) << 8);
+                let mut scanlines: u32 = rows as u32 * self.vb_char_height as u32;
 
-                if self.vc_index == self.vc_origin_end {
-                        self.scroll(ScrollDir::Down, Some(1));
+                /* If Scan Doubling enabled, 200-scan-line video data is converted to
+                 * 400-scan-line output */
+                let max_scan: u8 = crtc::read(crtc::Register::MaximumScanLine);
+                if (max_scan & 0x80) != 0 {
+                        scanlines <<= 1;
                 }
 
+                /* If SLDIV enabled, divide scan line clock by 2 */
+                let mode = crtc::read(crtc::Register::ModeControl);
+                if (mode & 0x04) != 0 {
+                        scanlines >>= 1;
+                }
+                scanlines
+        }
+
+        /// Loads the font the BIOS had set up back, halved for 8 scanlines
+        /// modes.
+        pub(crate) fn restore_bios_font(&mut self)
+        {
+                let mut half = [0; FONT_8X8_SIZE];
+
+                // SAFETY: The backend owns the VGA hardware.
                 unsafe {
-                        *(self.vc_index as *mut u16) = word;
+                        match self.vb_char_height {
+                                16 => modeset::load_glyphs(0, &self.vb_font, 16),
+                                _ => {
+                                        font::halve(&self.vb_font, &mut half);
+                                        modeset::load_glyphs(0, &half, 8);
+                                }
+                        }
+                }
+        }
+
+        /// Replaces the displayed font with `font`.
+        ///
+        /// The font must have as many scanlines as the current text mode
+        /// characters. Setting a mode loads the BIOS font back.
+        pub(crate) fn load_font(
+                &mut self,
+                font: &Psf,
+        ) -> Result<(), FontError>
+        {
+                if font.height() != self.vb_char_height {
+                        return Err(FontError::HeightMismatch);
                 }
 
-                self.vc_index += core::mem::size_of::<u16>() as u32;
-                self.cursor(None);
+                // SAFETY: The backend owns the VGA hardware.
+                unsafe { modeset::load_glyphs(0, font.text_glyphs(), font.height() as usize) };
+                Ok(())
         }
 
-        /// Writes a string to the VGA text buffer using default colors
-        #[inline(always)]
-        fn putstr(
+        /// Redefines the glyph of character `c`, one byte per scanline.
+        pub(crate) fn set_glyph(
                 &mut self,
-                str: &str,
-        )
+                c: u8,
+                bitmap: &[u8],
+        ) -> Result<(), FontError>
         {
-                self.cputstr(str, None, None);
+                if bitmap.len() != self.vb_char_height as usize {
+                        return Err(FontError::HeightMismatch);
+                }
+
+                // SAFETY: The backend owns the VGA hardware.
+                unsafe { modeset::load_glyphs(c as usize, bitmap, bitmap.len()) };
+                Ok(())
         }
 
-        /// Writes a string to the VGA text buffer with optional custom colors
-        fn cputstr(
+        /// Returns the font the BIOS had set up.
+        pub(crate) fn bios_font(&self) -> Psf<'_>
+        {
+                Psf::from_glyphs(&self.vb_font, 16).expect("the BIOS font is 8x16")
+        }
+
+        /// Switches the display to the graphics `mode`, with a black screen,
+        /// saving the text mode state in `text`.
+        ///
+        /// VGA memory is overwritten, [`leave_graphics`] restores the text
+        /// mode registers, DAC and font, and the text cells are written back
+        /// on the next flush.
+        ///
+        /// [`leave_graphics`]: Self::leave_graphics
+        pub(crate) fn enter_graphics(
                 &mut self,
-                str: &str,
-                foreground: Option<u8>,
-                background: Option<u8>,
+                mode: GraphicsMode,
+                text: &mut VgaState,
         )
         {
-                for byte in str.bytes() {
-                        match byte {
-                                // b'\n' => self.scroll(ScrollDir::NewLine, None),
-                                b'\n' => {
-                                        self.scroll(ScrollDir::Down, Some(1));
-                                }
-                                0x20..=0x7e => self.cputc(byte, foreground, background),
-                                _ => self.cputc(0xfe, None, None),
-                        };
+                // SAFETY: The backend owns the VGA hardware, and the tables of
+                // `mode` describe standard VGA timings.
+                unsafe {
+                        text.save();
+                        modeset::set_registers(mode.registers());
                 }
+                let palette = self.vb_palette;
+                self.show_palette(&palette);
         }
 
-        /// Scrolls the VGA text buffer in the specified direction
-        fn scroll(
+        /// Switches the display back to the text mode saved by
+        /// [`enter_graphics`](Self::enter_graphics).
+        pub(crate) fn leave_graphics(
                 &mut self,
-                dir: ScrollDir,
-                lines: Option<u32>,
+                text: &VgaState,
         )
         {
-                if let Some(lines) = lines {
-                        debug_assert!(lines > 0);
-                        if lines > self.vc_rows as u32 / 2 {
-                                return;
-                        }
+                // SAFETY: The backend owns the VGA hardware.
+                unsafe { text.restore() };
+                self.invalidate();
+        }
+
+        /// Loads the state the BIOS had set up back, and switches to the
+        /// 80x25 text mode.
+        ///
+        /// This undoes anything done to the registers, in case they were
+        /// left in an unusable state.
+        pub(crate) fn restore_bios_state(&mut self)
+        {
+                // SAFETY: The backend owns the VGA hardware. The state is only
+                // locked while being saved, once.
+                if let Some(bios) = BIOS_STATE.try_lock() {
+                        unsafe { bios.restore() };
                 }
+                self.set_text_mode(TextMode::T80x25);
+                self.invalidate();
+        }
 
-                debug_assert!(self.vc_index <= self.vc_origin_end);
+        /// Returns the colors of the 16 text attributes.
+        pub(crate) fn palette(&self) -> &Palette { &self.vb_palette }
 
-                let mut delta: u32 = lines.unwrap_or(0).saturating_mul((self.vc_cols * 2) as u32);
-                let oldo: u32 = self.vc_origin;
-                match dir {
-                        ScrollDir::VisualUp if lines.is_some() => {
-                                self.vc_visible_origin = cmp::max(
-                                        self.vc_visible_origin.saturating_sub(delta),
-                                        self.vc_vram_base,
-                                );
-                        }
-                        ScrollDir::VisualDown if lines.is_some() => {
-                                self.vc_visible_origin = cmp::min(
-                                        self.vc_visible_origin.saturating_add(delta),
-                                        self.vc_origin,
-                                );
-                        }
-                        ScrollDir::Down if lines.is_some() => {
-                                let oldi = self.vc_index;
-                                if self.vc_origin_end - self.vc_index > delta {
-                                        // If there is enough space to scroll down.
-                                        self.vc_index = self.start_of_line(self.vc_index + delta)
-                                } else {
-                                        // Otherwise, move the cursor to the end of the screen.
-                                        self.vc_index = self.start_of_line(self.vc_origin_end)
-                                };
-
-                                // Substract from delta the number of character already present on
-                                // screen.
-                                delta -= self.vc_index - self.start_of_line(oldi);
-
-                                // Ensure that delta is a multiple of the screen size.
-                                debug_assert!(delta % (self.vc_cols as u32 * 2) == 0);
-
-                                // If the buffer doesnt have enough place to scroll. Copy the screen
-                                // to the beginning of the buffer
-                                // and empty it.
-                                if self.vc_origin_end + delta > self.vc_vram_end {
-                                        unsafe {
-                                                ptr::copy(
-                                                        self.vc_origin as *mut u8,
-                                                        self.vc_vram_base as *mut u8,
-                                                        (self.vc_screen_size.saturating_sub(delta))
-                                                                as usize,
-                                                );
-                                        }
-                                        self.vc_origin = self.vc_vram_base;
-                                } else {
-                                        // Otherwise just move the current
-                                        self.vc_origin += delta;
-                                }
-                                // TEST: THIS
-                                self.vc_index = self
-                                        .vc_index
-                                        .saturating_sub(oldo)
-                                        .saturating_sub(delta)
-                                        .saturating_add(self.vc_origin);
-                                self.vc_origin_end = self.vc_origin + self.vc_screen_size;
-                                self.vc_visible_origin = self.vc_origin;
-                                unsafe {
-                                        let s: &mut [u16] = slice::from_raw_parts_mut(
-                                                (self.vc_origin as *mut u16).add(((self
-                                                        .vc_screen_size
-                                                        - delta)
-                                                        / 2)
-                                                        as usize),
-                                                delta as usize,
-                                        );
-                                        s.fill(BLANK);
-                                }
-                        }
-                        ScrollDir::Bottom => {
-                                self.vc_visible_origin = self.vc_origin;
-                        }
-                        ScrollDir::Top => {
-                                self.vc_visible_origin = self.vc_vram_base;
-                        }
-                        _ => {
-                                panic!("VGA Error: Unknown scroll direction")
-                        }
+        /// Changes the colors of the 16 text attributes.
+        ///
+        /// Attribute `n` is mapped to DAC entry `n`, so the palette is kept
+        /// across mode changes.
+        pub(crate) fn set_palette(
+                &mut self,
+                palette: &Palette,
+        )
+        {
+                for index in 0..attr::PALETTE_SIZE {
+                        // SAFETY: `index` is a valid palette register.
+                        unsafe { attr::write_palette(index, index) };
                 }
-                self.set_mem_start();
-                self.cursor(Some(CursorTypes::Full));
+                self.vb_palette = *palette;
+                self.show_palette(palette);
         }
 
-        /// Clears the entire VGA text buffer by filling it with blank
-        /// characters
-        fn blank(&mut self)
+        /// Displays `palette` without changing the attribute colors, used for
+        /// transitions.
+        pub(crate) fn show_palette(
+                &mut self,
+                palette: &Palette,
+        )
         {
-                unsafe {
-                        let s: &mut [u16] = slice::from_raw_parts_mut(
-                                self.vc_vram_base as *mut u16,
-                                self.vc_vram_size as usize,
-                        );
-                        s.fill(BLANK);
+                for (index, &color) in palette.iter().enumerate() {
+                        dac::write(index as u8, color);
                 }
-                self.vc_origin = self.vc_vram_base;
-                self.vc_visible_origin = self.vc_vram_base;
-                self.vc_index = self.vc_vram_base;
-
-                self.set_mem_start();
-                self.cursor(None);
         }
 
-        /// Sets the VGA text mode cursor size by configuring its start and end
-        /// scan lines
-        fn cursor_size(
+        fn size(&self) -> u32 { self.vb_vram_size }
+}
+
+impl TextBackend for VgaBackend
+{
+        fn cells(&mut self) -> &mut [u16] { self.vb_cells }
+
+        /// Updates the CRT Controller's Start Address registers, once the
+        /// cells they make visible are in VGA memory.
+        fn set_start_address(
                 &mut self,
-                from: u8,
-                to: u8,
+                start: u16,
         )
         {
-                let mut c_start: u8 = crtc::read(crtc::Register::CursorStart);
-                let mut c_end: u8 = crtc::read(crtc::Register::CursorEnd);
-
-                c_start = (c_start & 0xc0) | from;
-                c_end = (c_end & 0xe0) | to;
+                self.vb_start = start as usize;
+                self.flush();
 
+                misc::wait_vertical_display();
                 unsafe {
-                        crtc::write(crtc::Register::CursorStart, c_start);
-                        crtc::write(crtc::Register::CursorEnd, c_end);
+                        crtc::write(crtc::Register::StartAddressLow, start as u8);
+                        crtc::write(crtc::Register::StartAddressHigh, (start >> 8) as u8)
                 }
         }
 
-        /// Updates the hardware cursor position and optionally changes its
-        /// appearance
-        fn cursor(
+        fn set_cursor_address(
                 &mut self,
-                cursor_type: Option<CursorTypes>,
+                pos: u16,
         )
         {
-                let pos = (self.vc_index as u32 - self.vc_vram_base) / 2;
+                self.flush();
+
                 unsafe {
                         crtc::write(crtc::Register::CursorLocationLow, pos as u8);
                         crtc::write(crtc::Register::CursorLocationHigh, (pos >> 8) as u8);
                 }
+        }
 
-                if cursor_type.is_some() {
-                        const CURSOR_ENABLE_MASK: u8 = 0xdf;
-                        const CURSOR_DISABLE_MASK: u8 = 0x20;
-                        let c = crtc::read(crtc::Register::CursorStart);
-
-                        if self.vc_cursor_type == CursorTypes::None {
-                                unsafe {
-                                        crtc::write(
-                                                crtc::Register::CursorStart,
-                                                c & CURSOR_ENABLE_MASK,
-                                        );
-                                }
-                        }
+        fn set_cursor_shape(
+                &mut self,
+                shape: CursorTypes,
+        )
+        {
+                const CURSOR_ENABLE_MASK: u8 = 0xdf;
+                const CURSOR_DISABLE_MASK: u8 = 0x20;
+                let c = crtc::read(crtc::Register::CursorStart);
 
-                        match cursor_type.unwrap() {
-                                CursorTypes::Full => self.cursor_size(0, 16),
-                                CursorTypes::LowerHalf => self.cursor_size(8, 16),
-                                CursorTypes::LowerThird => self.cursor_size(10, 16),
-                                CursorTypes::Underline => self.cursor_size(15, 16),
-                                CursorTypes::None => unsafe {
-                                        crtc::write(
-                                                crtc::Register::CursorStart,
-                                                c | CURSOR_DISABLE_MASK,
-                                        )
-                                },
+                if shape != CursorTypes::None {
+                        unsafe {
+                                crtc::write(crtc::Register::CursorStart, c & CURSOR_ENABLE_MASK);
                         }
                 }
+
+                let h = self.vb_char_height;
+                match shape {
+                        CursorTypes::Full => self.cursor_size(0, h),
+                        CursorTypes::LowerHalf => self.cursor_size(h / 2, h),
+                        CursorTypes::LowerThird => self.cursor_size(h * 5 / 8, h),
+                        CursorTypes::Underline => self.cursor_size(h - 1, h),
+                        CursorTypes::None => unsafe {
+                                crtc::write(crtc::Register::CursorStart, c | CURSOR_DISABLE_MASK)
+                        },
+                }
         }
 
         /// Resizes the VGA text mode display to the specified dimensions
-        fn resize(
+        fn set_geometry(
                 &mut self,
                 width: u8,
                 height: u8,
         )
         {
-                let mut scanlines: u32 = height as u32 * 16;
-
-                /* If Scan Doubling enabled, 200-scan-line video data is converted to
-                 * 400-scan-line output */
-                let max_scan: u8 = crtc::read(crtc::Register::MaximumScanLine);
-                if (max_scan & 0x80) != 0 {
-                        scanlines <<= 1;
-                }
-
-                /* If SLDIV enabled, divide scan line clock by 2 */
-                let mode = crtc::read(crtc::Register::ModeControl);
-                if (mode & 0x04) != 0 {
-                        scanlines >>= 1;
-                }
-                scanlines -= 1;
+                let scanlines = self.scanlines(height) - 1;
                 let scanlines_lo = scanlines & 0xff;
 
                 /*
@@ -563,34 +440,129 @@ impl VgaConsole
                         crtc::write(crtc::Register::VerticalRetraceEnd, vsync_end);
                 }
 
-                self.vc_cols = width;
-                self.vc_rows = height;
+                self.vb_cols = width;
+                self.vb_rows = height;
+                self.set_split(self.vb_split);
         }
 
-        fn base_as_ptr(&self) -> *const () { self.vc_vram_base as *const () }
+        /// Programs every VGA register for `mode` and loads the BIOS font
+        /// at the matching height.
+        fn set_text_mode(
+                &mut self,
+                mode: TextMode,
+        )
+        {
+                // SAFETY: The backend owns the VGA hardware, and the tables of
+                // `mode` describe standard VGA timings.
+                unsafe {
+                        modeset::set_registers(&mode.registers().with_memory_map(self.vb_range));
+                }
+                self.vb_mode = mode;
+                self.vb_char_height = mode.char_height();
+                (self.vb_cols, self.vb_rows) = mode.dimensions();
+                self.set_split(self.vb_split);
+                self.restore_bios_font();
+                let palette = self.vb_palette;
+                self.set_palette(&palette);
+                self.set_blink(self.vb_blink);
+                self.set_underline(self.vb_underline);
+        }
 
-        fn size(&self) -> u32 { self.vc_vram_size }
-}
+        /// Programs the Line Compare scanline, after which the CRTC displays
+        /// VGA memory from its beginning.
+        ///
+        /// The 10-bit value is spread over the Line Compare register, bit 4
+        /// of Overflow and bit 6 of Maximum Scan Line. Its maximum disables
+        /// the split.
+        fn set_split(
+                &mut self,
+                rows: u8,
+        )
+        {
+                let line = match rows {
+                        0 => 0x3ff,
+                        _ => self.scanlines(self.vb_rows.saturating_sub(rows)) - 1,
+                };
+                let overflow = crtc::read(crtc::Register::Overflow) & !0x10;
+                let max_scan = crtc::read(crtc::Register::MaximumScanLine) & !0x40;
 
-/// Implements the [`core::fmt::Write`] trait for [`VgaConsole`], allowing it to
-/// be used with Rust's formatting macros like `write!` and `writeln!`.
-impl fmt::Write for VgaConsole
-{
-        fn write_str(
+                // SAFETY: The backend owns the VGA hardware. Bit 4 of Overflow
+                // stays writable when the CRTC registers are protected.
+                unsafe {
+                        crtc::write(crtc::Register::LineCompare, line as u8);
+                        crtc::write(
+                                crtc::Register::Overflow,
+                                overflow | ((line >> 4) & 0x10) as u8,
+                        );
+                        crtc::write(
+                                crtc::Register::MaximumScanLine,
+                                max_scan | ((line >> 3) & 0x40) as u8,
+                        );
+                }
+                self.vb_split = rows;
+        }
+
+        /// Copies the cells displayed, above and below the split, to VGA
+        /// memory.
+        fn flush(&mut self)
+        {
+                let cols = self.vb_cols as usize;
+                let split = self.vb_split.min(self.vb_rows) as usize;
+                let scrolled = (self.vb_rows as usize - split) * cols;
+
+                self.flush_range(self.vb_start..self.vb_start + scrolled);
+                self.flush_range(0..split * cols);
+        }
+
+        fn scanlines_per_row(&self) -> u8 { self.vb_char_height }
+
+        /// Sets the Preset Row Scan field, keeping the Byte Panning bits.
+        fn set_row_scan(
                 &mut self,
-                s: &str,
-        ) -> fmt::Result
+                scan: u8,
+        )
         {
-                self.putstr(s);
-                Ok(())
+                let preset = crtc::read(crtc::Register::PresetRowScan) & 0xe0;
+
+                // SAFETY: The backend owns the VGA hardware.
+                unsafe { crtc::write(crtc::Register::PresetRowScan, preset | (scan & 0x1f)) };
+        }
+
+        fn wait_retrace(&mut self) { misc::wait_vertical_retrace(); }
+
+        /// Toggles the Blink Enable bit of the Attribute Mode Control
+        /// register.
+        fn set_blink(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                let mode = attr::read(attr::Register::ModeControl) & !BLINK;
+                let blink = match enabled {
+                        true => BLINK,
+                        false => 0,
+                };
+
+                // SAFETY: The backend owns the VGA hardware.
+                unsafe { attr::write(attr::Register::ModeControl, mode | blink) };
+                self.vb_blink = enabled;
         }
 
-        fn write_char(
+        /// Moves the underline to the last scanline of the characters, or
+        /// past them to hide it.
+        fn set_underline(
                 &mut self,
-                c: char,
-        ) -> fmt::Result
+                enabled: bool,
+        )
         {
-                self.putc(c as u8);
-                Ok(())
+                let location = crtc::read(crtc::Register::UnderlineLocation) & !0x1f;
+                let line = match enabled {
+                        true => self.vb_char_height - 1,
+                        false => 0x1f,
+                };
+
+                // SAFETY: The backend owns the VGA hardware.
+                unsafe { crtc::write(crtc::Register::UnderlineLocation, location | line) };
+                self.vb_underline = enabled;
         }
 }
@@ -0,0 +1,604 @@
+//! Text console on a linear framebuffer.
+//!
+//! [`FramebufferBackend`] is a [`TextBackend`] for pixel displays such as
+//! the one set up by the bootloader: the console writes text cells to a
+//! memory buffer as usual, and the backend draws the cells that changed with
+//! a bitmap font whenever the cursor or the start address is updated.
+//!
+//! Scrolling by whole lines moves the pixels already drawn instead of drawing
+//! every glyph again.
+//!
+//! The backend is also a [`Surface`], whose colors are the 16 attribute
+//! colors, so the same drawing code runs on VGA graphics modes and on the
+//! framebuffer. [`FramebufferBackend::redraw`] brings the text back.
+use crate::multiboot::{FramebufferInfo, FramebufferType, PixelFormat};
+use crate::vga::CursorTypes;
+use crate::vga::backend::TextBackend;
+use crate::vga::font::{FontError, Psf};
+use crate::vga::graphics::{CHAR_WIDTH, Surface};
+use crate::vga::palette::{self, Palette, Rgb};
+use crate::vga::text::BLANK;
+
+/// Text backend drawing cells on a direct color framebuffer.
+#[derive(Debug)]
+pub struct FramebufferBackend<'a>
+{
+        pixels:          &'a mut [u8],
+        pitch:           usize,
+        width:           usize,
+        height:          usize,
+        bytes_per_pixel: usize,
+        format:          PixelFormat,
+        font:            Psf<'a>,
+        /// Palette encoded in the framebuffer pixel format
+        colors:          [u32; 16],
+        /// Text memory written by the console
+        cells:           &'a mut [u16],
+        /// Cells currently drawn, in screen order
+        shown:           &'a mut [u16],
+        /// Screen cell the cursor is drawn on
+        shown_cursor:    Option<usize>,
+        start:           usize,
+        cursor:          usize,
+        shape:           CursorTypes,
+        cols:            u8,
+        rows:            u8,
+        /// Bottom rows showing the beginning of the cells
+        split:           u8,
+        /// Bit 7 of the attribute is the blink bit, drawn as steady text
+        blink:           bool,
+        /// Characters with a blue foreground are underlined
+        underline:       bool,
+}
+
+impl<'a> FramebufferBackend<'a>
+{
+        /// Creates a backend drawing on `pixels`, laid out as described by
+        /// `info`, with `cells` as text memory.
+        ///
+        /// `shown` keeps track of the cells drawn, it must be at least as
+        /// large as the screen in characters. Returns `None` if the
+        /// framebuffer is not direct color with 16, 24 or 32 bits per pixel.
+        pub fn new(
+                info: &FramebufferInfo,
+                pixels: &'a mut [u8],
+                font: Psf<'a>,
+                cells: &'a mut [u16],
+                shown: &'a mut [u16],
+        ) -> Option<Self>
+        {
+                let FramebufferType::Rgb(format) = info.kind else {
+                        return None;
+                };
+                if !matches!(info.bpp, 16 | 24 | 32) {
+                        return None;
+                }
+
+                let mut fb = Self {
+                        pixels,
+                        pitch: info.pitch as usize,
+                        width: info.width as usize,
+                        height: info.height as usize,
+                        bytes_per_pixel: info.bpp as usize / 8,
+                        format,
+                        font,
+                        colors: [0; 16],
+                        cells,
+                        shown,
+                        shown_cursor: None,
+                        start: 0,
+                        cursor: 0,
+                        shape: CursorTypes::None,
+                        cols: 0,
+                        rows: 0,
+                        split: 0,
+                        blink: true,
+                        underline: false,
+                };
+                fb.set_palette(&palette::DEFAULT.palette);
+                let (cols, rows) = fb.max_geometry();
+                fb.set_geometry(cols, rows);
+
+                Some(fb)
+        }
+
+        /// Returns the largest number of columns and rows the framebuffer can
+        /// display with the current font.
+        pub fn max_geometry(&self) -> (u8, u8)
+        {
+                let cols = self.width / CHAR_WIDTH;
+                let rows = (self.height / self.font.height() as usize).min(self.shown.len() / cols);
+
+                (
+                        cols.min(u8::MAX as usize) as u8,
+                        rows.min(u8::MAX as usize) as u8,
+                )
+        }
+
+        /// Returns the font used to draw the cells.
+        pub fn font(&self) -> Psf<'a> { self.font }
+
+        /// Draws every cell again, after the pixels were drawn over.
+        pub fn redraw(&mut self)
+        {
+                self.pixels.fill(0);
+                self.shown_cursor = None;
+                self.invalidate();
+                self.flush();
+        }
+
+        /// Changes the colors of the 16 text attributes.
+        pub fn set_palette(
+                &mut self,
+                palette: &Palette,
+        )
+        {
+                self.colors = palette.map(|color| self.encode(color));
+                self.invalidate();
+                self.flush();
+        }
+
+        /// Replaces the font, which must have the same height as the current
+        /// one.
+        pub fn set_font(
+                &mut self,
+                font: Psf<'a>,
+        ) -> Result<(), FontError>
+        {
+                if font.height() != self.font.height() {
+                        return Err(FontError::HeightMismatch);
+                }
+
+                self.font = font;
+                self.invalidate();
+                self.flush();
+                Ok(())
+        }
+
+        /// Converts `color` to the framebuffer pixel format.
+        fn encode(
+                &self,
+                color: Rgb,
+        ) -> u32
+        {
+                let component = |value: u8, (position, size): (u8, u8)| {
+                        (value as u32 >> 8u8.saturating_sub(size)) << position
+                };
+
+                component(color.r, self.format.red)
+                        | component(color.g, self.format.green)
+                        | component(color.b, self.format.blue)
+        }
+
+        /// Forces every cell to be drawn on the next flush.
+        fn invalidate(&mut self)
+        {
+                for i in 0..self.shown.len() {
+                        self.shown[i] = !self.cell_at(i);
+                }
+        }
+
+        /// Returns the scanlines covered by the cursor.
+        fn cursor_lines(&self) -> core::ops::Range<usize>
+        {
+                let h = self.font.height() as usize;
+
+                match self.shape {
+                        CursorTypes::Full => 0..h,
+                        CursorTypes::LowerHalf => h / 2..h,
+                        CursorTypes::LowerThird => h * 5 / 8..h,
+                        CursorTypes::Underline => h - 1..h,
+                        CursorTypes::None => 0..0,
+                }
+        }
+
+        /// Draws `cell` at screen position `pos`.
+        fn draw_cell(
+                &mut self,
+                pos: usize,
+                cell: u16,
+                cursor: bool,
+        )
+        {
+                let cols = self.cols as usize;
+                let h = self.font.height() as usize;
+                let (x, y) = (pos % cols * CHAR_WIDTH, pos / cols * h);
+                let bg_mask = match self.blink {
+                        true => 0x7,
+                        false => 0xf,
+                };
+                let fg = self.colors[(cell >> 8) as usize & 0xf];
+                let bg = self.colors[(cell >> 12) as usize & bg_mask];
+                let underline = match self.underline && (cell >> 8) & 0x7 == 0x1 {
+                        true => h - 1,
+                        false => usize::MAX,
+                };
+                let cursor_lines = match cursor {
+                        true => self.cursor_lines(),
+                        false => 0..0,
+                };
+                let Some(glyph) = self.font.glyph((cell & 0xff) as usize) else {
+                        return;
+                };
+                let bpp = self.bytes_per_pixel;
+
+                for (line, &bits) in glyph.iter().enumerate() {
+                        let bits = match line == underline {
+                                true => 0xff,
+                                false => bits,
+                        };
+                        let bits = match cursor_lines.contains(&line) {
+                                true => !bits,
+                                false => bits,
+                        };
+                        let row = (y + line) * self.pitch + x * bpp;
+
+                        for col in 0..CHAR_WIDTH {
+                                let color = match bits & (0x80 >> col) {
+                                        0 => bg,
+                                        _ => fg,
+                                };
+                                let pixel = row + col * bpp;
+                                self.pixels[pixel..pixel + bpp]
+                                        .copy_from_slice(&color.to_le_bytes()[..bpp]);
+                        }
+                }
+        }
+
+        /// Draws the cells that changed since the last flush, and the cursor.
+        pub fn flush(&mut self)
+        {
+                let screen = self.cols as usize * self.rows as usize;
+                let scrolled = self.cols as usize * self.scrolled_rows();
+                let cursor = self
+                        .cursor
+                        .checked_sub(self.start)
+                        .filter(|&pos| pos < scrolled);
+
+                for pos in 0..screen {
+                        let cell = self.cell_at(pos);
+                        let is_cursor = cursor == Some(pos);
+
+                        if cell != self.shown[pos] || is_cursor || self.shown_cursor == Some(pos) {
+                                self.draw_cell(pos, cell, is_cursor);
+                                self.shown[pos] = cell;
+                        }
+                }
+                self.shown_cursor = cursor;
+        }
+
+        /// Number of rows above the split, following the start address.
+        fn scrolled_rows(&self) -> usize { self.rows.saturating_sub(self.split) as usize }
+
+        /// Returns the cell displayed at screen position `pos`.
+        fn cell_at(
+                &self,
+                pos: usize,
+        ) -> u16
+        {
+                let scrolled = self.cols as usize * self.scrolled_rows();
+                let index = match pos.checked_sub(scrolled) {
+                        Some(split_pos) => split_pos,
+                        None => self.start + pos,
+                };
+
+                self.cells.get(index).copied().unwrap_or(BLANK)
+        }
+
+        /// Moves the drawn pixels above the split by `lines` text lines, up if
+        /// positive.
+        fn shift(
+                &mut self,
+                lines: isize,
+        )
+        {
+                let cols = self.cols as usize;
+                let screen = cols * self.scrolled_rows();
+                let count = lines.unsigned_abs();
+                let line_bytes = self.pitch * self.font.height() as usize;
+                let moved = (self.scrolled_rows() - count) * line_bytes;
+                let (cells, bytes) = (count * cols, count * line_bytes);
+
+                if lines > 0 {
+                        self.pixels.copy_within(bytes..bytes + moved, 0);
+                        self.shown.copy_within(cells..screen, 0);
+                        self.shown_cursor = self.shown_cursor.and_then(|c| c.checked_sub(cells));
+                } else {
+                        self.pixels.copy_within(0..moved, bytes);
+                        self.shown.copy_within(0..screen - cells, cells);
+                        self.shown_cursor = self.shown_cursor.map(|c| c + cells);
+                }
+        }
+}
+
+impl TextBackend for FramebufferBackend<'_>
+{
+        fn cells(&mut self) -> &mut [u16] { self.cells }
+
+        fn set_start_address(
+                &mut self,
+                start: u16,
+        )
+        {
+                let cols = self.cols as isize;
+                let delta = start as isize - self.start as isize;
+
+                self.start = start as usize;
+                if delta != 0
+                        && delta % cols == 0
+                        && (delta / cols).unsigned_abs() < self.scrolled_rows()
+                {
+                        self.shift(delta / cols);
+                        // Only the lines that appeared differ from the cells shown.
+                        let screen = self.cols as usize * self.scrolled_rows();
+                        let fresh = match delta > 0 {
+                                true => screen - delta as usize..screen,
+                                false => 0..delta.unsigned_abs(),
+                        };
+                        for pos in fresh {
+                                self.shown[pos] = !self.cell_at(pos);
+                        }
+                }
+                self.flush();
+        }
+
+        fn set_cursor_address(
+                &mut self,
+                pos: u16,
+        )
+        {
+                self.cursor = pos as usize;
+                self.flush();
+        }
+
+        fn set_cursor_shape(
+                &mut self,
+                shape: CursorTypes,
+        )
+        {
+                self.shape = shape;
+                self.flush();
+        }
+
+        fn set_geometry(
+                &mut self,
+                cols: u8,
+                rows: u8,
+        )
+        {
+                let (max_cols, max_rows) = self.max_geometry();
+
+                self.cols = cols.min(max_cols);
+                self.rows = rows.min(max_rows);
+                self.redraw();
+        }
+
+        fn flush(&mut self) { FramebufferBackend::flush(self); }
+
+        fn set_split(
+                &mut self,
+                rows: u8,
+        )
+        {
+                self.split = rows;
+                self.invalidate();
+                self.flush();
+        }
+
+        fn set_blink(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.blink = enabled;
+                self.invalidate();
+                self.flush();
+        }
+
+        fn set_underline(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.underline = enabled;
+                self.invalidate();
+                self.flush();
+        }
+}
+
+impl Surface for FramebufferBackend<'_>
+{
+        fn width(&self) -> usize { self.width }
+
+        fn height(&self) -> usize { self.height }
+
+        /// Draws attribute color `color`, modulo 16.
+        fn write_pixel(
+                &mut self,
+                x: usize,
+                y: usize,
+                color: u8,
+        )
+        {
+                let bpp = self.bytes_per_pixel;
+                let pixel = y * self.pitch + x * bpp;
+                let color = self.colors[color as usize & 0xf];
+
+                self.pixels[pixel..pixel + bpp].copy_from_slice(&color.to_le_bytes()[..bpp]);
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+        use crate::vga::VGAColor;
+        use crate::vga::attribute::Attribute;
+        use crate::vga::console::VgaConsole;
+
+        const WIDTH: usize = 32;
+        const HEIGHT: usize = 12;
+        const FONT_HEIGHT: u8 = 4;
+
+        fn info() -> FramebufferInfo
+        {
+                FramebufferInfo {
+                        addr:   0,
+                        pitch:  (WIDTH * 4) as u32,
+                        width:  WIDTH as u32,
+                        height: HEIGHT as u32,
+                        bpp:    32,
+                        kind:   FramebufferType::Rgb(PixelFormat {
+                                red:   (16, 8),
+                                green: (8, 8),
+                                blue:  (0, 8),
+                        }),
+                }
+        }
+
+        /// Font whose glyph `c` has its first scanline set to `c`, so each
+        /// character can be recognized from its first pixels.
+        fn glyphs() -> Vec<u8> { (0..=255u8).flat_map(|c| [c, 0, 0, 0]).collect() }
+
+        /// Returns the pixel at `x`/`y` as `0xRRGGBB`.
+        fn pixel(
+                pixels: &[u8],
+                x: usize,
+                y: usize,
+        ) -> u32
+        {
+                let i = (y * WIDTH + x) * 4;
+                u32::from_le_bytes(pixels[i..i + 4].try_into().unwrap())
+        }
+
+        /// Returns the character drawn in the text cell at `col`/`row`.
+        fn char_at(
+                pixels: &[u8],
+                col: usize,
+                row: usize,
+        ) -> u8
+        {
+                let white = 0xffffff;
+                (0..8).fold(0, |c, x| {
+                        let bit = (pixel(pixels, col * 8 + x, row * FONT_HEIGHT as usize) == white)
+                                as u8;
+                        c << 1 | bit
+                })
+        }
+
+        #[test]
+        fn rejects_unsupported_formats()
+        {
+                let glyphs = glyphs();
+                let font = Psf::from_glyphs(&glyphs, FONT_HEIGHT).unwrap();
+                let (mut pixels, mut cells, mut shown) = (vec![0; 64], [0; 16], [0; 16]);
+                let mut indexed = info();
+                indexed.kind = FramebufferType::Indexed;
+
+                assert!(FramebufferBackend::new(
+                        &indexed,
+                        &mut pixels,
+                        font,
+                        &mut cells,
+                        &mut shown
+                )
+                .is_none());
+        }
+
+        #[test]
+        fn console_text_is_drawn()
+        {
+                let glyphs = glyphs();
+                let font = Psf::from_glyphs(&glyphs, FONT_HEIGHT).unwrap();
+                let mut pixels = vec![0; WIDTH * HEIGHT * 4];
+                let (mut cells, mut shown) = ([0; 64], [0; 16]);
+                let fb =
+                        FramebufferBackend::new(&info(), &mut pixels, font, &mut cells, &mut shown)
+                                .unwrap();
+                assert_eq!(fb.max_geometry(), (4, 3));
+
+                let mut con = VgaConsole::with_geometry(
+                        fb,
+                        VGAColor::White,
+                        VGAColor::Blue,
+                        4,
+                        3,
+                        Some(CursorTypes::Underline),
+                );
+                con.putstr("ab\ncd\nef\ngh");
+                drop(con);
+
+                assert_eq!(char_at(&pixels, 0, 0), b'c');
+                assert_eq!(char_at(&pixels, 1, 1), b'f');
+                assert_eq!(char_at(&pixels, 1, 2), b'h');
+                assert_eq!(pixel(&pixels, 0, 1), 0x0000aa);
+                // The underline cursor inverts the last scanline after "gh".
+                assert_eq!(pixel(&pixels, 16, 11), 0xaaaaaa);
+                assert_eq!(pixel(&pixels, 16, 10), 0x000000);
+        }
+
+        #[test]
+        fn palette_changes_redraw()
+        {
+                let glyphs = glyphs();
+                let font = Psf::from_glyphs(&glyphs, FONT_HEIGHT).unwrap();
+                let mut pixels = vec![0; WIDTH * HEIGHT * 4];
+                let (mut cells, mut shown) = ([BLANK; 64], [0; 16]);
+                let mut fb =
+                        FramebufferBackend::new(&info(), &mut pixels, font, &mut cells, &mut shown)
+                                .unwrap();
+
+                fb.set_palette(&palette::GRUVBOX.palette);
+                drop(fb);
+
+                assert_eq!(pixel(&pixels, 31, 11), 0x282828);
+        }
+
+        #[test]
+        fn redraw_restores_text()
+        {
+                let glyphs = glyphs();
+                let font = Psf::from_glyphs(&glyphs, FONT_HEIGHT).unwrap();
+                let mut pixels = vec![0; WIDTH * HEIGHT * 4];
+                let (mut cells, mut shown) = ([BLANK; 64], [0; 16]);
+                cells[5] = 0x0f00 | b'x' as u16;
+                let mut fb =
+                        FramebufferBackend::new(&info(), &mut pixels, font, &mut cells, &mut shown)
+                                .unwrap();
+
+                fb.fill_rect((0, 0), (WIDTH, HEIGHT), VGAColor::Red as u8);
+                assert_eq!(Surface::width(&fb), WIDTH);
+                fb.redraw();
+                drop(fb);
+
+                assert_eq!(char_at(&pixels, 1, 1), b'x');
+                assert_eq!(pixel(&pixels, 0, 0), 0);
+        }
+
+        #[test]
+        fn split_rows_stay_in_place()
+        {
+                let glyphs = glyphs();
+                let font = Psf::from_glyphs(&glyphs, FONT_HEIGHT).unwrap();
+                let mut pixels = vec![0; WIDTH * HEIGHT * 4];
+                let (mut cells, mut shown) = ([0; 64], [0; 16]);
+                let fb =
+                        FramebufferBackend::new(&info(), &mut pixels, font, &mut cells, &mut shown)
+                                .unwrap();
+
+                let mut con =
+                        VgaConsole::with_geometry(fb, VGAColor::White, VGAColor::Black, 4, 3, None);
+                con.set_split(1);
+                con.write_status(
+                        0,
+                        Attribute::new(VGAColor::White, VGAColor::Black),
+                        format_args!("st"),
+                );
+                con.putstr("ab\ncd\nef");
+                drop(con);
+
+                assert_eq!(char_at(&pixels, 0, 0), b'c');
+                assert_eq!(char_at(&pixels, 0, 1), b'e');
+                assert_eq!(char_at(&pixels, 1, 2), b't');
+        }
+}
@@ -1,5 +1,26 @@
 use core::arch::asm;
 
+/// Interrupt Enable Flag of the EFLAGS register.
+pub const EFLAGS_IF: u32 = 1 << 9;
+
+/// Reads the EFLAGS register of the current CPU.
+#[inline]
+pub fn eflags() -> u32
+{
+        let flags: u32;
+
+        // SAFETY: Pushing the flags and popping them back only uses the
+        // stack.
+        unsafe {
+                asm!("pushfd", "pop {}", out(reg) flags, options(nomem, preserves_flags));
+        }
+        flags
+}
+
+/// Returns whether maskable interrupts are enabled on the current CPU.
+#[inline]
+pub fn interrupts_enabled() -> bool { eflags() & EFLAGS_IF != 0 }
+
 /// Disables maskable interrupts on the current CPU.
 ///
 /// # Safety
@@ -22,3 +43,72 @@ pub unsafe fn sti()
 {
         asm!("sti", options(readonly, nostack, preserves_flags));
 }
+
+/// Halts the current CPU until the next interrupt arrives.
+#[inline]
+pub fn hlt()
+{
+        // SAFETY: Halting has no side effect besides waiting for an interrupt.
+        unsafe {
+                asm!("hlt", options(nomem, nostack, preserves_flags));
+        }
+}
+
+/// Registers returned by the `cpuid` instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cpuid
+{
+        pub eax: u32,
+        pub ebx: u32,
+        pub ecx: u32,
+        pub edx: u32,
+}
+
+/// Runs `cpuid` for `leaf` and `subleaf`.
+///
+/// The kernel targets CPUs with `cpuid`, from the late 486s on.
+#[inline]
+pub fn cpuid(
+        leaf: u32,
+        subleaf: u32,
+) -> Cpuid
+{
+        // SAFETY: `cpuid` only reads CPU identification.
+        #[allow(unused_unsafe)]
+        let result = unsafe { core::arch::x86::__cpuid_count(leaf, subleaf) };
+
+        Cpuid {
+                eax: result.eax,
+                ebx: result.ebx,
+                ecx: result.ecx,
+                edx: result.edx,
+        }
+}
+
+/// Reads the model-specific register `msr`.
+///
+/// # Safety
+/// The MSR must exist on the current CPU, or `rdmsr` raises a general
+/// protection fault.
+#[inline]
+pub unsafe fn rdmsr(msr: u32) -> u64
+{
+        let (low, high): (u32, u32);
+
+        asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+        (high as u64) << 32 | low as u64
+}
+
+/// Writes `value` to the model-specific register `msr`.
+///
+/// # Safety
+/// The MSR must exist on the current CPU and `value` be valid for it, the
+/// write changing CPU behavior.
+#[inline]
+pub unsafe fn wrmsr(
+        msr: u32,
+        value: u64,
+)
+{
+        asm!("wrmsr", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack, preserves_flags));
+}
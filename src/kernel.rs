@@ -13,41 +13,52 @@
 
 mod drivers;
 mod instructions;
-mod multiboot;
 mod panic;
 mod qemu;
+mod sync;
 mod test;
 
 use core::arch::global_asm;
 use core::mem::MaybeUninit;
 
-use multiboot::MultibootInfo;
-
-use crate::multiboot::{MULTIBOOT_HEADER_MAGIC, MultibootHeader, MultibootHeaderFlags};
+use drivers::irq::{self, Mode};
+use drivers::{acpi, ata, pci, rtc, speaker, video};
+use kfs::cmdline;
+use kfs::multiboot::{
+        self, MULTIBOOT_HEADER_MAGIC, MultibootHeader, MultibootHeaderFlags, MultibootInfo,
+};
+use kfs::vga::config::Config;
+use kfs::vga::font::Psf;
+use kfs::vga::palette;
 
 const STACK_SIZE: usize = 0x10000;
 
+/// Played at the end of the boot with the `sound=on` option.
+const BOOT_MELODY: &str = "T200 C5:8 E5:8 G5:8 C6:4";
+
+const HEADER_FLAGS: MultibootHeaderFlags = MultibootHeaderFlags::ALIGN_MODULES
+        .union(MultibootHeaderFlags::MEMORY_INFO)
+        .union(MultibootHeaderFlags::VIDEO_MODE);
+
 #[used]
 #[unsafe(link_section = ".multiboot")]
 pub static MULTIBOOT_HEADER: MultibootHeader = MultibootHeader {
         magic:         MULTIBOOT_HEADER_MAGIC,
-        flags:         MultibootHeaderFlags::ALIGN_MODULES.bits()
-                | MultibootHeaderFlags::MEMORY_INFO.bits(),
+        flags:         HEADER_FLAGS.bits(),
         checksum:      MULTIBOOT_HEADER_MAGIC
-                .wrapping_add(
-                        MultibootHeaderFlags::ALIGN_MODULES.bits()
-                                | MultibootHeaderFlags::MEMORY_INFO.bits(),
-                )
+                .wrapping_add(HEADER_FLAGS.bits())
                 .wrapping_neg(),
         header_addr:   0,
         load_addr:     0,
         load_end_addr: 0,
         bss_end_addr:  0,
         entry_addr:    0,
+        // Linear framebuffer, GRUB picks the closest mode available. The
+        // default menu entry keeps the text mode with `gfxpayload=text`.
         mode_type:     0,
-        width:         0,
-        height:        0,
-        depth:         0,
+        width:         1024,
+        height:        768,
+        depth:         32,
 };
 
 #[used]
@@ -84,17 +95,90 @@ _start:
 #[unsafe(no_mangle)]
 pub extern "C" fn kernel_main(
         multiboot_magic: u32,
-        _mbi: &'static MultibootInfo,
+        mbi: &'static MultibootInfo,
 ) -> !
 {
         if multiboot_magic != multiboot::BOOTLOADER_MAGIC {
                 panic!("invalid magic number at ")
         }
 
+        // SAFETY: Modules are still where the bootloader loaded them.
+        let modules = unsafe { mbi.modules() }.unwrap_or(&[]);
+        // SAFETY: The command line is still where the bootloader left it.
+        let mut config =
+                unsafe { mbi.cmdline() }.map_or_else(Config::default, Config::from_cmdline);
+        config.framebuffer = mbi.framebuffer();
+        config.font = modules
+                .iter()
+                .find_map(|m| Psf::parse(unsafe { m.bytes() }).ok());
+
         #[cfg(test)]
-        kernel_maintest();
+        {
+                // SAFETY: No framebuffer is used by the tests.
+                unsafe { video::init(&Config::default()) };
+                kernel_maintest();
+        }
+
+        // SAFETY: The framebuffer is identity mapped, and only the console
+        // uses it.
+        unsafe { video::init(&config) };
+
+        video::splash();
+        video::boot_stage("Memory map", || {
+                // SAFETY: The memory map is still where the bootloader left it.
+                unsafe { mbi.memory_map() }.is_some_and(|mut mmap| mmap.next().is_some())
+        });
 
-        println!("\nsizeof\n");
+        if let Some(font) = config.font {
+                video::boot_stage("Font", || video::load_font(font).is_ok());
+        }
+
+        video::boot_stage("ACPI tables", || acpi::init().is_ok());
+        if let Some(tables) = acpi::tables() {
+                acpi::print_summary(tables);
+                if let Some(fadt) = tables.fadt {
+                        rtc::set_century_register(fadt.century);
+                }
+                if let Some(madt) = tables.madt {
+                        irq::use_madt(&madt);
+                }
+        }
+
+        // SAFETY: The command line is still where the bootloader left it.
+        let mode = match unsafe { mbi.cmdline() }.and_then(|c| cmdline::option(c, "irq")) {
+                Some("apic") => Mode::Apic,
+                _ => Mode::Pic,
+        };
+        video::boot_stage("Interrupt controller", || irq::set_mode(mode).is_ok());
+
+        video::boot_stage("PCI devices", || pci::init() > 0);
+        // SAFETY: The command line is still where the bootloader left it.
+        if unsafe { mbi.cmdline() }.and_then(|c| cmdline::option(c, "pci")) == Some("list") {
+                pci::lspci();
+        }
+
+        video::boot_stage("ATA drives", || ata::init() > 0);
+        ata::print_drives();
+
+        video::set_status_rows(1);
+        video::set_status(
+                0,
+                format_args!(
+                        " kfs | {} KiB memory | theme {}",
+                        mbi.mem_lower + mbi.mem_upper,
+                        config.theme
+                                .map_or(palette::DEFAULT.name, |theme| theme.name)
+                ),
+        );
+
+        log!("kfs started");
+
+        // SAFETY: The command line is still where the bootloader left it.
+        if unsafe { mbi.cmdline() }.and_then(|c| cmdline::option(c, "sound")) == Some("on") {
+                speaker::play(BOOT_MELODY).ok();
+        }
 
-        loop {}
+        loop {
+                instructions::cpu::hlt();
+        }
 }
@@ -0,0 +1,44 @@
+//! Hardware-independent part of the kernel.
+//!
+//! This crate only contains logic that does not touch I/O ports, CPU
+//! instructions or fixed physical addresses. It is linked into the kernel
+//! binary as a regular `no_std` dependency, and can also be compiled for the
+//! host target with `std` so its unit tests run without booting QEMU:
+//!
+//! ```sh
+//! make test-lib
+//! ```
+#![cfg_attr(any(not(test), target_os = "none"), no_std)]
+// The tests need `std`, so on the kernel target the test build of this crate
+// is an empty `no_std` crate with a runner that does nothing.
+#![cfg_attr(all(test, target_os = "none"), no_main)]
+#![cfg_attr(all(test, target_os = "none"), feature(custom_test_frameworks))]
+#![cfg_attr(all(test, target_os = "none"), test_runner(crate::host_only_runner))]
+
+pub mod acpi;
+pub mod apic;
+pub mod ata;
+pub mod block;
+pub mod cmdline;
+pub mod framebuffer;
+pub mod melody;
+pub mod multiboot;
+pub mod pci;
+pub mod rtc;
+pub mod splash;
+pub mod time;
+pub mod vga;
+
+#[cfg(all(test, target_os = "none"))]
+fn host_only_runner(_tests: &[&dyn Fn()])
+{
+}
+
+#[cfg(all(test, target_os = "none"))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> !
+{
+        loop {
+                core::hint::spin_loop();
+        }
+}
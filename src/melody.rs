@@ -0,0 +1,250 @@
+//! Melody notation for the PC speaker.
+//!
+//! A melody is a list of notes separated by spaces. A note is a pitch letter
+//! from `A` to `G`, an optional `#` or `b` accidental and an octave from 0
+//! to 8, or `R` for a rest, followed by an optional `:length` giving the
+//! fraction of a whole note it lasts, a quarter note by default. A `T` word
+//! sets the tempo in quarter notes per minute, 120 by default:
+//!
+//! ```text
+//! T180 C5:8 E5:8 G5:8 R:8 C6:2
+//! ```
+//!
+//! Pitches follow the equal temperament, with A4 at 440 Hz.
+use core::str::SplitAsciiWhitespace;
+
+/// Default number of quarter notes per minute.
+pub const DEFAULT_TEMPO: u32 = 120;
+
+/// Default note length, a quarter note.
+const DEFAULT_LENGTH: u32 = 4;
+
+/// Frequencies of the notes of octave 4 in mHz, from C4.
+const OCTAVE_4: [u32; 12] = [
+        261_626,
+        277_183,
+        293_665,
+        311_127,
+        329_628,
+        349_228,
+        369_994,
+        391_995,
+        415_305,
+        440_000,
+        466_164,
+        493_883,
+];
+
+/// Highest octave a note can be in.
+const MAX_OCTAVE: u8 = 8;
+
+/// Error found in a melody.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MelodyError
+{
+        /// A word is neither a note, a rest nor a tempo
+        InvalidNote,
+        /// A note length is not a power of two from 1 to 64
+        InvalidLength,
+        /// A tempo is not a number from 1 to 1000
+        InvalidTempo,
+}
+
+/// Note of a melody.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note
+{
+        /// Pitch in Hz, `None` for a rest
+        pub frequency:   Option<u32>,
+        /// How long the note lasts
+        pub duration_ms: u32,
+}
+
+/// Iterator over the notes of a melody, see [`notes`].
+#[derive(Debug, Clone)]
+pub struct Notes<'a>
+{
+        words: SplitAsciiWhitespace<'a>,
+        tempo: u32,
+}
+
+/// Returns the notes of `melody`, stopping after the first error.
+pub fn notes(melody: &str) -> Notes<'_>
+{
+        Notes {
+                words: melody.split_ascii_whitespace(),
+                tempo: DEFAULT_TEMPO,
+        }
+}
+
+impl Iterator for Notes<'_>
+{
+        type Item = Result<Note, MelodyError>;
+
+        fn next(&mut self) -> Option<Self::Item>
+        {
+                loop {
+                        let word = self.words.next()?;
+                        let result = match word.strip_prefix('T') {
+                                Some(tempo) => self.set_tempo(tempo).map(|_| None),
+                                None => self.note(word).map(Some),
+                        };
+
+                        match result {
+                                Ok(None) => continue,
+                                Ok(Some(note)) => return Some(Ok(note)),
+                                Err(err) => {
+                                        self.words = "".split_ascii_whitespace();
+                                        return Some(Err(err));
+                                }
+                        }
+                }
+        }
+}
+
+impl Notes<'_>
+{
+        fn set_tempo(
+                &mut self,
+                tempo: &str,
+        ) -> Result<(), MelodyError>
+        {
+                self.tempo = tempo
+                        .parse()
+                        .ok()
+                        .filter(|tempo| (1..=1000).contains(tempo))
+                        .ok_or(MelodyError::InvalidTempo)?;
+                Ok(())
+        }
+
+        fn note(
+                &self,
+                word: &str,
+        ) -> Result<Note, MelodyError>
+        {
+                let (pitch, length) = match word.split_once(':') {
+                        Some((pitch, length)) => {
+                                let length = length
+                                        .parse()
+                                        .ok()
+                                        .filter(|&l: &u32| l.is_power_of_two() && l <= 64)
+                                        .ok_or(MelodyError::InvalidLength)?;
+                                (pitch, length)
+                        }
+                        None => (word, DEFAULT_LENGTH),
+                };
+                let frequency = match pitch {
+                        "R" => None,
+                        _ => Some(frequency(pitch).ok_or(MelodyError::InvalidNote)?),
+                };
+
+                Ok(Note {
+                        frequency,
+                        // A whole note lasts 4 beats of 60000 / tempo ms.
+                        duration_ms: 4 * 60_000 / (self.tempo * length),
+                })
+        }
+}
+
+/// Returns the frequency in Hz of a pitch such as `C4` or `F#5`.
+fn frequency(pitch: &str) -> Option<u32>
+{
+        let mut chars = pitch.chars();
+        let semitone: i32 = match chars.next()? {
+                'C' => 0,
+                'D' => 2,
+                'E' => 4,
+                'F' => 5,
+                'G' => 7,
+                'A' => 9,
+                'B' => 11,
+                _ => return None,
+        };
+        let rest = chars.as_str();
+        let (semitone, octave) = match rest.as_bytes().first()? {
+                b'#' => (semitone + 1, &rest[1..]),
+                b'b' => (semitone - 1, &rest[1..]),
+                _ => (semitone, rest),
+        };
+        let octave: u8 = octave.parse().ok().filter(|&o| o <= MAX_OCTAVE)?;
+
+        // Cb and B# belong to the neighbouring octaves.
+        let (semitone, octave) = match semitone {
+                -1 => (11, octave.checked_sub(1)?),
+                12 => (0, octave + 1),
+                _ => (semitone as usize, octave),
+        };
+        let millihertz = match octave {
+                0..4 => OCTAVE_4[semitone] >> (4 - octave),
+                _ => OCTAVE_4[semitone] << (octave - 4),
+        };
+
+        Some((millihertz + 500) / 1000)
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn parses_notes_and_rests()
+        {
+                let melody: Vec<_> = notes("A4 C#5:8 R:2 Bb3").collect();
+
+                assert_eq!(
+                        melody,
+                        [
+                                Ok(Note {
+                                        frequency:   Some(440),
+                                        duration_ms: 500,
+                                }),
+                                Ok(Note {
+                                        frequency:   Some(554),
+                                        duration_ms: 250,
+                                }),
+                                Ok(Note {
+                                        frequency:   None,
+                                        duration_ms: 1000,
+                                }),
+                                Ok(Note {
+                                        frequency:   Some(233),
+                                        duration_ms: 500,
+                                }),
+                        ]
+                );
+        }
+
+        #[test]
+        fn tempo_changes_durations()
+        {
+                let durations: Vec<_> = notes("C4 T240 C4 C4:16")
+                        .map(|note| note.unwrap().duration_ms)
+                        .collect();
+
+                assert_eq!(durations, [500, 250, 62]);
+        }
+
+        #[test]
+        fn octaves_double_frequencies()
+        {
+                assert_eq!(frequency("A0"), Some(28));
+                assert_eq!(frequency("A8"), Some(7040));
+                assert_eq!(frequency("C4"), Some(262));
+                assert_eq!(frequency("B#4"), Some(523));
+                assert_eq!(frequency("Cb4"), Some(247));
+                assert_eq!(frequency("Cb0"), None);
+                assert_eq!(frequency("A9"), None);
+        }
+
+        #[test]
+        fn stops_at_first_error()
+        {
+                let melody: Vec<_> = notes("C4 H4 C4").collect();
+                assert_eq!(melody.len(), 2);
+                assert_eq!(melody[1], Err(MelodyError::InvalidNote));
+
+                assert_eq!(notes("C4:3").next(), Some(Err(MelodyError::InvalidLength)));
+                assert_eq!(notes("T0 C4").next(), Some(Err(MelodyError::InvalidTempo)));
+        }
+}
@@ -5,7 +5,9 @@
 //! use Multiboot 1.
 
 use core::cmp::Ordering;
+use core::ffi::{CStr, c_char};
 use core::fmt::Debug;
+use core::mem;
 
 use bitflags::bitflags;
 pub const MULTIBOOT_HEADER_MAGIC: u32 = 0x1BADB002;
@@ -40,6 +42,27 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Fields of [`MultibootInfo`] filled in by the bootloader.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct MultibootInfoFlags: u32 {
+        const MEMORY = 1 << 0;
+        const BOOT_DEVICE = 1 << 1;
+        const CMDLINE = 1 << 2;
+        const MODULES = 1 << 3;
+        const AOUT_SYMBOLS = 1 << 4;
+        const ELF_SECTIONS = 1 << 5;
+        const MEMORY_MAP = 1 << 6;
+        const DRIVES = 1 << 7;
+        const CONFIG_TABLE = 1 << 8;
+        const BOOT_LOADER_NAME = 1 << 9;
+        const APM_TABLE = 1 << 10;
+        const VBE_INFO = 1 << 11;
+        const FRAMEBUFFER_INFO = 1 << 12;
+    }
+}
+
 #[repr(C)]
 pub struct MultibootInfo
 {
@@ -60,6 +83,196 @@ pub struct MultibootInfo
         drives_addr:      u32,
         _config_table:    u32,
         boot_loader_name: u32,
+        apm_table:        u32,
+        vbe_control_info: u32,
+        vbe_mode_info:    u32,
+        vbe_mode:         u16,
+        vbe_interface:    [u16; 3],
+        // The 64-bit address is split so the structure stays 4-byte aligned,
+        // as laid out by the bootloader.
+        fb_addr_low:      u32,
+        fb_addr_high:     u32,
+        fb_pitch:         u32,
+        fb_width:         u32,
+        fb_height:        u32,
+        fb_bpp:           u8,
+        fb_type:          u8,
+        fb_color_info:    [u8; 6],
+}
+
+impl MultibootInfo
+{
+        /// Reinterprets the beginning of `bytes` as a Multiboot information
+        /// structure.
+        ///
+        /// Returns `None` if `bytes` is too short or not aligned on 4 bytes.
+        pub fn from_bytes(bytes: &[u8]) -> Option<&Self>
+        {
+                if bytes.len() < mem::size_of::<Self>()
+                        || bytes.as_ptr().align_offset(mem::align_of::<Self>()) != 0
+                {
+                        return None;
+                }
+
+                // SAFETY: The slice is large enough and correctly aligned, and every
+                // field is a plain `u32` so any bit pattern is valid.
+                Some(unsafe { &*(bytes.as_ptr() as *const Self) })
+        }
+
+        /// Returns which fields of the structure are valid.
+        pub fn flags(&self) -> MultibootInfoFlags
+        {
+                MultibootInfoFlags::from_bits_retain(self.flags)
+        }
+
+        /// Returns the framebuffer set up by the bootloader, if any.
+        pub fn framebuffer(&self) -> Option<FramebufferInfo>
+        {
+                if !self.flags().contains(MultibootInfoFlags::FRAMEBUFFER_INFO) {
+                        return None;
+                }
+
+                let info = self.fb_color_info;
+                let kind = match self.fb_type {
+                        0 => FramebufferType::Indexed,
+                        1 => FramebufferType::Rgb(PixelFormat {
+                                red:   (info[0], info[1]),
+                                green: (info[2], info[3]),
+                                blue:  (info[4], info[5]),
+                        }),
+                        _ => FramebufferType::Text,
+                };
+
+                Some(FramebufferInfo {
+                        addr: (self.fb_addr_high as u64) << 32 | self.fb_addr_low as u64,
+                        pitch: self.fb_pitch,
+                        width: self.fb_width,
+                        height: self.fb_height,
+                        bpp: self.fb_bpp,
+                        kind,
+                })
+        }
+
+        /// Returns the kernel command line, if any and valid UTF-8.
+        ///
+        /// # Safety
+        /// `cmdline` must point to the NUL terminated string set up by the
+        /// bootloader, which is the case as long as that memory has not been
+        /// reused.
+        pub unsafe fn cmdline(&self) -> Option<&str>
+        {
+                if !self.flags().contains(MultibootInfoFlags::CMDLINE) {
+                        return None;
+                }
+
+                unsafe { CStr::from_ptr(self.cmdline as usize as *const c_char) }
+                        .to_str()
+                        .ok()
+        }
+
+        /// Returns the modules loaded by the bootloader, if any.
+        ///
+        /// # Safety
+        /// `mods_addr` must point to `mods_count` module structures, which is
+        /// the case for the structure handed over by the bootloader as long as
+        /// that memory has not been reused.
+        pub unsafe fn modules(&self) -> Option<&[MultibootModule]>
+        {
+                if !self.flags().contains(MultibootInfoFlags::MODULES) {
+                        return None;
+                }
+
+                Some(unsafe {
+                        core::slice::from_raw_parts(
+                                self.mods_addr as usize as *const MultibootModule,
+                                self.mods_count as usize,
+                        )
+                })
+        }
+
+        /// Returns the memory map provided by the bootloader, if any.
+        ///
+        /// # Safety
+        /// `mmap_addr` must point to `mmap_length` readable bytes, which is
+        /// the case for the structure handed over by the bootloader as long as
+        /// that memory has not been reused.
+        pub unsafe fn memory_map(&self) -> Option<MmapIter<'_>>
+        {
+                if !self.flags().contains(MultibootInfoFlags::MEMORY_MAP) {
+                        return None;
+                }
+
+                let bytes = unsafe {
+                        core::slice::from_raw_parts(
+                                self.mmap_addr as usize as *const u8,
+                                self.mmap_length as usize,
+                        )
+                };
+                Some(MmapIter::new(bytes))
+        }
+}
+
+/// Position and size in bits of each color component of a direct color
+/// pixel, as `(position, size)`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct PixelFormat
+{
+        pub red:   (u8, u8),
+        pub green: (u8, u8),
+        pub blue:  (u8, u8),
+}
+
+/// How the pixels of a framebuffer are encoded.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum FramebufferType
+{
+        /// Pixels are palette indices
+        Indexed,
+        /// Pixels hold their color components
+        Rgb(PixelFormat),
+        /// EGA text mode, `width` and `height` are in characters
+        Text,
+}
+
+/// Framebuffer set up by the bootloader.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct FramebufferInfo
+{
+        /// Physical address of the first pixel
+        pub addr:   u64,
+        /// Bytes between the beginning of two lines
+        pub pitch:  u32,
+        pub width:  u32,
+        pub height: u32,
+        /// Bits per pixel
+        pub bpp:    u8,
+        pub kind:   FramebufferType,
+}
+
+/// File loaded in memory by the bootloader alongside the kernel.
+#[repr(C)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct MultibootModule
+{
+        pub mod_start: u32,
+        pub mod_end:   u32,
+        string:        u32,
+        _reserved:     u32,
+}
+
+impl MultibootModule
+{
+        /// Returns the content of the module.
+        ///
+        /// # Safety
+        /// `mod_start` to `mod_end` must still hold the module loaded by the
+        /// bootloader.
+        pub unsafe fn bytes(&self) -> &'static [u8]
+        {
+                let len = self.mod_end.saturating_sub(self.mod_start) as usize;
+
+                unsafe { core::slice::from_raw_parts(self.mod_start as usize as *const u8, len) }
+        }
 }
 
 #[repr(u32)]
@@ -73,6 +286,21 @@ pub enum MultibootMmapEntryType
         Badrram,
 }
 
+impl From<u32> for MultibootMmapEntryType
+{
+        /// Any value not defined by the specification is reserved memory.
+        fn from(value: u32) -> Self
+        {
+                match value {
+                        1 => Self::Available,
+                        3 => Self::AcpiReclamable,
+                        4 => Self::Nvs,
+                        5 => Self::Badrram,
+                        _ => Self::Reserved,
+                }
+        }
+}
+
 #[repr(C)]
 #[derive(Eq, PartialEq, Clone, Copy)]
 pub struct MultibootMmapEntry
@@ -83,6 +311,62 @@ pub struct MultibootMmapEntry
         pub entry_type: MultibootMmapEntryType,
 }
 
+impl MultibootMmapEntry
+{
+        /// Size in bytes of an entry as laid out by the bootloader, `size`
+        /// field included.
+        const RAW_SIZE: usize = 24;
+
+        /// Decodes an entry from the raw little-endian bootloader layout.
+        ///
+        /// Returns `None` if `bytes` is too short to contain an entry.
+        pub fn from_bytes(bytes: &[u8]) -> Option<Self>
+        {
+                if bytes.len() < Self::RAW_SIZE {
+                        return None;
+                }
+
+                let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+                let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+
+                Some(Self {
+                        size:       u32_at(0),
+                        addr:       u64_at(4),
+                        len:        u64_at(12),
+                        entry_type: MultibootMmapEntryType::from(u32_at(20)),
+                })
+        }
+}
+
+/// Iterator over the entries of a Multiboot memory map buffer.
+///
+/// Entries have a variable size: each one starts with a `size` field that
+/// does not count itself, so the next entry is at `size + 4` bytes.
+#[derive(Debug, Clone)]
+pub struct MmapIter<'a>
+{
+        bytes: &'a [u8],
+}
+
+impl<'a> MmapIter<'a>
+{
+        pub fn new(bytes: &'a [u8]) -> Self { Self { bytes } }
+}
+
+impl Iterator for MmapIter<'_>
+{
+        type Item = MultibootMmapEntry;
+
+        fn next(&mut self) -> Option<Self::Item>
+        {
+                let entry = MultibootMmapEntry::from_bytes(self.bytes)?;
+                let next = entry.size as usize + mem::size_of::<u32>();
+
+                self.bytes = self.bytes.get(next..).unwrap_or(&[]);
+                Some(entry)
+        }
+}
+
 impl PartialOrd for MultibootMmapEntry
 {
         fn partial_cmp(
@@ -90,7 +374,7 @@ impl PartialOrd for MultibootMmapEntry
                 other: &Self,
         ) -> Option<Ordering>
         {
-                Some(self.len.cmp(&other.len))
+                Some(self.cmp(other))
         }
 }
 
@@ -143,3 +427,115 @@ impl Debug for MultibootInfo
                         .finish()
         }
 }
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        fn mmap_entry(
+                addr: u64,
+                len: u64,
+                entry_type: u32,
+        ) -> Vec<u8>
+        {
+                let mut bytes = Vec::new();
+                bytes.extend_from_slice(&20u32.to_le_bytes());
+                bytes.extend_from_slice(&addr.to_le_bytes());
+                bytes.extend_from_slice(&len.to_le_bytes());
+                bytes.extend_from_slice(&entry_type.to_le_bytes());
+                bytes
+        }
+
+        #[test]
+        fn info_from_bytes()
+        {
+                let mut words = [0u32; 32];
+                words[0] = (MultibootInfoFlags::MEMORY | MultibootInfoFlags::MEMORY_MAP).bits();
+                words[1] = 639;
+                words[2] = 130048;
+                words[11] = 48;
+                words[12] = 0x9000;
+                let bytes: &[u8] = unsafe {
+                        core::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4)
+                };
+
+                let info = MultibootInfo::from_bytes(bytes).unwrap();
+                assert!(info.flags().contains(MultibootInfoFlags::MEMORY_MAP));
+                assert!(!info.flags().contains(MultibootInfoFlags::CMDLINE));
+                assert_eq!(info.mem_lower, 639);
+                assert_eq!(info.mem_upper, 130048);
+                assert_eq!(info.mmap_length, 48);
+                assert_eq!(info.mmap_addr, 0x9000);
+
+                assert!(MultibootInfo::from_bytes(&bytes[..16]).is_none());
+                assert!(MultibootInfo::from_bytes(&bytes[1..]).is_none());
+        }
+
+        #[test]
+        fn framebuffer_info()
+        {
+                let mut words = [0u32; 32];
+                words[0] = MultibootInfoFlags::FRAMEBUFFER_INFO.bits();
+                words[22] = 0xfd00_0000;
+                words[24] = 4096;
+                words[25] = 1024;
+                words[26] = 768;
+                words[27] = u32::from_le_bytes([32, 1, 16, 8]);
+                words[28] = u32::from_le_bytes([8, 8, 0, 8]);
+                let bytes: &[u8] = unsafe {
+                        core::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4)
+                };
+
+                let fb = MultibootInfo::from_bytes(bytes)
+                        .unwrap()
+                        .framebuffer()
+                        .unwrap();
+                assert_eq!(fb.addr, 0xfd00_0000);
+                assert_eq!(
+                        (fb.pitch, fb.width, fb.height, fb.bpp),
+                        (4096, 1024, 768, 32)
+                );
+                assert_eq!(
+                        fb.kind,
+                        FramebufferType::Rgb(PixelFormat {
+                                red:   (16, 8),
+                                green: (8, 8),
+                                blue:  (0, 8),
+                        })
+                );
+        }
+
+        #[test]
+        fn mmap_entries()
+        {
+                let mut bytes = mmap_entry(0, 0x9fc00, 1);
+                bytes.extend(mmap_entry(0xf0000, 0x10000, 2));
+                bytes.extend(mmap_entry(0x100000, 0x7ee0000, 3));
+                bytes.extend(mmap_entry(0xfffc0000, 0x40000, 42));
+
+                let entries: Vec<_> = MmapIter::new(&bytes).collect();
+                assert_eq!(entries.len(), 4);
+                assert_eq!(entries[0].addr, 0);
+                assert_eq!(entries[0].len, 0x9fc00);
+                assert_eq!(entries[0].entry_type, MultibootMmapEntryType::Available);
+                assert_eq!(entries[1].entry_type, MultibootMmapEntryType::Reserved);
+                assert_eq!(entries[2].addr, 0x100000);
+                assert_eq!(
+                        entries[2].entry_type,
+                        MultibootMmapEntryType::AcpiReclamable
+                );
+                assert_eq!(entries[3].entry_type, MultibootMmapEntryType::Reserved);
+                assert_eq!(entries.iter().max().unwrap().addr, 0x100000);
+        }
+
+        #[test]
+        fn mmap_truncated_entry()
+        {
+                let mut bytes = mmap_entry(0, 0x9fc00, 1);
+                bytes.extend(&mmap_entry(0x100000, 0x1000, 1)[..10]);
+
+                assert_eq!(MmapIter::new(&bytes).count(), 1);
+                assert_eq!(MmapIter::new(&[]).count(), 0);
+        }
+}
@@ -2,12 +2,28 @@ use crate::drivers::video;
 #[cfg(test)]
 use crate::qemu;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Number of panics in progress, a panic while reporting one falls back to
+/// simpler reporting.
+static PANIC_DEPTH: AtomicU8 = AtomicU8::new(0);
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> !
 {
-        video::_panic_print(format_args_nl!("Fatal Error: {}", info.message()));
-        video::_panic_print(format_args_nl!("Location: {:?}", info.location()));
+        match PANIC_DEPTH.fetch_add(1, Ordering::AcqRel) {
+                0 => {
+                        video::_panic_reset();
+                        video::_panic_print(format_args_nl!("Fatal Error: {}", info.message()));
+                        video::_panic_print(format_args_nl!("Location: {:?}", info.location()));
+                }
+                1 => video::_emergency_print(format_args!(
+                        "Fatal Error while panicking: {} at {:?}",
+                        info.message(),
+                        info.location()
+                )),
+                _ => {}
+        }
 
         #[cfg(test)]
         qemu::exit(qemu::QemuExitCode::Failed);
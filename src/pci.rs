@@ -0,0 +1,540 @@
+//! PCI configuration space decoding and device registry.
+//!
+//! Each PCI function has a 256-byte configuration space, whose first 64
+//! bytes are a standard header: vendor and device IDs, class code, Base
+//! Address Registers (BARs) and interrupt routing. Bridges have their own
+//! header type, giving the bus behind them.
+//!
+//! A BAR is sized by writing all ones to it and reading back which address
+//! bits stuck at 0: the kernel does the probing, this module the decoding.
+//!
+//! Reference: PCI Local Bus Specification 3.0, chapter 6, and
+//! https://wiki.osdev.org/PCI
+use core::fmt;
+
+use bitflags::bitflags;
+
+/// Vendor ID read from an absent function.
+pub const NO_VENDOR: u16 = 0xffff;
+
+/// Devices on a bus.
+pub const DEVICES: u8 = 32;
+
+/// Functions of a device.
+pub const FUNCTIONS: u8 = 8;
+
+/// Size in 32-bit registers of the standard header.
+pub const HEADER_REGISTERS: usize = 16;
+
+/// Offset of the first BAR.
+pub const BAR_OFFSET: u8 = 0x10;
+
+/// Most functions a [`Registry`] holds.
+pub const MAX_FUNCTIONS: usize = 64;
+
+/// Bit of the header type set on multi-function devices.
+const MULTIFUNCTION: u8 = 0x80;
+
+/// Header type of PCI-to-PCI bridges.
+pub const HEADER_BRIDGE: u8 = 0x01;
+
+/// Value of the interrupt line register when no IRQ is routed.
+const NO_INTERRUPT_LINE: u8 = 0xff;
+
+bitflags! {
+    /// Bits of the command register.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Command: u16 {
+        /// The function answers I/O space accesses
+        const IO_SPACE = 0x0001;
+        /// The function answers memory space accesses
+        const MEMORY_SPACE = 0x0002;
+        /// The function can initiate DMA
+        const BUS_MASTER = 0x0004;
+        /// The function does not assert its INTx# pin
+        const INTERRUPT_DISABLE = 0x0400;
+    }
+}
+
+/// Location of a function in the configuration space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Address
+{
+        pub bus:      u8,
+        pub device:   u8,
+        pub function: u8,
+}
+
+impl Address
+{
+        pub const fn new(
+                bus: u8,
+                device: u8,
+                function: u8,
+        ) -> Self
+        {
+                Self {
+                        bus,
+                        device,
+                        function,
+                }
+        }
+
+        /// Returns the value selecting the register at `offset` through
+        /// configuration mechanism #1, the offset rounded down to 4 bytes.
+        pub const fn config_address(
+                &self,
+                offset: u8,
+        ) -> u32
+        {
+                0x8000_0000
+                        | (self.bus as u32) << 16
+                        | ((self.device & 0x1f) as u32) << 11
+                        | ((self.function & 0x07) as u32) << 8
+                        | (offset & 0xfc) as u32
+        }
+}
+
+impl fmt::Display for Address
+{
+        /// Formats as `bus:device.function`, like `lspci`.
+        fn fmt(
+                &self,
+                f: &mut fmt::Formatter<'_>,
+        ) -> fmt::Result
+        {
+                write!(f, "{:02x}:{:02x}.{}", self.bus, self.device, self.function)
+        }
+}
+
+/// Decoded Base Address Register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bar
+{
+        Io
+        {
+                port: u16, size: u32
+        },
+        Memory
+        {
+                address:      u64,
+                size:         u64,
+                prefetchable: bool,
+                /// Whether the BAR takes two registers, for a 64-bit address
+                wide:         bool,
+        },
+}
+
+impl Bar
+{
+        /// Returns whether the BAR whose register reads `raw` takes two
+        /// registers.
+        pub const fn is_wide(raw: u32) -> bool { raw & 0x1 == 0 && (raw >> 1) & 0x3 == 0x2 }
+
+        /// Decodes a BAR from its value `raw` and the value `mask` read back
+        /// after writing all ones, both 64-bit for wide BARs.
+        ///
+        /// Returns `None` if the BAR is not implemented.
+        pub const fn decode(
+                raw: u64,
+                mask: u64,
+        ) -> Option<Self>
+        {
+                if raw & 0x1 != 0 {
+                        let size = (!(mask as u16 & 0xfffc)).wrapping_add(1) as u32;
+                        return match mask as u16 & 0xfffc {
+                                0 => None,
+                                _ => Some(Self::Io {
+                                        port: (raw & 0xfffc) as u16,
+                                        size,
+                                }),
+                        };
+                }
+
+                // The mask of 32-bit BARs is extended with ones, so that the
+                // size computes the same way.
+                let wide = Self::is_wide(raw as u32);
+                let mask = match wide {
+                        true => mask & !0xf,
+                        false => (mask & 0xffff_fff0) | 0xffff_ffff_0000_0000,
+                };
+                if mask == 0 || mask == 0xffff_ffff_0000_0000 {
+                        return None;
+                }
+                Some(Self::Memory {
+                        address: raw & !0xf,
+                        size: (!mask).wrapping_add(1),
+                        prefetchable: raw & 0x8 != 0,
+                        wide,
+                })
+        }
+}
+
+/// PCI function, as read from its configuration header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Function
+{
+        pub address:        Address,
+        pub vendor_id:      u16,
+        pub device_id:      u16,
+        pub revision:       u8,
+        pub prog_if:        u8,
+        pub subclass:       u8,
+        pub class:          u8,
+        /// Header type, without the multi-function bit
+        pub header_type:    u8,
+        pub multifunction:  bool,
+        /// IRQ the firmware routed the function to
+        pub interrupt_line: Option<u8>,
+        /// Interrupt pin, 1 to 4 for INTA# to INTD#
+        pub interrupt_pin:  Option<u8>,
+        /// Bus behind a bridge
+        pub secondary_bus:  Option<u8>,
+        /// BARs, filled by the probing
+        pub bars:           [Option<Bar>; 6],
+}
+
+impl Function
+{
+        /// Decodes the standard header of the function at `address`, `None`
+        /// if there is none.
+        pub fn from_header(
+                address: Address,
+                regs: &[u32; HEADER_REGISTERS],
+        ) -> Option<Self>
+        {
+                let byte = |reg: usize, i: u32| (regs[reg] >> (i * 8)) as u8;
+                let vendor_id = regs[0] as u16;
+
+                if vendor_id == NO_VENDOR || vendor_id == 0 {
+                        return None;
+                }
+
+                let header_type = byte(3, 2);
+                Some(Self {
+                        address,
+                        vendor_id,
+                        device_id: (regs[0] >> 16) as u16,
+                        revision: byte(2, 0),
+                        prog_if: byte(2, 1),
+                        subclass: byte(2, 2),
+                        class: byte(2, 3),
+                        header_type: header_type & !MULTIFUNCTION,
+                        multifunction: header_type & MULTIFUNCTION != 0,
+                        interrupt_line: Some(byte(15, 0)).filter(|&line| line != NO_INTERRUPT_LINE),
+                        interrupt_pin: Some(byte(15, 1)).filter(|&pin| (1..=4).contains(&pin)),
+                        secondary_bus: match header_type & !MULTIFUNCTION {
+                                HEADER_BRIDGE => Some(byte(6, 1)),
+                                _ => None,
+                        },
+                        bars: [None; 6],
+                })
+        }
+
+        /// Returns the number of BAR registers of the header type.
+        pub const fn bar_registers(&self) -> usize
+        {
+                match self.header_type {
+                        0x00 => 6,
+                        HEADER_BRIDGE => 2,
+                        _ => 0,
+                }
+        }
+
+        /// Returns `true` if the function is a host bridge, behind which the
+        /// CPU reaches the RAM.
+        pub const fn is_host_bridge(&self) -> bool { self.class == 0x06 && self.subclass == 0x00 }
+
+        /// Returns the name of the class of the function.
+        pub fn class_name(&self) -> &'static str { class_name(self.class, self.subclass) }
+}
+
+/// Returns the name `lspci` gives to a class and subclass.
+pub const fn class_name(
+        class: u8,
+        subclass: u8,
+) -> &'static str
+{
+        match (class, subclass) {
+                (0x01, 0x01) => "IDE interface",
+                (0x01, 0x06) => "SATA controller",
+                (0x01, 0x08) => "Non-Volatile memory controller",
+                (0x01, _) => "Mass storage controller",
+                (0x02, 0x00) => "Ethernet controller",
+                (0x02, _) => "Network controller",
+                (0x03, 0x00) => "VGA compatible controller",
+                (0x03, _) => "Display controller",
+                (0x04, 0x01) => "Multimedia audio controller",
+                (0x04, 0x03) => "Audio device",
+                (0x04, _) => "Multimedia controller",
+                (0x05, _) => "Memory controller",
+                (0x06, 0x00) => "Host bridge",
+                (0x06, 0x01) => "ISA bridge",
+                (0x06, 0x04) => "PCI bridge",
+                (0x06, _) => "Bridge",
+                (0x07, _) => "Communication controller",
+                (0x08, _) => "System peripheral",
+                (0x09, _) => "Input device controller",
+                (0x0c, 0x03) => "USB controller",
+                (0x0c, 0x05) => "SMBus",
+                (0x0c, _) => "Serial bus controller",
+                (0x0d, _) => "Wireless controller",
+                _ => "Unclassified device",
+        }
+}
+
+/// Criteria drivers match functions against, `None` matching anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Match
+{
+        pub vendor_id: Option<u16>,
+        pub device_id: Option<u16>,
+        pub class:     Option<u8>,
+        pub subclass:  Option<u8>,
+}
+
+impl Match
+{
+        /// Matches the functions with the given IDs.
+        pub const fn device(
+                vendor_id: u16,
+                device_id: u16,
+        ) -> Self
+        {
+                Self {
+                        vendor_id: Some(vendor_id),
+                        device_id: Some(device_id),
+                        class:     None,
+                        subclass:  None,
+                }
+        }
+
+        /// Matches the functions of the given class and subclass.
+        pub const fn class(
+                class: u8,
+                subclass: u8,
+        ) -> Self
+        {
+                Self {
+                        vendor_id: None,
+                        device_id: None,
+                        class:     Some(class),
+                        subclass:  Some(subclass),
+                }
+        }
+
+        pub fn matches(
+                &self,
+                function: &Function,
+        ) -> bool
+        {
+                self.vendor_id.is_none_or(|id| id == function.vendor_id)
+                        && self.device_id.is_none_or(|id| id == function.device_id)
+                        && self.class.is_none_or(|class| class == function.class)
+                        && self.subclass
+                                .is_none_or(|subclass| subclass == function.subclass)
+        }
+}
+
+/// Functions found by the bus scan, in scan order.
+#[derive(Debug, Clone)]
+pub struct Registry
+{
+        functions: [Option<Function>; MAX_FUNCTIONS],
+        len:       usize,
+}
+
+impl Registry
+{
+        pub const fn new() -> Self
+        {
+                Self {
+                        functions: [None; MAX_FUNCTIONS],
+                        len:       0,
+                }
+        }
+
+        /// Adds `function`, returning `false` if the registry is full.
+        pub fn add(
+                &mut self,
+                function: Function,
+        ) -> bool
+        {
+                let Some(slot) = self.functions.get_mut(self.len) else {
+                        return false;
+                };
+
+                *slot = Some(function);
+                self.len += 1;
+                true
+        }
+
+        pub fn clear(&mut self)
+        {
+                self.functions.fill(None);
+                self.len = 0;
+        }
+
+        pub fn len(&self) -> usize { self.len }
+
+        pub fn is_empty(&self) -> bool { self.len == 0 }
+
+        pub fn iter(&self) -> impl Iterator<Item = &Function>
+        {
+                self.functions[..self.len].iter().flatten()
+        }
+
+        /// Returns the functions `criteria` matches.
+        pub fn matching(
+                &self,
+                criteria: Match,
+        ) -> impl Iterator<Item = &Function>
+        {
+                self.iter()
+                        .filter(move |function| criteria.matches(function))
+        }
+
+        /// Returns the first function `criteria` matches.
+        pub fn find(
+                &self,
+                criteria: &Match,
+        ) -> Option<&Function>
+        {
+                self.matching(*criteria).next()
+        }
+}
+
+impl Default for Registry
+{
+        fn default() -> Self { Self::new() }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        /// Header of the PIIX3 IDE controller of QEMU.
+        fn ide_header() -> [u32; HEADER_REGISTERS]
+        {
+                let mut regs = [0; HEADER_REGISTERS];
+
+                regs[0] = 0x7010_8086;
+                regs[2] = 0x0101_8000;
+                regs[3] = 0x0000_0000;
+                regs[8] = 0x0000_c041;
+                regs[15] = 0x0000_00ff;
+                regs
+        }
+
+        #[test]
+        fn encodes_config_addresses()
+        {
+                let address = Address::new(1, 2, 3);
+
+                assert_eq!(address.config_address(0x3e), 0x8001_133c);
+                assert_eq!(address.to_string(), "01:02.3");
+        }
+
+        #[test]
+        fn decodes_headers()
+        {
+                let function = Function::from_header(Address::new(0, 1, 1), &ide_header()).unwrap();
+
+                assert_eq!((function.vendor_id, function.device_id), (0x8086, 0x7010));
+                assert_eq!(
+                        (function.class, function.subclass, function.prog_if),
+                        (0x01, 0x01, 0x80)
+                );
+                assert_eq!(function.class_name(), "IDE interface");
+                assert_eq!(function.interrupt_line, None);
+                assert_eq!(function.bar_registers(), 6);
+                assert!(!function.multifunction);
+                assert!(!function.is_host_bridge());
+
+                let mut bridge = ide_header();
+                bridge[2] = 0x0604_0000;
+                bridge[3] = 0x0081_0000;
+                bridge[6] = 0x0002_0200;
+                let bridge = Function::from_header(Address::new(0, 3, 0), &bridge).unwrap();
+                assert_eq!(bridge.secondary_bus, Some(2));
+                assert!(bridge.multifunction);
+                assert!(!bridge.is_host_bridge());
+
+                let mut host = ide_header();
+                host[2] = 0x0600_0000;
+                let host = Function::from_header(Address::new(0, 0, 0), &host).unwrap();
+                assert!(host.is_host_bridge());
+
+                let mut absent = ide_header();
+                absent[0] = 0xffff_ffff;
+                assert_eq!(Function::from_header(Address::new(0, 4, 0), &absent), None);
+        }
+
+        #[test]
+        fn decodes_bars()
+        {
+                assert_eq!(
+                        Bar::decode(0xc041, 0xffff_fff1),
+                        Some(Bar::Io {
+                                port: 0xc040,
+                                size: 16,
+                        })
+                );
+                assert_eq!(
+                        Bar::decode(0xfebf_0000, 0xfffe_0000),
+                        Some(Bar::Memory {
+                                address:      0xfebf_0000,
+                                size:         0x2_0000,
+                                prefetchable: false,
+                                wide:         false,
+                        })
+                );
+                assert_eq!(
+                        Bar::decode(0x0000_0008_0000_000c, 0xffff_ffff_ffff_c00c),
+                        Some(Bar::Memory {
+                                address:      0x8_0000_0000,
+                                size:         0x4000,
+                                prefetchable: true,
+                                wide:         true,
+                        })
+                );
+                assert!(Bar::is_wide(0xc));
+                assert_eq!(Bar::decode(0, 0), None);
+        }
+
+        #[test]
+        fn registry_matches_functions()
+        {
+                let mut registry = Registry::new();
+                let ide = Function::from_header(Address::new(0, 1, 1), &ide_header()).unwrap();
+                let mut vga = ide;
+                vga.address = Address::new(0, 2, 0);
+                vga.vendor_id = 0x1234;
+                vga.device_id = 0x1111;
+                vga.class = 0x03;
+                vga.subclass = 0x00;
+
+                assert!(registry.add(ide));
+                assert!(registry.add(vga));
+                assert_eq!(registry.len(), 2);
+                assert_eq!(
+                        registry.find(&Match::class(0x01, 0x01)).unwrap().address,
+                        ide.address
+                );
+                assert_eq!(
+                        registry.find(&Match::device(0x1234, 0x1111))
+                                .unwrap()
+                                .address,
+                        vga.address
+                );
+                assert_eq!(registry.matching(Match::default()).count(), 2);
+                assert_eq!(registry.find(&Match::class(0x02, 0x00)), None);
+
+                for _ in 2..MAX_FUNCTIONS {
+                        assert!(registry.add(ide));
+                }
+                assert!(!registry.add(ide));
+        }
+}
@@ -0,0 +1,261 @@
+//! Decoding of the CMOS real-time clock registers.
+//!
+//! The RTC keeps the date and time in CMOS registers, whose format depends
+//! on Status Register B: values are either binary or BCD, and hours either
+//! count to 23 or from 1 to 12 with bit 7 set after noon. The year only has
+//! two digits, the century being in another register when the firmware
+//! provides one.
+//!
+//! Reference: https://wiki.osdev.org/CMOS
+use bitflags::bitflags;
+
+use crate::time::DateTime;
+
+bitflags! {
+    /// Bits of Status Register B.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct StatusB: u8 {
+        /// Hours count from 0 to 23
+        const HOURS_24 = 0x02;
+        /// Values are binary instead of BCD
+        const BINARY = 0x04;
+        /// IRQ8 is raised after each update
+        const UPDATE_ENDED = 0x10;
+        /// IRQ8 is raised at the alarm time
+        const ALARM = 0x20;
+        /// IRQ8 is raised at the periodic rate of Status Register A
+        const PERIODIC = 0x40;
+        /// Updates are stopped, so the clock can be set
+        const SET = 0x80;
+    }
+}
+
+bitflags! {
+    /// Interrupts reported by Status Register C.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Interrupts: u8 {
+        /// An update ended
+        const UPDATE_ENDED = 0x10;
+        /// The alarm time was reached
+        const ALARM = 0x20;
+        /// A periodic interrupt period elapsed
+        const PERIODIC = 0x40;
+        /// One of the enabled interrupts is pending
+        const IRQ = 0x80;
+    }
+}
+
+/// Bit of the hours register set after noon, in 12-hour mode.
+const PM: u8 = 0x80;
+
+/// Value of the alarm registers matching every value.
+pub const ALARM_ANY: u8 = 0xc0;
+
+/// Century assumed when the firmware has no century register.
+const DEFAULT_CENTURY: u16 = 20;
+
+/// Lowest century register value taken as a century, lower values such as 0
+/// coming from a missing register.
+const MIN_CENTURY: u16 = 19;
+
+/// Raw values of the date and time registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers
+{
+        pub second:   u8,
+        pub minute:   u8,
+        pub hour:     u8,
+        pub day:      u8,
+        pub month:    u8,
+        pub year:     u8,
+        /// Century register, if the firmware has one
+        pub century:  Option<u8>,
+        pub status_b: StatusB,
+}
+
+/// Converts a BCD byte to binary.
+pub const fn from_bcd(value: u8) -> u8 { (value >> 4) * 10 + (value & 0x0f) }
+
+/// Converts a binary value below 100 to BCD.
+pub const fn to_bcd(value: u8) -> u8 { ((value / 10) << 4) | (value % 10) }
+
+impl StatusB
+{
+        /// Decodes a register value other than the hours.
+        pub const fn decode(
+                self,
+                value: u8,
+        ) -> u8
+        {
+                match self.contains(StatusB::BINARY) {
+                        true => value,
+                        false => from_bcd(value),
+                }
+        }
+
+        /// Encodes a value other than the hours, below 100.
+        pub const fn encode(
+                self,
+                value: u8,
+        ) -> u8
+        {
+                match self.contains(StatusB::BINARY) {
+                        true => value,
+                        false => to_bcd(value),
+                }
+        }
+
+        /// Decodes the hours register to an hour from 0 to 23.
+        pub const fn decode_hour(
+                self,
+                value: u8,
+        ) -> u8
+        {
+                if self.contains(StatusB::HOURS_24) {
+                        return self.decode(value);
+                }
+
+                // 12 AM is midnight, 12 PM is noon.
+                let hour = self.decode(value & !PM) % 12;
+                match value & PM != 0 {
+                        true => hour + 12,
+                        false => hour,
+                }
+        }
+
+        /// Encodes an hour from 0 to 23 for the hours register.
+        pub const fn encode_hour(
+                self,
+                hour: u8,
+        ) -> u8
+        {
+                if self.contains(StatusB::HOURS_24) {
+                        return self.encode(hour);
+                }
+
+                let pm = match hour >= 12 {
+                        true => PM,
+                        false => 0,
+                };
+                match hour % 12 {
+                        0 => self.encode(12) | pm,
+                        hour => self.encode(hour) | pm,
+                }
+        }
+}
+
+impl Registers
+{
+        /// Returns the date and time the registers hold, or `None` if they
+        /// are not a valid date.
+        pub fn to_datetime(&self) -> Option<DateTime>
+        {
+                let format = self.status_b;
+                let century = self
+                        .century
+                        .map(|century| format.decode(century) as u16)
+                        .filter(|century| (MIN_CENTURY..100).contains(century))
+                        .unwrap_or(DEFAULT_CENTURY);
+                let time = DateTime {
+                        year:   century * 100 + format.decode(self.year) as u16,
+                        month:  format.decode(self.month),
+                        day:    format.decode(self.day),
+                        hour:   format.decode_hour(self.hour),
+                        minute: format.decode(self.minute),
+                        second: format.decode(self.second),
+                };
+
+                time.is_valid().then_some(time)
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn decodes_bcd_12_hours()
+        {
+                let regs = Registers {
+                        second:   0x56,
+                        minute:   0x34,
+                        hour:     PM | 0x12,
+                        day:      0x18,
+                        month:    0x10,
+                        year:     0x26,
+                        century:  None,
+                        status_b: StatusB::empty(),
+                };
+
+                assert_eq!(
+                        regs.to_datetime().unwrap().to_string(),
+                        "2026-10-18 12:34:56"
+                );
+        }
+
+        #[test]
+        fn decodes_binary_24_hours_with_century()
+        {
+                let regs = Registers {
+                        second:   59,
+                        minute:   0,
+                        hour:     23,
+                        day:      31,
+                        month:    12,
+                        year:     99,
+                        century:  Some(19),
+                        status_b: StatusB::BINARY | StatusB::HOURS_24,
+                };
+
+                assert_eq!(
+                        regs.to_datetime().unwrap().to_string(),
+                        "1999-12-31 23:00:59"
+                );
+        }
+
+        #[test]
+        fn hours_round_trip()
+        {
+                let formats = [
+                        StatusB::empty(),
+                        StatusB::BINARY,
+                        StatusB::HOURS_24,
+                        StatusB::BINARY | StatusB::HOURS_24,
+                ];
+
+                for format in formats {
+                        for hour in 0..24 {
+                                assert_eq!(format.decode_hour(format.encode_hour(hour)), hour);
+                        }
+                }
+                assert_eq!(StatusB::empty().encode_hour(0), 0x12);
+                assert_eq!(StatusB::empty().encode_hour(13), PM | 0x01);
+        }
+
+        #[test]
+        fn rejects_invalid_dates()
+        {
+                let regs = Registers {
+                        second:   0,
+                        minute:   0,
+                        hour:     0,
+                        day:      0x31,
+                        month:    0x02,
+                        year:     0x24,
+                        century:  Some(0x20),
+                        status_b: StatusB::HOURS_24,
+                };
+
+                assert_eq!(regs.to_datetime(), None);
+
+                let regs = Registers {
+                        day: 0x28,
+                        century: Some(0),
+                        ..regs
+                };
+                assert_eq!(regs.to_datetime().unwrap().year, 2024);
+        }
+}
@@ -0,0 +1,251 @@
+//! Boot screen.
+//!
+//! Displays the project banner at the top of the console, followed by one
+//! line per boot stage:
+//!
+//! ```text
+//! [ .... ] Memory map      <- while the stage is running
+//! [  OK  ] Memory map      <- once it succeeded
+//! [ FAIL ] Video           <- if it failed
+//! ```
+use crate::vga::VGAColor;
+use crate::vga::backend::TextBackend;
+use crate::vga::console::VgaConsole;
+
+/// ASCII-art banner of the project.
+pub const BANNER: &str = include_str!(".assets/header.txt");
+
+/// Colors used for the banner lines, from top to bottom.
+const BANNER_COLORS: [VGAColor; 4] = [
+        VGAColor::LightCyan,
+        VGAColor::Cyan,
+        VGAColor::LightBlue,
+        VGAColor::Blue,
+];
+
+const PENDING: &[u8; 8] = b"[ .... ]";
+const OK: &[u8; 8] = b"[  OK  ]";
+const FAIL: &[u8; 8] = b"[ FAIL ]";
+
+/// Draws `banner` centered on a cleared screen, and moves the cursor below
+/// it.
+pub fn draw_banner<B: TextBackend>(
+        con: &mut VgaConsole<B>,
+        banner: &str,
+)
+{
+        let cols = con.grid().cols() as usize;
+        let width = banner.lines().map(str::len).max().unwrap_or(0);
+        let left = cols.saturating_sub(width) / 2;
+        let lines = banner.lines().count();
+
+        con.blank();
+        for (row, line) in banner.lines().enumerate() {
+                let color = BANNER_COLORS[row * BANNER_COLORS.len() / lines];
+
+                con.set_position(left, row);
+                con.cputstr(&line[..line.len().min(cols)], Some(color as u8), None);
+        }
+        con.set_position(0, lines);
+}
+
+/// Boot stage displayed on screen while it runs.
+#[derive(Debug)]
+#[must_use = "a stage stays pending until finished"]
+pub struct Stage
+{
+        /// Line of the stage, as numbered by the console grid
+        line: usize,
+}
+
+/// Prints a pending line for the stage `name`.
+pub fn begin_stage<B: TextBackend>(
+        con: &mut VgaConsole<B>,
+        name: &str,
+) -> Stage
+{
+        if con.grid().column() != 0 {
+                con.putstr("\n");
+        }
+
+        for _ in 0..=PENDING.len() {
+                con.putc(b' ');
+        }
+        con.putstr(name);
+        con.putstr("\n");
+
+        // The newline may have scrolled the screen, the stage is always on the
+        // line above the cursor.
+        let line = con.grid().line().wrapping_sub(1);
+        if let Some(marker) = con.grid().line_start(line) {
+                write_marker(con, marker, PENDING, VGAColor::DarkGray);
+        }
+
+        Stage { line }
+}
+
+impl Stage
+{
+        /// Replaces the pending marker with the stage result.
+        ///
+        /// If the stage line has scrolled out of the screen, the result is
+        /// printed on a new line instead.
+        pub fn finish<B: TextBackend>(
+                self,
+                con: &mut VgaConsole<B>,
+                ok: bool,
+        )
+        {
+                let (marker, color) = match ok {
+                        true => (OK, VGAColor::LightGreen),
+                        false => (FAIL, VGAColor::LightRed),
+                };
+
+                let line = match con.grid().line_start(self.line) {
+                        Some(_) => self.line,
+                        None => begin_stage(con, "").line,
+                };
+                if let Some(pos) = con.grid().line_start(line) {
+                        write_marker(con, pos, marker, color);
+                }
+        }
+}
+
+/// Writes `marker` at `pos` of the text area, with brackets in the default
+/// color.
+fn write_marker<B: TextBackend>(
+        con: &mut VgaConsole<B>,
+        pos: usize,
+        marker: &[u8; 8],
+        color: VGAColor,
+)
+{
+        for (i, &c) in marker.iter().enumerate() {
+                let color = match c {
+                        b'[' | b']' => VGAColor::White,
+                        _ => color,
+                };
+                con.write_cell(pos + i, c, color, VGAColor::Black);
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+        use crate::vga::backend::RamBackend;
+        use crate::vga::snapshot::Snapshot;
+        use crate::vga::{CursorTypes, Resolution};
+
+        fn console(mem: &mut [u16]) -> VgaConsole<RamBackend<'_>>
+        {
+                VgaConsole::new(
+                        RamBackend::new(mem),
+                        VGAColor::White,
+                        VGAColor::Black,
+                        Resolution::R80_25,
+                        Some(CursorTypes::Full),
+                )
+        }
+
+        #[test]
+        fn banner_is_centered()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                draw_banner(&mut con, BANNER);
+
+                let snapshot = Snapshot::<80, 25>::capture(con.visible());
+                let expected: String = BANNER
+                        .lines()
+                        .map(|l| format!("{:19}{}\n", "", l))
+                        .collect();
+                assert!(snapshot.matches(&expected), "{}", snapshot);
+                assert_eq!(con.grid().row(), 7);
+                assert_eq!(con.visible()[19 + 80] >> 8, VGAColor::LightCyan as u16);
+                assert_eq!(con.visible()[19 + 80 * 6] >> 8, VGAColor::Blue as u16);
+        }
+
+        #[test]
+        fn stages_are_updated_in_place()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                con.putstr("boot");
+                let memory = begin_stage(&mut con, "Memory map");
+                let video = begin_stage(&mut con, "Video");
+
+                let snapshot = Snapshot::<80, 25>::capture(con.visible());
+                assert!(snapshot.matches("boot\n[ .... ] Memory map\n[ .... ] Video\n"));
+
+                video.finish(&mut con, false);
+                memory.finish(&mut con, true);
+
+                let snapshot = Snapshot::<80, 25>::capture(con.visible());
+                assert!(snapshot.matches("boot\n[  OK  ] Memory map\n[ FAIL ] Video\n"));
+                assert_eq!(con.visible()[80 + 3] >> 8, VGAColor::LightGreen as u16);
+                assert_eq!(con.visible()[160 + 3] >> 8, VGAColor::LightRed as u16);
+                assert_eq!(con.grid().row(), 3);
+        }
+
+        #[test]
+        fn scrolled_out_stage_is_reprinted()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                let stage = begin_stage(&mut con, "Slow");
+                for _ in 0..30 {
+                        con.putstr("log\n");
+                }
+                stage.finish(&mut con, true);
+
+                let snapshot = Snapshot::<80, 25>::capture(con.visible());
+                assert_eq!(
+                        snapshot.to_string().lines().nth(23).unwrap().trim_end(),
+                        "[  OK  ]"
+                );
+        }
+
+        #[test]
+        fn stages_stay_below_status_rows()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                con.set_split(2);
+                con.write_status(0, con.attribute(), format_args!("status"));
+                let stage = begin_stage(&mut con, "Split");
+                stage.finish(&mut con, true);
+
+                let snapshot = Snapshot::<80, 23>::capture(con.visible());
+                assert!(snapshot.matches("[  OK  ] Split\n"), "{}", snapshot);
+                assert_eq!(con.backend_mut().cells()[0] as u8, b's');
+                assert!(con.backend_mut().cells()[80..160]
+                        .iter()
+                        .all(|&c| c as u8 == b' '));
+        }
+
+        #[test]
+        fn stages_survive_video_memory_wraps()
+        {
+                let mut mem = vec![0; 80 * 30];
+                let mut con = console(&mut mem);
+
+                let stage = begin_stage(&mut con, "Wrap");
+                for _ in 0..30 {
+                        con.putstr("log\n");
+                }
+                // The screen wrapped back to the start of video memory, where
+                // the stage line was, but it has scrolled out.
+                assert!(con.grid().origin() < 80 * 5);
+                stage.finish(&mut con, true);
+
+                let snapshot = Snapshot::<80, 25>::capture(con.visible()).to_string();
+                let lines: Vec<&str> = snapshot.lines().map(str::trim_end).collect();
+                assert_eq!(lines[0], "log");
+                assert_eq!(lines[23], "[  OK  ]");
+        }
+}
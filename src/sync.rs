@@ -0,0 +1,187 @@
+//! Interrupt-safe locking.
+//!
+//! A spinlock taken by the main thread and by an interrupt handler deadlocks
+//! as soon as the interrupt fires while the main thread holds it: the
+//! handler spins forever on a lock that can only be released once it
+//! returns. [`IrqSafeMutex`] disables interrupts for as long as it is locked,
+//! and enables them back on unlock if they were enabled before, so that
+//! nested locks restore the right state.
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+use spin::{Mutex, MutexGuard};
+
+use crate::instructions::cpu;
+
+/// Spinlock masking interrupts while held.
+pub(crate) struct IrqSafeMutex<T>
+{
+        inner: Mutex<T>,
+}
+
+/// Guard of an [`IrqSafeMutex`], unlocking it and restoring the interrupt
+/// flag when dropped.
+pub(crate) struct IrqSafeMutexGuard<'a, T>
+{
+        guard:      ManuallyDrop<MutexGuard<'a, T>>,
+        /// Whether interrupts were enabled before locking
+        interrupts: bool,
+}
+
+impl<T> IrqSafeMutex<T>
+{
+        pub(crate) const fn new(value: T) -> Self
+        {
+                Self {
+                        inner: Mutex::new(value),
+                }
+        }
+
+        /// Disables interrupts, returning whether they were enabled.
+        fn disable_interrupts() -> bool
+        {
+                let interrupts = cpu::interrupts_enabled();
+
+                // SAFETY: Interrupts are enabled back when the guard is
+                // dropped, and handlers cannot take the lock meanwhile.
+                unsafe { cpu::cli() };
+                interrupts
+        }
+
+        /// Restores the interrupt flag saved by
+        /// [`disable_interrupts`](Self::disable_interrupts).
+        fn restore_interrupts(interrupts: bool)
+        {
+                if interrupts {
+                        // SAFETY: Interrupts were enabled before locking.
+                        unsafe { cpu::sti() };
+                }
+        }
+
+        /// Disables interrupts and spins until the lock is acquired.
+        pub(crate) fn lock(&self) -> IrqSafeMutexGuard<'_, T>
+        {
+                let interrupts = Self::disable_interrupts();
+
+                IrqSafeMutexGuard {
+                        guard: ManuallyDrop::new(self.inner.lock()),
+                        interrupts,
+                }
+        }
+
+        /// Acquires the lock if it is free, with interrupts disabled.
+        pub(crate) fn try_lock(&self) -> Option<IrqSafeMutexGuard<'_, T>>
+        {
+                let interrupts = Self::disable_interrupts();
+
+                match self.inner.try_lock() {
+                        Some(guard) => Some(IrqSafeMutexGuard {
+                                guard: ManuallyDrop::new(guard),
+                                interrupts,
+                        }),
+                        None => {
+                                Self::restore_interrupts(interrupts);
+                                None
+                        }
+                }
+        }
+
+        pub(crate) fn is_locked(&self) -> bool { self.inner.is_locked() }
+
+        /// Releases the lock without a guard.
+        ///
+        /// # Safety
+        /// The guard holding the lock must never be used again, as when the
+        /// code holding it panicked. Its interrupt flag is not restored.
+        pub(crate) unsafe fn force_unlock(&self) { self.inner.force_unlock(); }
+}
+
+impl<T> Deref for IrqSafeMutexGuard<'_, T>
+{
+        type Target = T;
+
+        fn deref(&self) -> &T { &self.guard }
+}
+
+impl<T> DerefMut for IrqSafeMutexGuard<'_, T>
+{
+        fn deref_mut(&mut self) -> &mut T { &mut self.guard }
+}
+
+impl<T> Drop for IrqSafeMutexGuard<'_, T>
+{
+        fn drop(&mut self)
+        {
+                // SAFETY: The guard is dropped once, here, before interrupts are
+                // enabled back.
+                unsafe { ManuallyDrop::drop(&mut self.guard) };
+                IrqSafeMutex::<T>::restore_interrupts(self.interrupts);
+        }
+}
+
+impl<T: fmt::Debug> fmt::Debug for IrqSafeMutex<T>
+{
+        fn fmt(
+                &self,
+                f: &mut fmt::Formatter,
+        ) -> fmt::Result
+        {
+                f.debug_struct("IrqSafeMutex")
+                        .field("locked", &self.is_locked())
+                        .finish_non_exhaustive()
+        }
+}
+
+#[cfg(test)]
+mod tests
+{
+        use kfs::apic::ISA_IRQS;
+
+        use super::*;
+        use crate::drivers::{irq, pic};
+
+        /// Runs `f` with interrupts enabled, every IRQ being masked as there
+        /// is no IDT to handle them.
+        fn with_interrupts(f: impl FnOnce())
+        {
+                let pic_masks = pic::masks();
+                let masked: [bool; ISA_IRQS] = core::array::from_fn(|i| irq::is_masked(i as u8));
+
+                for i in 0..ISA_IRQS {
+                        irq::set_masked(i as u8, true);
+                }
+                pic::set_masks(u16::MAX);
+                // SAFETY: No IRQ is delivered, and interrupts are disabled
+                // back before they are unmasked.
+                unsafe { cpu::sti() };
+                f();
+                // SAFETY: Disabling interrupts has no other effect.
+                unsafe { cpu::cli() };
+                for (i, &masked) in masked.iter().enumerate() {
+                        irq::set_masked(i as u8, masked);
+                }
+                pic::set_masks(pic_masks);
+        }
+
+        #[test_case]
+        fn lock_masks_interrupts()
+        {
+                let mutex = IrqSafeMutex::new(0);
+
+                with_interrupts(|| {
+                        {
+                                let mut guard = mutex.lock();
+                                *guard += 1;
+                                assert!(!cpu::interrupts_enabled());
+                                assert!(mutex.try_lock().is_none());
+                        }
+                        assert!(cpu::interrupts_enabled());
+                });
+
+                // Interrupts stay disabled after unlocking if they were.
+                drop(mutex.lock());
+                assert!(!cpu::interrupts_enabled());
+                assert_eq!(*mutex.try_lock().unwrap(), 1);
+        }
+}
@@ -1,3 +1,8 @@
+#[cfg(test)]
+use crate::drivers::video;
+#[cfg(test)]
+use crate::println;
+
 #[cfg(test)]
 pub fn test_runner(tests: &[&dyn Fn()])
 {
@@ -8,3 +13,44 @@ pub fn test_runner(tests: &[&dyn Fn()])
         }
         qemu::exit(qemu::QemuExitCode::Success);
 }
+
+/// Maximum number of mismatching cells printed by [`assert_screen`].
+#[cfg(test)]
+const MAX_REPORTED_CELLS: usize = 10;
+
+/// Panics if the visible screen differs from the `expected` golden text,
+/// after printing the mismatching cells.
+#[cfg(test)]
+#[track_caller]
+pub fn assert_screen(expected: &str)
+{
+        let snapshot = video::snapshot();
+        let mismatches = snapshot.diff(expected).count();
+
+        if mismatches == 0 {
+                return;
+        }
+
+        println!("\nScreen mismatch (row, col): expected / actual");
+        for diff in snapshot.diff(expected).take(MAX_REPORTED_CELLS) {
+                println!(
+                        "  ({:2}, {:2}): {:?} / {:?}",
+                        diff.row, diff.col, diff.expected, diff.actual
+                );
+        }
+        if mismatches > MAX_REPORTED_CELLS {
+                println!("  ... and {} more", mismatches - MAX_REPORTED_CELLS);
+        }
+        panic!("{} cells differ from the golden screen", mismatches);
+}
+
+/// Compares the visible screen against a golden file, see [`assert_screen`].
+///
+/// The path is relative to the file invoking the macro, as for
+/// [`include_str!`].
+#[macro_export]
+macro_rules! assert_screen {
+        ($path:literal) => {
+                $crate::test::assert_screen(include_str!($path))
+        };
+}
@@ -0,0 +1,151 @@
+//! Calendar date and time.
+use core::fmt;
+
+/// Wall-clock date and time, in the time zone the clock is set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime
+{
+        pub year:   u16,
+        /// Month, from 1 to 12
+        pub month:  u8,
+        /// Day of the month, from 1
+        pub day:    u8,
+        pub hour:   u8,
+        pub minute: u8,
+        pub second: u8,
+}
+
+/// Returns whether `year` has a February 29th.
+pub const fn is_leap_year(year: u16) -> bool
+{
+        year.is_multiple_of(4) && !year.is_multiple_of(100) || year.is_multiple_of(400)
+}
+
+/// Returns the number of days of `month` in `year`.
+pub const fn days_in_month(
+        year: u16,
+        month: u8,
+) -> u8
+{
+        match month {
+                2 if is_leap_year(year) => 29,
+                2 => 28,
+                4 | 6 | 9 | 11 => 30,
+                _ => 31,
+        }
+}
+
+impl DateTime
+{
+        /// Returns whether every field is in range, the day included.
+        pub const fn is_valid(&self) -> bool
+        {
+                self.month >= 1
+                        && self.month <= 12
+                        && self.day >= 1
+                        && self.day <= days_in_month(self.year, self.month)
+                        && self.hour < 24
+                        && self.minute < 60
+                        && self.second < 60
+        }
+
+        /// Returns the number of seconds since 1970-01-01 00:00:00.
+        ///
+        /// Dates before 1970 give 0.
+        pub const fn unix_time(&self) -> u64
+        {
+                // Days since 0000-03-01, counting years from March so that
+                // the leap day ends them.
+                let (year, month) = match self.month {
+                        1 | 2 => (self.year as u64 - 1, self.month as u64 + 9),
+                        _ => (self.year as u64, self.month as u64 - 3),
+                };
+                let days = year * 365 + year / 4 - year / 100
+                        + year / 400
+                        + (153 * month + 2) / 5
+                        + self.day as u64
+                        - 1;
+                // 1970-01-01 is day 719468.
+                let Some(days) = days.checked_sub(719_468) else {
+                        return 0;
+                };
+
+                days * 86_400
+                        + self.hour as u64 * 3600
+                        + self.minute as u64 * 60
+                        + self.second as u64
+        }
+}
+
+impl fmt::Display for DateTime
+{
+        /// Formats as `YYYY-MM-DD HH:MM:SS`.
+        fn fmt(
+                &self,
+                f: &mut fmt::Formatter<'_>,
+        ) -> fmt::Result
+        {
+                write!(
+                        f,
+                        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                        self.year, self.month, self.day, self.hour, self.minute, self.second
+                )
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        const fn date(
+                year: u16,
+                month: u8,
+                day: u8,
+        ) -> DateTime
+        {
+                DateTime {
+                        year,
+                        month,
+                        day,
+                        hour: 0,
+                        minute: 0,
+                        second: 0,
+                }
+        }
+
+        #[test]
+        fn validates_days()
+        {
+                assert!(date(2024, 2, 29).is_valid());
+                assert!(!date(2023, 2, 29).is_valid());
+                assert!(!date(1900, 2, 29).is_valid());
+                assert!(date(2000, 2, 29).is_valid());
+                assert!(!date(2024, 4, 31).is_valid());
+                assert!(!date(2024, 13, 1).is_valid());
+        }
+
+        #[test]
+        fn converts_to_unix_time()
+        {
+                let mut time = date(2026, 10, 18);
+                time.hour = 12;
+                time.minute = 34;
+                time.second = 56;
+
+                assert_eq!(date(1970, 1, 1).unix_time(), 0);
+                assert_eq!(date(2000, 3, 1).unix_time(), 951_868_800);
+                assert_eq!(time.unix_time(), 1_792_326_896);
+                assert_eq!(date(1969, 12, 31).unix_time(), 0);
+        }
+
+        #[test]
+        fn displays_iso_8601()
+        {
+                let mut time = date(2026, 1, 2);
+                time.hour = 3;
+                time.second = 9;
+
+                assert_eq!(time.to_string(), "2026-01-02 03:00:09");
+        }
+}
@@ -0,0 +1,137 @@
+//! Text cell attributes.
+//!
+//! A text cell attribute byte holds the foreground color in bits 3:0, and
+//! the background color in bits 6:4. Bit 7 is either:
+//! - the blink bit, when the Attribute Controller blink mode is enabled (the
+//!   BIOS default), leaving 8 background colors,
+//! - the background intensity bit otherwise, giving 16 background colors.
+//!
+//! The hardware has no underline in color text modes. In mono-compatible
+//! fashion, the CRTC Underline Location register draws a line under every
+//! character whose foreground is blue (`x001`), so underlined text is shown
+//! with a blue foreground once underlining is enabled.
+//!
+//! Underlining is a display-wide setting: while it is on, every blue or light
+//! blue character is underlined, whatever attribute it was written with. The
+//! console turns it on with the first underlined character, and off once no
+//! blue character is displayed anymore.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/attrreg.htm#10
+use bitflags::bitflags;
+
+use super::VGAColor;
+
+bitflags! {
+    /// Text rendition on top of the colors.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct AttributeFlags: u8 {
+        /// Brighter foreground
+        const BOLD = 1 << 0;
+        /// Line under the text, shown as a blue foreground that underlines
+        /// every blue character on screen
+        const UNDERLINE = 1 << 1;
+        /// Blinking text, only when blink mode is enabled
+        const BLINK = 1 << 2;
+        /// Swapped foreground and background
+        const REVERSE = 1 << 3;
+    }
+}
+
+/// Colors and rendition of the text written by a console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attribute
+{
+        pub foreground: VGAColor,
+        pub background: VGAColor,
+        pub flags:      AttributeFlags,
+}
+
+impl Attribute
+{
+        /// Creates a plain attribute with the given colors.
+        pub const fn new(
+                foreground: VGAColor,
+                background: VGAColor,
+        ) -> Self
+        {
+                Self {
+                        foreground,
+                        background,
+                        flags: AttributeFlags::empty(),
+                }
+        }
+
+        /// Returns the attribute byte of a text cell.
+        ///
+        /// With `blink` mode, bright backgrounds are darkened and bit 7 is
+        /// the blink bit, otherwise [`AttributeFlags::BLINK`] is ignored.
+        pub const fn to_byte(
+                self,
+                blink: bool,
+        ) -> u8
+        {
+                let (mut fg, mut bg) = (self.foreground as u8, self.background as u8);
+
+                if self.flags.contains(AttributeFlags::REVERSE) {
+                        (fg, bg) = (bg, fg);
+                }
+                if self.flags.contains(AttributeFlags::BOLD) {
+                        fg |= 0x08;
+                }
+                if self.flags.contains(AttributeFlags::UNDERLINE) {
+                        fg = (fg & 0x08) | VGAColor::Blue as u8;
+                }
+                if blink {
+                        bg &= 0x07;
+                        if self.flags.contains(AttributeFlags::BLINK) {
+                                bg |= 0x08;
+                        }
+                }
+
+                bg << 4 | fg
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn plain_colors()
+        {
+                let attr = Attribute::new(VGAColor::LightGray, VGAColor::Blue);
+
+                assert_eq!(attr.to_byte(true), 0x17);
+                assert_eq!(attr.to_byte(false), 0x17);
+        }
+
+        #[test]
+        fn blink_depends_on_mode()
+        {
+                let mut attr = Attribute::new(VGAColor::White, VGAColor::LightRed);
+                assert_eq!(attr.to_byte(true), 0x4f);
+                assert_eq!(attr.to_byte(false), 0xcf);
+
+                attr.flags = AttributeFlags::BLINK;
+                assert_eq!(attr.to_byte(true), 0xcf);
+                assert_eq!(attr.to_byte(false), 0xcf);
+
+                attr.background = VGAColor::Red;
+                assert_eq!(attr.to_byte(false), 0x4f);
+        }
+
+        #[test]
+        fn rendition_flags()
+        {
+                let mut attr = Attribute::new(VGAColor::Green, VGAColor::Black);
+
+                attr.flags = AttributeFlags::REVERSE;
+                assert_eq!(attr.to_byte(true), 0x20);
+                attr.flags = AttributeFlags::BOLD;
+                assert_eq!(attr.to_byte(true), 0x0a);
+                attr.flags = AttributeFlags::BOLD | AttributeFlags::UNDERLINE;
+                assert_eq!(attr.to_byte(true), 0x09);
+        }
+}
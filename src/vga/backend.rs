@@ -0,0 +1,286 @@
+//! Text mode output backends.
+//!
+//! A [`TextBackend`] is everything the console needs from the display: the
+//! text cells, and the CRTC registers selecting what is visible and where
+//! the cursor is. The kernel implements it on top of the real VGA hardware,
+//! while [`RamBackend`] keeps everything in memory so the console can run
+//! off-screen and its output can be compared against expected text.
+use super::CursorTypes;
+use super::mode::TextMode;
+
+/// Display used by a text console.
+pub trait TextBackend
+{
+        /// Returns the whole text memory as cells.
+        ///
+        /// Each cell holds the character in bits 7:0 and its attribute in bits
+        /// 15:8.
+        fn cells(&mut self) -> &mut [u16];
+
+        /// Selects the first cell displayed on screen.
+        fn set_start_address(
+                &mut self,
+                start: u16,
+        );
+
+        /// Moves the cursor to the given cell.
+        fn set_cursor_address(
+                &mut self,
+                pos: u16,
+        );
+
+        /// Changes the cursor shape, [`CursorTypes::None`] hides it.
+        fn set_cursor_shape(
+                &mut self,
+                shape: CursorTypes,
+        );
+
+        /// Changes the number of columns and rows displayed on screen.
+        fn set_geometry(
+                &mut self,
+                cols: u8,
+                rows: u8,
+        );
+
+        /// Makes the cells written since the last cursor or start address
+        /// update visible.
+        ///
+        /// Backends displaying the cells directly have nothing to do.
+        fn flush(&mut self)
+        {
+        }
+
+        /// Displays the beginning of text memory on the last `rows` rows of
+        /// the screen, whatever the start address, 0 disabling the split.
+        ///
+        /// Rows above the split show the cells from the start address, as
+        /// usual. The cursor is expected to stay above the split.
+        fn set_split(
+                &mut self,
+                _rows: u8,
+        )
+        {
+        }
+
+        /// Returns the number of scanlines of a row the display can pan
+        /// through with [`TextBackend::set_row_scan`], 0 if it cannot.
+        fn scanlines_per_row(&self) -> u8 { 0 }
+
+        /// Starts the display at scanline `scan` of the row at the start
+        /// address.
+        fn set_row_scan(
+                &mut self,
+                _scan: u8,
+        )
+        {
+        }
+
+        /// Waits for the display to start a new frame, so that the changes
+        /// made next show at once.
+        fn wait_retrace(&mut self)
+        {
+        }
+
+        /// Selects whether bit 7 of the attribute blinks the character, or
+        /// selects a bright background.
+        ///
+        /// Displays are expected to start with blinking enabled, like the
+        /// BIOS sets it up.
+        fn set_blink(
+                &mut self,
+                _enabled: bool,
+        )
+        {
+        }
+
+        /// Selects whether the characters with a blue foreground (`x001`) are
+        /// underlined.
+        fn set_underline(
+                &mut self,
+                _enabled: bool,
+        )
+        {
+        }
+
+        /// Sounds the bell, returning `false` if the display has no way to.
+        fn bell(&mut self) -> bool { false }
+
+        /// Switches the display to `mode`, keeping the content of the text
+        /// cells.
+        fn set_text_mode(
+                &mut self,
+                mode: TextMode,
+        )
+        {
+                let (cols, rows) = mode.dimensions();
+                self.set_geometry(cols, rows);
+        }
+}
+
+/// Text backend writing into a memory buffer.
+#[derive(Debug)]
+pub struct RamBackend<'a>
+{
+        cells:     &'a mut [u16],
+        start:     u16,
+        cursor:    u16,
+        shape:     CursorTypes,
+        cols:      u8,
+        rows:      u8,
+        split:     u8,
+        row_scan:  u8,
+        retraces:  usize,
+        blink:     bool,
+        underline: bool,
+        bells:     usize,
+}
+
+impl<'a> RamBackend<'a>
+{
+        /// Creates a backend using `cells` as text memory.
+        pub fn new(cells: &'a mut [u16]) -> Self
+        {
+                Self {
+                        cells,
+                        start: 0,
+                        cursor: 0,
+                        shape: CursorTypes::None,
+                        cols: 80,
+                        rows: 25,
+                        split: 0,
+                        row_scan: 0,
+                        retraces: 0,
+                        blink: true,
+                        underline: false,
+                        bells: 0,
+                }
+        }
+
+        pub fn start_address(&self) -> u16 { self.start }
+
+        pub fn cursor_address(&self) -> u16 { self.cursor }
+
+        pub fn cursor_shape(&self) -> CursorTypes { self.shape }
+
+        pub fn geometry(&self) -> (u8, u8) { (self.cols, self.rows) }
+
+        pub fn split(&self) -> u8 { self.split }
+
+        pub fn row_scan(&self) -> u8 { self.row_scan }
+
+        /// Returns the number of frames waited for.
+        pub fn retraces(&self) -> usize { self.retraces }
+
+        pub fn blink(&self) -> bool { self.blink }
+
+        pub fn underline(&self) -> bool { self.underline }
+
+        /// Returns the number of times the bell rang.
+        pub fn bells(&self) -> usize { self.bells }
+
+        /// Returns the cells currently displayed from the start address,
+        /// ignoring the split.
+        pub fn visible(&self) -> &[u16]
+        {
+                let start = self.start as usize;
+                let end = start + self.cols as usize * self.rows as usize;
+
+                &self.cells[start..end]
+        }
+
+        /// Returns the text memory cells in `range`.
+        pub fn cells_at(
+                &self,
+                range: core::ops::Range<usize>,
+        ) -> &[u16]
+        {
+                &self.cells[range]
+        }
+
+        /// Returns the displayed rows, from top to bottom.
+        pub fn visible_rows(&self) -> impl Iterator<Item = &[u16]>
+        {
+                self.visible().chunks(self.cols as usize)
+        }
+}
+
+impl TextBackend for RamBackend<'_>
+{
+        fn cells(&mut self) -> &mut [u16] { self.cells }
+
+        fn set_start_address(
+                &mut self,
+                start: u16,
+        )
+        {
+                self.start = start;
+        }
+
+        fn set_cursor_address(
+                &mut self,
+                pos: u16,
+        )
+        {
+                self.cursor = pos;
+        }
+
+        fn set_cursor_shape(
+                &mut self,
+                shape: CursorTypes,
+        )
+        {
+                self.shape = shape;
+        }
+
+        fn set_geometry(
+                &mut self,
+                cols: u8,
+                rows: u8,
+        )
+        {
+                self.cols = cols;
+                self.rows = rows;
+        }
+        fn set_split(
+                &mut self,
+                rows: u8,
+        )
+        {
+                self.split = rows;
+        }
+
+        /// Pans like the 16 scanlines rows of VGA text modes.
+        fn scanlines_per_row(&self) -> u8 { 16 }
+
+        fn set_row_scan(
+                &mut self,
+                scan: u8,
+        )
+        {
+                self.row_scan = scan;
+        }
+
+        fn wait_retrace(&mut self) { self.retraces += 1; }
+
+        fn set_blink(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.blink = enabled;
+        }
+
+        fn set_underline(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.underline = enabled;
+        }
+
+        fn bell(&mut self) -> bool
+        {
+                self.bells += 1;
+                true
+        }
+}
@@ -0,0 +1,91 @@
+//! Console settings chosen at boot.
+//!
+//! The kernel builds a [`Config`] from the Multiboot information and the
+//! command line options:
+//!
+//! ```text
+//! /boot/kfs.bin vga=80x50 theme=gruvbox scroll=smooth
+//! ```
+//!
+//! and hands it to the video driver, which sets the display up once.
+use super::font::Psf;
+use super::mode::TextMode;
+use super::palette::Theme;
+use crate::cmdline;
+use crate::multiboot::FramebufferInfo;
+
+/// Display and console settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config<'a>
+{
+        /// VGA text mode, ignored on a framebuffer
+        pub mode:          TextMode,
+        /// Console colors, the default palette if `None`
+        pub theme:         Option<&'static Theme>,
+        /// Whether the text moves a few scanlines at a time when scrolling
+        pub smooth_scroll: bool,
+        /// Framebuffer set up by the bootloader, used when `font` is given
+        pub framebuffer:   Option<FramebufferInfo>,
+        /// Font to draw the console with on a framebuffer
+        pub font:          Option<Psf<'a>>,
+}
+
+impl Default for Config<'_>
+{
+        fn default() -> Self
+        {
+                Self {
+                        mode:          TextMode::T80x25,
+                        theme:         None,
+                        smooth_scroll: false,
+                        framebuffer:   None,
+                        font:          None,
+                }
+        }
+}
+
+impl Config<'_>
+{
+        /// Reads the `vga=`, `theme=` and `scroll=` options of `cmdline`.
+        ///
+        /// Unknown values keep the default setting.
+        pub fn from_cmdline(cmdline: &str) -> Self
+        {
+                let default = Self::default();
+
+                Self {
+                        mode: cmdline::option(cmdline, "vga")
+                                .and_then(TextMode::find)
+                                .unwrap_or(default.mode),
+                        theme: cmdline::option(cmdline, "theme").and_then(Theme::find),
+                        smooth_scroll: cmdline::option(cmdline, "scroll") == Some("smooth"),
+                        ..default
+                }
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+        use crate::vga::palette;
+
+        #[test]
+        fn reads_cmdline_options()
+        {
+                let config =
+                        Config::from_cmdline("/boot/kfs.bin vga=90x60 theme=Gruvbox scroll=smooth");
+
+                assert_eq!(config.mode, TextMode::T90x60);
+                assert_eq!(config.theme, Some(&palette::GRUVBOX));
+                assert!(config.smooth_scroll);
+        }
+
+        #[test]
+        fn unknown_options_keep_defaults()
+        {
+                let config = Config::from_cmdline("/boot/kfs.bin vga=12x34 theme=neon scroll=fast");
+
+                assert_eq!(config, Config::default());
+        }
+}
@@ -0,0 +1,1139 @@
+//! VGA text mode console.
+//!
+//! The bottom rows of the screen can be split off the scrolling text with
+//! [`VgaConsole::set_split`], to keep status lines displayed. They live at
+//! the beginning of video memory, which the hardware shows below the CRTC
+//! Line Compare scanline whatever the start address:
+//!
+//! ```text
+//! 0 ----------------> +---------------+
+//!                     | status lines  |
+//! text_offset() ----> +---------------+
+//!                     |   TextGrid    |
+//!                     .               .
+//!                     +---------------+ <-- vram end
+//! ```
+use core::fmt;
+
+use super::attribute::{Attribute, AttributeFlags};
+use super::backend::TextBackend;
+use super::mode::TextMode;
+use super::sgr::SgrParser;
+use super::tabs::TabStops;
+use super::text::{BLANK, ScrollDir, TextGrid};
+use super::{CursorTypes, Resolution, VGAColor, cp437};
+
+/// Glyph shown by default for characters Code Page 437 does not have, `■`.
+const FALLBACK_GLYPH: u8 = 0xfe;
+
+/// Number of frames the screen stays inverted for a visual bell.
+const FLASH_FRAMES: usize = 4;
+
+/// What the console does on a bell character (`\x07`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bell
+{
+        /// Ignores it
+        Off,
+        /// Sounds the display bell, or flashes the screen if it has none
+        Audible,
+        /// Flashes the screen
+        Visible,
+}
+
+/// VGA text mode console driver that provides basic text output functionality
+///
+/// This structure manages a VGA text mode console by maintaining the state of
+/// the video memory and providing methods for text output and scrolling. It
+/// supports standard VGA text mode operations including cursor management,
+/// color control, and scrolling.
+///
+/// Strings may change the attribute of the following text with SGR escape
+/// sequences, see [`super::sgr`]. Their characters are translated to Code
+/// Page 437 glyphs, see [`super::cp437`], those without one being shown with
+/// a fallback glyph.
+///
+/// The control characters a line editor needs are interpreted:
+///
+/// | Character | Effect                                             |
+/// |-----------|----------------------------------------------------|
+/// | `\n`      | moves to the beginning of the next line            |
+/// | `\r`      | moves to the beginning of the line                 |
+/// | `\t`      | moves to the next tab stop, or the last column     |
+/// | `\x08`    | moves back one cell, erasing it if configured      |
+/// | `\x07`    | rings the [`Bell`]                                 |
+/// | `\x0c`    | clears the screen                                  |
+///
+/// The cursor and scrolling arithmetic is delegated to a [`TextGrid`], and
+/// every access to the display goes through a [`TextBackend`], so the same
+/// console drives the VGA hardware or an in-memory buffer.
+#[derive(Debug)]
+pub struct VgaConsole<B: TextBackend>
+{
+        /// Display the console writes to
+        vc_backend:           B,
+        /// Current attribute for text output
+        vc_attribute:         Attribute,
+        /// Attribute restored by an SGR reset
+        vc_default_attribute: Attribute,
+        /// Escape sequences parser state
+        vc_sgr:               SgrParser,
+        /// Glyph shown for characters Code Page 437 does not have
+        vc_fallback:          u8,
+        /// Columns a tab moves to
+        vc_tabs:              TabStops,
+        /// Whether the text continues on the next line at the last column,
+        /// instead of overwriting it
+        vc_autowrap:          bool,
+        /// Whether a backspace blanks the cell it moves to
+        vc_erase_backspace:   bool,
+        /// Response to the bell character
+        vc_bell:              Bell,
+        /// Whether bit 7 of the attribute blinks instead of brightening the
+        /// background
+        vc_blink:             bool,
+        /// Whether the display underlines characters yet
+        vc_underline:         bool,
+        /// Number of status rows split off the bottom of the screen
+        vc_split:             u8,
+        /// Scanlines the text moves by each frame when scrolling, 0 to move
+        /// whole rows at once
+        vc_smooth_scroll:     u8,
+        /// Start address last given to the display
+        vc_start_address:     u16,
+        /// Current cursor appearance type
+        vc_cursor_type:       CursorTypes,
+        /// Position of the console in video memory
+        vc_grid:              TextGrid,
+}
+
+impl<B: TextBackend> VgaConsole<B>
+{
+        /// Creates a new VGA text mode console with the specified
+        /// configuration.
+        pub fn new(
+                backend: B,
+                foreground_color: VGAColor,
+                background_color: VGAColor,
+                resolution: Resolution,
+                cursor_type: Option<CursorTypes>,
+        ) -> Self
+        {
+                let (cols, rows) = resolution.dimensions();
+
+                Self::with_geometry(
+                        backend,
+                        foreground_color,
+                        background_color,
+                        cols,
+                        rows,
+                        cursor_type,
+                )
+        }
+
+        /// Creates a console of `cols`×`rows` characters, for displays not
+        /// limited to the standard resolutions.
+        pub fn with_geometry(
+                mut backend: B,
+                foreground_color: VGAColor,
+                background_color: VGAColor,
+                cols: u8,
+                rows: u8,
+                cursor_type: Option<CursorTypes>,
+        ) -> Self
+        {
+                let vram_cells = backend.cells().len();
+                let attribute = Attribute::new(foreground_color, background_color);
+
+                let mut con = Self {
+                        vc_backend:           backend,
+                        vc_attribute:         attribute,
+                        vc_default_attribute: attribute,
+                        vc_sgr:               SgrParser::new(),
+                        vc_fallback:          FALLBACK_GLYPH,
+                        vc_tabs:              TabStops::default(),
+                        vc_autowrap:          true,
+                        vc_erase_backspace:   false,
+                        vc_bell:              Bell::Audible,
+                        vc_blink:             true,
+                        vc_underline:         false,
+                        vc_split:             0,
+                        vc_smooth_scroll:     0,
+                        vc_start_address:     0,
+                        vc_cursor_type:       CursorTypes::None,
+                        vc_grid:              TextGrid::new(vram_cells, cols, rows),
+                };
+
+                con.resize(cols, rows);
+                con.cursor(cursor_type);
+
+                con
+        }
+
+        /// Returns the display the console writes to.
+        pub fn backend(&self) -> &B { &self.vc_backend }
+
+        /// Returns the display the console writes to, to use features the
+        /// console does not know about.
+        pub fn backend_mut(&mut self) -> &mut B { &mut self.vc_backend }
+
+        /// Returns the position of the console in video memory.
+        pub fn grid(&self) -> &TextGrid { &self.vc_grid }
+
+        /// Returns the cells currently displayed on screen, above the status
+        /// rows.
+        pub fn visible(&mut self) -> &[u16]
+        {
+                let start = self.text_offset() + self.vc_grid.visible_origin();
+                let end = start + self.vc_grid.screen_cells();
+
+                &self.vc_backend.cells()[start..end]
+        }
+
+        /// Changes the default colors used for text output.
+        pub fn set_colors(
+                &mut self,
+                foreground: VGAColor,
+                background: VGAColor,
+        )
+        {
+                self.vc_attribute.foreground = foreground;
+                self.vc_attribute.background = background;
+                self.vc_default_attribute = Attribute::new(foreground, background);
+        }
+
+        /// Returns the attribute used for text output.
+        pub fn attribute(&self) -> Attribute { self.vc_attribute }
+
+        /// Changes the attribute used for text output, until the next SGR
+        /// sequence.
+        pub fn set_attribute(
+                &mut self,
+                attribute: Attribute,
+        )
+        {
+                self.vc_attribute = attribute;
+        }
+
+        /// Changes the glyph shown for characters Code Page 437 does not
+        /// have, `■` by default.
+        pub fn set_fallback(
+                &mut self,
+                glyph: u8,
+        )
+        {
+                self.vc_fallback = glyph;
+        }
+
+        /// Returns the columns a tab moves to.
+        pub fn tab_stops(&self) -> &TabStops { &self.vc_tabs }
+
+        /// Replaces the columns a tab moves to, a stop every 8 columns by
+        /// default.
+        pub fn set_tab_stops(
+                &mut self,
+                tabs: TabStops,
+        )
+        {
+                self.vc_tabs = tabs;
+        }
+
+        /// Selects whether text reaching the last column continues on the
+        /// next line, or keeps overwriting the last column.
+        pub fn set_autowrap(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.vc_autowrap = enabled;
+        }
+
+        /// Selects whether a backspace blanks the cell it moves back to.
+        pub fn set_erase_backspace(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.vc_erase_backspace = enabled;
+        }
+
+        /// Changes what the bell character does, [`Bell::Audible`] by
+        /// default.
+        pub fn set_bell(
+                &mut self,
+                bell: Bell,
+        )
+        {
+                self.vc_bell = bell;
+        }
+
+        /// Gives bit 7 of the attribute to the background intensity instead
+        /// of blinking, making 16 background colors available.
+        pub fn set_bright_background(
+                &mut self,
+                enabled: bool,
+        )
+        {
+                self.vc_blink = !enabled;
+                self.vc_backend.set_blink(self.vc_blink);
+        }
+
+        /// Returns the number of status rows split off the bottom of the
+        /// screen.
+        pub fn split(&self) -> u8 { self.vc_split }
+
+        /// Keeps the last `rows` rows of the screen for status lines, 0
+        /// removing them.
+        ///
+        /// The scrolling text shrinks or grows by the same number of rows,
+        /// keeping the lines up to the cursor. Status rows start blank. At
+        /// least one row is left to the scrolling text.
+        pub fn set_split(
+                &mut self,
+                rows: u8,
+        )
+        {
+                let (cols, screen_rows) = (self.vc_grid.cols(), self.screen_rows());
+
+                self.relayout(cols, screen_rows, rows.min(screen_rows - 1));
+                self.redisplay();
+        }
+
+        /// Writes the formatted `args` on status row `row` with `attribute`,
+        /// truncated or padded to the screen width.
+        pub fn write_status(
+                &mut self,
+                row: usize,
+                attribute: Attribute,
+                args: fmt::Arguments,
+        )
+        {
+                if row >= self.vc_split as usize {
+                        return;
+                }
+
+                let cols = self.vc_grid.cols() as usize;
+                let attribute = (attribute.to_byte(self.vc_blink) as u16) << 8;
+                let mut line = StatusLine {
+                        cells: &mut self.vc_backend.cells()[row * cols..(row + 1) * cols],
+                        pos: 0,
+                        attribute,
+                        fallback: self.vc_fallback,
+                };
+
+                fmt::write(&mut line, args).ok();
+                let pos = line.pos;
+                line.cells[pos..].fill(attribute | b' ' as u16);
+                self.vc_backend.flush();
+        }
+
+        /// Returns the glyph showing `c`.
+        fn glyph(
+                &self,
+                c: char,
+        ) -> u8
+        {
+                cp437::encode(c).unwrap_or(self.vc_fallback)
+        }
+
+        /// Number of rows of the screen, status rows included.
+        fn screen_rows(&self) -> u8 { self.vc_grid.rows() + self.vc_split }
+
+        /// First cell of video memory used by the scrolling text.
+        fn text_offset(&self) -> usize { self.vc_split as usize * self.vc_grid.cols() as usize }
+
+        /// Turns underlining off once no displayed cell may be underlined.
+        ///
+        /// Underlined cells are only told apart by their blue foreground, so
+        /// it stays on while blue text is displayed, or while the attribute
+        /// underlines.
+        fn release_underline(&mut self)
+        {
+                if !self.vc_underline || self.vc_attribute.flags.contains(AttributeFlags::UNDERLINE)
+                {
+                        return;
+                }
+
+                let offset = self.text_offset();
+                let start = offset + self.vc_grid.visible_origin();
+                let end = start + self.vc_grid.screen_cells();
+                let cells = self.vc_backend.cells();
+                let blue = |&cell: &u16| (cell >> 8) & 0x07 == VGAColor::Blue as u16;
+
+                if !cells[..offset].iter().any(blue) && !cells[start..end].iter().any(blue) {
+                        self.vc_backend.set_underline(false);
+                        self.vc_underline = false;
+                }
+        }
+
+        /// Lays the text out for a `cols`×`rows` screen with `split` status
+        /// rows, keeping the lines up to the cursor.
+        fn relayout(
+                &mut self,
+                cols: u8,
+                rows: u8,
+                split: u8,
+        )
+        {
+                let old_offset = self.text_offset();
+                let new_offset = split as usize * cols as usize;
+                let cells = self.vc_backend.cells();
+                let vram_cells = cells.len();
+
+                self.vc_grid
+                        .reshape(&mut cells[old_offset..], cols, rows - split);
+                let text = self.vc_grid.screen_cells().min(vram_cells - old_offset);
+                cells.copy_within(old_offset..old_offset + text, new_offset);
+                cells[..new_offset].fill(BLANK);
+                cells[new_offset + text..].fill(BLANK);
+
+                self.vc_grid.set_vram_cells(vram_cells - new_offset);
+                self.vc_split = split;
+                self.vc_backend.set_split(split);
+        }
+
+        /// Makes scrolling move the text by `scanlines` scanlines each
+        /// vertical retrace instead of a row at once, 0 turning it off.
+        ///
+        /// Only displays able to pan through the scanlines of a row scroll
+        /// smoothly.
+        pub fn set_smooth_scroll(
+                &mut self,
+                scanlines: u8,
+        )
+        {
+                self.vc_smooth_scroll = scanlines;
+        }
+
+        /// Updates the CRT Controller's Start Address registers to set the
+        /// visible_origin
+        #[inline(always)]
+        fn set_mem_start(&mut self)
+        {
+                let start = self.text_offset() + self.vc_grid.start_address() as usize;
+
+                self.vc_backend.set_start_address(start as u16);
+                self.vc_start_address = start as u16;
+        }
+
+        /// Moves the start address to the visible_origin after a scroll,
+        /// panning through the rows in between in smooth scroll mode
+        fn scroll_mem_start(&mut self)
+        {
+                let from = self.vc_start_address as usize;
+                let to = self.text_offset() + self.vc_grid.start_address() as usize;
+                let cols = self.vc_grid.cols() as usize;
+                let height = self.vc_backend.scanlines_per_row();
+                let step = self.vc_smooth_scroll as usize;
+
+                // Jumps, like the wrap to the beginning of video memory, are
+                // not animated.
+                if step == 0
+                        || height <= 1
+                        || !from.abs_diff(to).is_multiple_of(cols)
+                        || from.abs_diff(to) / cols >= self.vc_grid.rows() as usize
+                {
+                        return self.set_mem_start();
+                }
+
+                let scans = (step..height as usize).step_by(step);
+                if to > from {
+                        for row in (from..to).step_by(cols) {
+                                for scan in scans.clone() {
+                                        self.pan(row, scan);
+                                }
+                                self.pan(row + cols, 0);
+                        }
+                } else {
+                        for row in (to..from).step_by(cols).rev() {
+                                for scan in scans.clone().rev() {
+                                        self.pan(row, scan);
+                                }
+                                self.pan(row, 0);
+                        }
+                }
+                self.vc_start_address = to as u16;
+        }
+
+        /// Displays the text from scanline `scan` of the row at `start` on
+        /// the next frame
+        fn pan(
+                &mut self,
+                start: usize,
+                scan: usize,
+        )
+        {
+                self.vc_backend.wait_retrace();
+                self.vc_backend.set_start_address(start as u16);
+                self.vc_backend.set_row_scan(scan as u8);
+        }
+
+        /// Writes a single character to the VGA text buffer using default
+        /// colors
+        #[inline(always)]
+        pub fn putc(
+                &mut self,
+                c: u8,
+        )
+        {
+                self.cputc(c, None, None);
+        }
+
+        /// Writes a single character to the VGA text buffer with optional
+        /// custom colors
+        pub fn cputc(
+                &mut self,
+                c: u8,
+                foreground: Option<u8>,
+                background: Option<u8>,
+        )
+        {
+                let mut attribute = self.vc_attribute;
+                if let Some(foreground) = foreground {
+                        attribute.foreground = VGAColor::from_index(foreground);
+                }
+                if let Some(background) = background {
+                        attribute.background = VGAColor::from_index(background);
+                }
+                if attribute.flags.contains(AttributeFlags::UNDERLINE) && !self.vc_underline {
+                        self.vc_backend.set_underline(true);
+                        self.vc_underline = true;
+                }
+
+                let word = (c as u16) | (attribute.to_byte(self.vc_blink) as u16) << 8;
+                let origin = self.vc_grid.origin();
+                let offset = self.text_offset();
+                let mem = &mut self.vc_backend.cells()[offset..];
+
+                if self.vc_autowrap || self.vc_grid.column() + 1 < self.vc_grid.cols() as usize {
+                        self.vc_grid.put(mem, word);
+                } else {
+                        self.vc_grid.overwrite(mem, word);
+                }
+
+                if self.vc_grid.origin() != origin {
+                        self.scroll_mem_start();
+                        self.release_underline();
+                }
+                self.cursor(None);
+        }
+
+        /// Writes a single character at the cell `pos` of the text area, as
+        /// positioned by the [`TextGrid`], without moving the cursor
+        pub fn write_cell(
+                &mut self,
+                pos: usize,
+                c: u8,
+                foreground: VGAColor,
+                background: VGAColor,
+        )
+        {
+                let pos = self.text_offset() + pos;
+
+                self.vc_backend.cells()[pos] = make_cell(c, foreground as u8, background as u8);
+                self.vc_backend.flush();
+        }
+
+        /// Moves the cursor to `col`/`row` of the current screen
+        pub fn set_position(
+                &mut self,
+                col: usize,
+                row: usize,
+        )
+        {
+                self.vc_grid.set_position(col, row);
+                self.cursor(None);
+        }
+
+        /// Writes a string to the VGA text buffer using default colors
+        #[inline(always)]
+        pub fn putstr(
+                &mut self,
+                str: &str,
+        )
+        {
+                self.cputstr(str, None, None);
+        }
+
+        /// Writes a string to the VGA text buffer with optional custom colors,
+        /// applying the SGR sequences it contains
+        pub fn cputstr(
+                &mut self,
+                str: &str,
+                foreground: Option<u8>,
+                background: Option<u8>,
+        )
+        {
+                for c in str.chars() {
+                        // Escape sequences are made of ASCII characters only.
+                        if !c.is_ascii() {
+                                self.cputc(self.glyph(c), foreground, background);
+                                continue;
+                        }
+
+                        let default = self.vc_default_attribute;
+                        let Some(byte) = self.vc_sgr.feed(c as u8, &mut self.vc_attribute, default)
+                        else {
+                                continue;
+                        };
+
+                        match byte {
+                                b'\n' => self.scroll(ScrollDir::Down, 1),
+                                b'\r' => self.carriage_return(),
+                                b'\t' => self.tab(),
+                                0x08 => self.backspace(),
+                                0x07 => self.bell(),
+                                0x0c => self.blank(),
+                                0x20..=0x7e => self.cputc(byte, foreground, background),
+                                _ => self.cputc(self.vc_fallback, None, None),
+                        };
+                }
+        }
+
+        /// Moves the cursor to the beginning of the line.
+        fn carriage_return(&mut self)
+        {
+                self.vc_grid.carriage_return();
+                self.cursor(None);
+        }
+
+        /// Moves the cursor to the next tab stop, or to the last column if
+        /// there is none.
+        fn tab(&mut self)
+        {
+                let (col, row) = (self.vc_grid.column(), self.vc_grid.row());
+                let last = self.vc_grid.cols() as usize - 1;
+
+                if col < last {
+                        let stop = self.vc_tabs.next(col).map_or(last, |stop| stop.min(last));
+                        self.vc_grid.set_position(stop, row);
+                        self.cursor(None);
+                }
+        }
+
+        /// Moves the cursor one cell back, blanking the cell if backspaces
+        /// erase.
+        fn backspace(&mut self)
+        {
+                if self.vc_grid.backspace() && self.vc_erase_backspace {
+                        let pos = self.text_offset() + self.vc_grid.index();
+                        let attribute = self.vc_attribute.to_byte(self.vc_blink) as u16;
+
+                        self.vc_backend.cells()[pos] = attribute << 8 | b' ' as u16;
+                }
+                self.cursor(None);
+        }
+
+        /// Rings the bell as configured with [`VgaConsole::set_bell`].
+        fn bell(&mut self)
+        {
+                match self.vc_bell {
+                        Bell::Off => {}
+                        Bell::Audible if self.vc_backend.bell() => {}
+                        Bell::Audible | Bell::Visible => self.flash(),
+                }
+        }
+
+        /// Inverts the colors of the screen for a few frames.
+        fn flash(&mut self)
+        {
+                self.invert_visible();
+                for _ in 0..FLASH_FRAMES {
+                        self.vc_backend.wait_retrace();
+                }
+                self.invert_visible();
+        }
+
+        /// Swaps the foreground and background colors of the cells on screen.
+        fn invert_visible(&mut self)
+        {
+                let start = self.text_offset() + self.vc_grid.visible_origin();
+                let end = start + self.vc_grid.screen_cells();
+
+                for cell in &mut self.vc_backend.cells()[start..end] {
+                        *cell = (*cell & 0x00ff) | (*cell & 0x0f00) << 4 | (*cell & 0xf000) >> 4;
+                }
+                self.vc_backend.flush();
+        }
+
+        /// Scrolls the VGA text buffer in the specified direction
+        pub fn scroll(
+                &mut self,
+                dir: ScrollDir,
+                lines: usize,
+        )
+        {
+                let offset = self.text_offset();
+                let origin = self.vc_grid.origin();
+
+                self.vc_grid
+                        .scroll(&mut self.vc_backend.cells()[offset..], dir, lines);
+                self.scroll_mem_start();
+                if self.vc_grid.origin() != origin {
+                        self.release_underline();
+                }
+                self.cursor(None);
+        }
+
+        /// Clears the entire VGA text buffer by filling it with blank
+        /// characters, except for the status rows
+        pub fn blank(&mut self)
+        {
+                let offset = self.text_offset();
+
+                self.vc_backend.cells()[offset..].fill(BLANK);
+                self.vc_grid.reset();
+                self.release_underline();
+
+                self.vc_backend.wait_retrace();
+                self.set_mem_start();
+                self.cursor(None);
+        }
+
+        /// Updates the hardware cursor position and optionally changes its
+        /// appearance
+        pub fn cursor(
+                &mut self,
+                cursor_type: Option<CursorTypes>,
+        )
+        {
+                let pos = self.text_offset() + self.vc_grid.cursor_address() as usize;
+                self.vc_backend.set_cursor_address(pos as u16);
+
+                if let Some(cursor_type) = cursor_type {
+                        self.vc_backend.set_cursor_shape(cursor_type);
+                        self.vc_cursor_type = cursor_type;
+                }
+        }
+
+        /// Resizes the VGA text mode display to the specified dimensions and
+        /// clears it, status rows included
+        pub fn resize(
+                &mut self,
+                width: u8,
+                height: u8,
+        )
+        {
+                let split = self.vc_split.min(height - 1);
+                let vram_cells = self.vc_backend.cells().len();
+
+                self.vc_backend.set_geometry(width, height);
+                self.vc_grid = TextGrid::new(
+                        vram_cells - split as usize * width as usize,
+                        width,
+                        height - split,
+                );
+                self.vc_split = split;
+                self.vc_backend.cells().fill(BLANK);
+                self.vc_backend.set_split(split);
+                self.blank();
+        }
+
+        /// Writes the start address and cursor back to the display, after its
+        /// registers were reset, starting on a vertical retrace
+        pub fn redisplay(&mut self)
+        {
+                self.vc_backend.wait_retrace();
+                self.set_mem_start();
+                self.cursor(Some(self.vc_cursor_type));
+        }
+
+        /// Switches the display to another text mode, keeping the lines up to
+        /// the cursor
+        pub fn set_mode(
+                &mut self,
+                mode: TextMode,
+        )
+        {
+                let (cols, rows) = mode.dimensions();
+
+                self.vc_backend.set_text_mode(mode);
+                self.relayout(cols, rows, self.vc_split.min(rows - 1));
+                self.redisplay();
+        }
+}
+
+/// Formatting target filling the cells of a status row.
+struct StatusLine<'a>
+{
+        cells:     &'a mut [u16],
+        pos:       usize,
+        attribute: u16,
+        fallback:  u8,
+}
+
+impl fmt::Write for StatusLine<'_>
+{
+        fn write_str(
+                &mut self,
+                s: &str,
+        ) -> fmt::Result
+        {
+                for c in s.chars() {
+                        let Some(cell) = self.cells.get_mut(self.pos) else {
+                                break;
+                        };
+                        let glyph = cp437::encode(c).unwrap_or(self.fallback);
+
+                        *cell = self.attribute | glyph as u16;
+                        self.pos += 1;
+                }
+                Ok(())
+        }
+}
+
+/// Builds a text cell from a character and its 4-bit colors.
+#[inline(always)]
+fn make_cell(
+        c: u8,
+        foreground: u8,
+        background: u8,
+) -> u16
+{
+        (c as u16) | (((background & 0xf) as u16) << 12) | (((foreground & 0xf) as u16) << 8)
+}
+
+/// Implements the [`core::fmt::Write`] trait for [`VgaConsole`], allowing it to
+/// be used with Rust's formatting macros like `write!` and `writeln!`.
+impl<B: TextBackend> fmt::Write for VgaConsole<B>
+{
+        fn write_str(
+                &mut self,
+                s: &str,
+        ) -> fmt::Result
+        {
+                self.putstr(s);
+                Ok(())
+        }
+
+        fn write_char(
+                &mut self,
+                c: char,
+        ) -> fmt::Result
+        {
+                self.putstr(c.encode_utf8(&mut [0; 4]));
+                Ok(())
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use core::fmt::Write;
+
+        use super::*;
+        use crate::vga::backend::RamBackend;
+
+        fn console(mem: &mut [u16]) -> VgaConsole<RamBackend<'_>>
+        {
+                VgaConsole::new(
+                        RamBackend::new(mem),
+                        VGAColor::White,
+                        VGAColor::Black,
+                        Resolution::R40_10,
+                        Some(CursorTypes::Underline),
+                )
+        }
+
+        fn screen(backend: &RamBackend) -> Vec<String>
+        {
+                backend.visible_rows()
+                        .map(|row| {
+                                row.iter()
+                                        .map(|&c| (c & 0xff) as u8 as char)
+                                        .collect::<String>()
+                                        .trim_end()
+                                        .to_string()
+                        })
+                        .collect()
+        }
+
+        #[test]
+        fn new_configures_backend()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                assert_eq!(con.backend().geometry(), (40, 10));
+                assert_eq!(con.backend().cursor_shape(), CursorTypes::Underline);
+                assert_eq!(con.backend().cursor_address(), 0);
+                assert!(con.backend().visible().iter().all(|&c| c == BLANK));
+
+                let visible = con.visible().to_vec();
+                assert_eq!(visible, con.backend().visible());
+        }
+
+        #[test]
+        fn write_uses_colors()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                write!(con, "hi").unwrap();
+                con.cputc(b'!', Some(VGAColor::Red as u8), Some(VGAColor::Blue as u8));
+
+                let cells = con.backend().visible();
+                assert_eq!(cells[0], 0x0f68);
+                assert_eq!(cells[1], 0x0f69);
+                assert_eq!(cells[2], 0x1421);
+                assert_eq!(con.backend().cursor_address(), 3);
+        }
+
+        #[test]
+        fn scrolling_moves_start_address()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                for i in 0..12 {
+                        writeln!(con, "line {}", i).unwrap();
+                }
+
+                let screen = screen(con.backend());
+                assert_eq!(screen[0], "line 3");
+                assert_eq!(screen[8], "line 11");
+                assert_eq!(screen[9], "");
+                assert_eq!(con.backend().start_address(), 3 * 40);
+                assert_eq!(con.backend().cursor_address(), 12 * 40);
+        }
+
+        #[test]
+        fn set_mode_keeps_text()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                for i in 0..12 {
+                        writeln!(con, "line {}", i).unwrap();
+                }
+                write!(con, "$ ").unwrap();
+                con.set_mode(TextMode::T90x60);
+
+                assert_eq!(con.backend().geometry(), (90, 60));
+                assert_eq!(con.backend().start_address(), 0);
+                assert_eq!(con.backend().cursor_address(), 9 * 90 + 2);
+                assert_eq!(con.backend().cursor_shape(), CursorTypes::Underline);
+
+                let screen = screen(con.backend());
+                assert_eq!(screen[0], "line 3");
+                assert_eq!(screen[8], "line 11");
+                assert_eq!(screen[9], "$");
+                assert_eq!(screen[10], "");
+        }
+
+        #[test]
+        fn sgr_sequences_change_attribute()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                write!(con, "\x1b[31;44ma\x1b[7mb\x1b[0mc").unwrap();
+
+                let cells = con.backend().visible();
+                assert_eq!(cells[0], 0x1461);
+                assert_eq!(cells[1], 0x4162);
+                assert_eq!(cells[2], 0x0f63);
+                assert_eq!(con.backend().cursor_address(), 3);
+        }
+
+        #[test]
+        fn utf8_is_translated_to_cp437()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                write!(con, "é│€").unwrap();
+                con.set_fallback(b'?');
+                write!(con, "{}\x01", '€').unwrap();
+
+                let chars: Vec<u8> = con.backend().visible()[..5]
+                        .iter()
+                        .map(|&c| c as u8)
+                        .collect();
+                assert_eq!(chars, [0x82, 0xb3, 0xfe, b'?', b'?']);
+                assert_eq!(con.backend().cursor_address(), 5);
+        }
+
+        #[test]
+        fn control_characters_move_cursor()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                write!(con, "ab\tc\rd\x08\x08e").unwrap();
+                assert_eq!(screen(con.backend())[0], "eb      c");
+
+                con.set_erase_backspace(true);
+                let mut tabs = TabStops::new();
+                tabs.set(3);
+                con.set_tab_stops(tabs);
+                write!(con, "\n\tx\t\x08").unwrap();
+                assert_eq!(screen(con.backend())[1], "   x");
+                assert_eq!(con.backend().cursor_address(), 78);
+
+                write!(con, "\x0cz").unwrap();
+                assert_eq!(screen(con.backend())[0], "z");
+        }
+
+        #[test]
+        fn bell_rings_or_flashes()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                write!(con, "a\x07").unwrap();
+                assert_eq!(con.backend().bells(), 1);
+
+                let retraces = con.backend().retraces();
+                con.set_bell(Bell::Visible);
+                write!(con, "\x07").unwrap();
+                assert_eq!(con.backend().bells(), 1);
+                assert_eq!(con.backend().retraces() - retraces, FLASH_FRAMES);
+                assert_eq!(con.backend().visible()[0], 0x0f61);
+
+                con.set_bell(Bell::Off);
+                write!(con, "\x07").unwrap();
+                assert_eq!(con.backend().retraces() - retraces, FLASH_FRAMES);
+        }
+
+        #[test]
+        fn autowrap_off_overwrites_last_column()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                con.set_autowrap(false);
+                write!(con, "{:>39}abc", "").unwrap();
+                assert!(screen(con.backend())[0].ends_with('c'));
+                assert_eq!(screen(con.backend())[1], "");
+                assert_eq!(con.backend().cursor_address(), 39);
+        }
+
+        #[test]
+        fn blink_and_underline_configure_backend()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                write!(con, "\x1b[5;101ma").unwrap();
+                assert_eq!(con.visible()[0], 0xcf61);
+                assert!(!con.backend().underline());
+
+                con.set_bright_background(true);
+                write!(con, "\x1b[25ma\x1b[4mb").unwrap();
+                assert!(!con.backend().blink());
+                assert!(con.backend().underline());
+                assert_eq!(con.visible()[1], 0xcf61);
+                assert_eq!(con.visible()[2], 0xc962);
+
+                // Underlining stays on while underlined cells are displayed.
+                write!(con, "\x1b[24m\n").unwrap();
+                assert!(con.backend().underline());
+                write!(con, "\x0c").unwrap();
+                assert!(!con.backend().underline());
+        }
+
+        #[test]
+        fn split_keeps_status_rows()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                write!(con, "boot\n$ ").unwrap();
+                con.set_split(2);
+                con.write_status(
+                        1,
+                        Attribute::new(VGAColor::Black, VGAColor::Cyan),
+                        format_args!("up {}s", 42),
+                );
+
+                assert_eq!(con.split(), 2);
+                assert_eq!(con.backend().split(), 2);
+                assert_eq!(con.grid().rows(), 8);
+                assert_eq!(con.backend().start_address(), 80);
+                assert_eq!(con.backend().cursor_address(), 80 + 40 + 2);
+                assert_eq!(con.visible()[0], 0x0f62);
+
+                for i in 0..12 {
+                        writeln!(con, "line {}", i).unwrap();
+                }
+                con.blank();
+
+                let status = &con.backend().cells_at(40..80);
+                assert_eq!(status[0], 0x3075);
+                assert_eq!(status[5], 0x3073);
+                assert_eq!(status[6], 0x3020);
+                assert!(con.backend().cells_at(0..40).iter().all(|&c| c == BLANK));
+                assert!(con.backend().start_address() >= 80);
+        }
+
+        #[test]
+        fn set_mode_keeps_split()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                con.set_split(1);
+                write!(con, "$ ").unwrap();
+                con.set_mode(TextMode::T90x60);
+
+                assert_eq!(con.split(), 1);
+                assert_eq!(con.grid().rows(), 59);
+                assert_eq!(con.backend().start_address(), 90);
+                assert_eq!(con.backend().cursor_address(), 92);
+                assert_eq!(screen(con.backend())[0], "$");
+                assert!(con.backend().cells_at(0..90).iter().all(|&c| c == BLANK));
+        }
+
+        #[test]
+        fn smooth_scroll_pans_each_row()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                let frames = con.backend().retraces();
+                con.set_smooth_scroll(4);
+                for i in 0..11 {
+                        writeln!(con, "line {}", i).unwrap();
+                }
+                assert_eq!(con.backend().retraces() - frames, 2 * 4);
+                assert_eq!(con.backend().row_scan(), 0);
+                assert_eq!(con.backend().start_address(), 2 * 40);
+
+                con.scroll(ScrollDir::VisualUp, 1);
+                assert_eq!(con.backend().retraces() - frames, 3 * 4);
+                assert_eq!(con.backend().start_address(), 40);
+
+                con.scroll(ScrollDir::Top, 0);
+                con.set_smooth_scroll(0);
+                writeln!(con).unwrap();
+                assert_eq!(con.backend().retraces() - frames, 4 * 4);
+                assert_eq!(con.backend().start_address(), 3 * 40);
+        }
+
+        #[test]
+        fn bulk_redraws_wait_for_retrace()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                let frames = con.backend().retraces();
+                write!(con, "text").unwrap();
+                assert_eq!(con.backend().retraces(), frames);
+
+                con.blank();
+                assert_eq!(con.backend().retraces(), frames + 1);
+                con.set_mode(TextMode::T80x50);
+                assert_eq!(con.backend().retraces(), frames + 2);
+        }
+
+        #[test]
+        fn non_printable_bytes_are_replaced()
+        {
+                let mut mem = vec![0; 0x4000];
+                let mut con = console(&mut mem);
+
+                con.putstr("a\x01b");
+                assert_eq!(screen(con.backend())[0], "a\u{fe}b");
+        }
+}
@@ -0,0 +1,105 @@
+//! Unicode to Code Page 437 translation.
+//!
+//! VGA text memory holds one byte per character, an index in the font loaded
+//! in plane 2. The BIOS font, like the PSF fonts the kernel loads, follows
+//! Code Page 437: ASCII, plus box-drawing characters, block elements, Greek
+//! and accented letters. [`encode`] gives the glyph showing a Unicode
+//! character, so that Rust strings render as written.
+//!
+//! Reference: https://en.wikipedia.org/wiki/Code_page_437
+
+/// Characters shown by glyphs 0x01 to 0x1f, glyph 0x00 being blank.
+#[rustfmt::skip]
+const LOW: [char; 32] = [
+        '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+        '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+/// Characters shown by glyphs 0x80 to 0xff.
+#[rustfmt::skip]
+const HIGH: [char; 128] = [
+        'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+        'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+        'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+        '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+        '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+        '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+        'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+        '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Characters close enough to a glyph meant for another one.
+const ALIASES: [(char, u8); 10] = [
+        ('β', 0xe1),
+        ('Π', 0xe3),
+        ('∑', 0xe4),
+        ('μ', 0xe6),
+        ('\u{2126}', 0xea),
+        ('ð', 0xeb),
+        ('ϕ', 0xed),
+        ('∈', 0xee),
+        ('∅', 0xed),
+        ('⌂', 0x7f),
+];
+
+/// Returns the CP437 glyph showing `c`, or `None` if the code page has none.
+///
+/// Control characters have no glyph, although the font draws symbols for
+/// most of them.
+pub fn encode(c: char) -> Option<u8>
+{
+        if (' '..='~').contains(&c) {
+                return Some(c as u8);
+        }
+
+        let low = LOW.iter().skip(1).position(|&g| g == c).map(|i| i + 1);
+        let high = || HIGH.iter().position(|&g| g == c).map(|i| i + 0x80);
+        let alias = || {
+                ALIASES.iter()
+                        .find(|&&(g, _)| g == c)
+                        .map(|&(_, i)| i as usize)
+        };
+
+        low.or_else(high).or_else(alias).map(|i| i as u8)
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn ascii_maps_to_itself()
+        {
+                assert_eq!(encode('A'), Some(b'A'));
+                assert_eq!(encode('~'), Some(b'~'));
+                assert_eq!(encode('\n'), None);
+                assert_eq!(encode('\u{7f}'), None);
+        }
+
+        #[test]
+        fn translates_code_page_characters()
+        {
+                assert_eq!(encode('é'), Some(0x82));
+                assert_eq!(encode('│'), Some(0xb3));
+                assert_eq!(encode('╬'), Some(0xce));
+                assert_eq!(encode('█'), Some(0xdb));
+                assert_eq!(encode('π'), Some(0xe3));
+                assert_eq!(encode('♥'), Some(0x03));
+                assert_eq!(encode('μ'), Some(0xe6));
+                assert_eq!(encode('€'), None);
+        }
+
+        #[test]
+        fn tables_have_no_duplicates()
+        {
+                let glyphs = || LOW.iter().skip(1).chain(HIGH.iter());
+
+                for (i, c) in glyphs().enumerate() {
+                        assert_eq!(glyphs().position(|g| g == c), Some(i), "{:?}", c);
+                }
+                for (c, _) in ALIASES {
+                        assert!(!glyphs().any(|&g| g == c), "{:?}", c);
+                }
+        }
+}
@@ -0,0 +1,276 @@
+//! Text mode fonts.
+//!
+//! In text mode, the VGA reads glyph bitmaps from plane 2: each of the 256
+//! characters owns a 32 bytes slot, one byte per scanline, of which only the
+//! first `char_height` bytes are displayed.
+//!
+//! Fonts are provided as PC Screen Font files, in version 1 (PSF1) or 2
+//! (PSF2). Only 8 pixels wide fonts can be displayed, as the 9th column of
+//! text modes is generated by the hardware. Unicode tables are ignored: glyph
+//! `n` is displayed for character `n`.
+//!
+//! Reference: https://www.win.tue.nl/~aeb/linux/kbd/font-formats-1.html
+
+/// Number of glyphs in a text mode font.
+pub const GLYPHS: usize = 256;
+
+/// Size in bytes of a glyph slot in plane 2.
+pub const GLYPH_SLOT: usize = 32;
+
+/// Size in bytes of an 8×16 font.
+pub const FONT_8X16_SIZE: usize = GLYPHS * 16;
+
+/// Size in bytes of an 8×8 font.
+pub const FONT_8X8_SIZE: usize = GLYPHS * 8;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_SIZE: usize = 4;
+/// PSF1 mode bit selecting 512 glyphs instead of 256
+const PSF1_MODE512: u8 = 0x01;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HEADER_SIZE: usize = 32;
+
+/// Reasons a font cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError
+{
+        /// The data does not start with a PSF1 or PSF2 magic number
+        BadMagic,
+        /// The data is shorter than announced by its header
+        Truncated,
+        /// Glyphs are not 8 pixels wide, or are higher than a glyph slot
+        UnsupportedSize,
+        /// The glyph height does not match the current text mode
+        HeightMismatch,
+        /// The display does not allow changing glyphs
+        Unsupported,
+}
+
+/// Bitmap font parsed from a PSF1 or PSF2 file.
+///
+/// Each glyph is `height` bytes, one per scanline, the leftmost pixel in the
+/// most significant bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Psf<'a>
+{
+        glyphs: &'a [u8],
+        count:  usize,
+        height: u8,
+}
+
+impl<'a> Psf<'a>
+{
+        /// Parses a PSF1 or PSF2 file.
+        pub fn parse(bytes: &'a [u8]) -> Result<Self, FontError>
+        {
+                if bytes.starts_with(&PSF1_MAGIC) {
+                        Self::parse_psf1(bytes)
+                } else if bytes.starts_with(&PSF2_MAGIC) {
+                        Self::parse_psf2(bytes)
+                } else {
+                        Err(FontError::BadMagic)
+                }
+        }
+
+        fn parse_psf1(bytes: &'a [u8]) -> Result<Self, FontError>
+        {
+                let header = bytes.get(..PSF1_HEADER_SIZE).ok_or(FontError::Truncated)?;
+                let count = match header[2] & PSF1_MODE512 {
+                        0 => 256,
+                        _ => 512,
+                };
+
+                Self::from_parts(&bytes[PSF1_HEADER_SIZE..], count, 8, header[3] as usize)
+        }
+
+        fn parse_psf2(bytes: &'a [u8]) -> Result<Self, FontError>
+        {
+                let header = bytes.get(..PSF2_HEADER_SIZE).ok_or(FontError::Truncated)?;
+                let u32_at = |i: usize| {
+                        u32::from_le_bytes(header[i..i + 4].try_into().unwrap()) as usize
+                };
+                let (header_size, count, char_size) = (u32_at(8), u32_at(16), u32_at(20));
+                let (height, width) = (u32_at(24), u32_at(28));
+
+                if width > 8 || char_size != height {
+                        return Err(FontError::UnsupportedSize);
+                }
+                let glyphs = bytes.get(header_size..).ok_or(FontError::Truncated)?;
+
+                Self::from_parts(glyphs, count, width, height)
+        }
+
+        fn from_parts(
+                glyphs: &'a [u8],
+                count: usize,
+                width: usize,
+                height: usize,
+        ) -> Result<Self, FontError>
+        {
+                if width > 8 || height == 0 || height > GLYPH_SLOT {
+                        return Err(FontError::UnsupportedSize);
+                }
+                // The count comes from the file, and may not fit in memory.
+                let size = count.checked_mul(height).ok_or(FontError::Truncated)?;
+                let glyphs = glyphs.get(..size).ok_or(FontError::Truncated)?;
+
+                Ok(Self {
+                        glyphs,
+                        count,
+                        height: height as u8,
+                })
+        }
+
+        /// Wraps raw glyphs of `height` scanlines, such as a font saved from
+        /// plane 2.
+        pub fn from_glyphs(
+                glyphs: &'a [u8],
+                height: u8,
+        ) -> Result<Self, FontError>
+        {
+                let count = glyphs.len() / height.max(1) as usize;
+
+                Self::from_parts(glyphs, count, 8, height as usize)
+        }
+
+        /// Returns the number of scanlines of each glyph.
+        pub fn height(&self) -> u8 { self.height }
+
+        /// Returns the number of glyphs of the file.
+        pub fn len(&self) -> usize { self.count }
+
+        /// Returns `true` if the file holds no glyph.
+        pub fn is_empty(&self) -> bool { self.count == 0 }
+
+        /// Returns the bitmap of glyph `index`.
+        pub fn glyph(
+                &self,
+                index: usize,
+        ) -> Option<&'a [u8]>
+        {
+                let height = self.height as usize;
+                self.glyphs.get(index * height..(index + 1) * height)
+        }
+
+        /// Returns the bitmaps of the glyphs displayable in text mode, which
+        /// are the first [`GLYPHS`].
+        pub fn text_glyphs(&self) -> &'a [u8]
+        {
+                &self.glyphs[..self.count.min(GLYPHS) * self.height as usize]
+        }
+}
+
+/// Builds an 8×8 font from an 8×16 one.
+///
+/// Each pair of scanlines is merged, so one pixel high strokes are kept.
+pub fn halve(
+        src: &[u8; FONT_8X16_SIZE],
+        dst: &mut [u8; FONT_8X8_SIZE],
+)
+{
+        for (dst, src) in dst.iter_mut().zip(src.chunks_exact(2)) {
+                *dst = src[0] | src[1];
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        fn psf1(height: u8) -> Vec<u8>
+        {
+                let mut bytes = vec![0x36, 0x04, 0x00, height];
+                for glyph in 0..256 {
+                        bytes.extend((0..height).map(|line| glyph as u8 ^ line));
+                }
+                bytes
+        }
+
+        fn psf2(
+                count: u32,
+                height: u32,
+                width: u32,
+        ) -> Vec<u8>
+        {
+                let char_size = height * width.div_ceil(8);
+                let mut bytes = PSF2_MAGIC.to_vec();
+                for field in [0, 32, 0, count, char_size, height, width] {
+                        bytes.extend(field.to_le_bytes());
+                }
+                bytes.resize(32 + (count * char_size) as usize, 0x5a);
+                bytes
+        }
+
+        #[test]
+        fn parses_psf1()
+        {
+                let bytes = psf1(16);
+                let font = Psf::parse(&bytes).unwrap();
+
+                assert_eq!(font.height(), 16);
+                assert_eq!(font.len(), 256);
+                assert_eq!(font.glyph(3).unwrap()[..3], [3, 2, 1]);
+                assert_eq!(font.glyph(256), None);
+                assert_eq!(font.text_glyphs().len(), FONT_8X16_SIZE);
+        }
+
+        #[test]
+        fn parses_psf2()
+        {
+                let bytes = psf2(512, 8, 8);
+                let font = Psf::parse(&bytes).unwrap();
+
+                assert_eq!(font.height(), 8);
+                assert_eq!(font.len(), 512);
+                assert_eq!(font.glyph(511), Some(&[0x5a; 8][..]));
+                assert_eq!(font.text_glyphs().len(), FONT_8X8_SIZE);
+        }
+
+        #[test]
+        fn rejects_invalid_fonts()
+        {
+                let mut truncated = psf1(8);
+                truncated.pop();
+
+                assert_eq!(Psf::parse(b"font"), Err(FontError::BadMagic));
+                assert_eq!(Psf::parse(&truncated), Err(FontError::Truncated));
+                assert_eq!(
+                        Psf::parse(&psf2(256, 16, 9)),
+                        Err(FontError::UnsupportedSize)
+                );
+                assert_eq!(Psf::parse(&psf1(0)), Err(FontError::UnsupportedSize));
+        }
+
+        #[test]
+        fn rejects_huge_glyph_counts()
+        {
+                let mut bytes = psf2(1, 16, 8);
+                bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+
+                assert_eq!(Psf::parse(&bytes), Err(FontError::Truncated));
+                assert_eq!(
+                        Psf::from_parts(&bytes, usize::MAX, 8, 16),
+                        Err(FontError::Truncated)
+                );
+        }
+
+        #[test]
+        fn halve_merges_scanlines()
+        {
+                let mut src = [0u8; FONT_8X16_SIZE];
+                let mut dst = [0xffu8; FONT_8X8_SIZE];
+
+                // Glyph 1: horizontal bar on scanline 5, vertical bar everywhere.
+                src[16 + 5] = 0xff;
+                for line in &mut src[32..48] {
+                        *line = 0x18;
+                }
+                halve(&src, &mut dst);
+
+                assert_eq!(dst[..8], [0; 8]);
+                assert_eq!(dst[8..16], [0, 0, 0xff, 0, 0, 0, 0, 0]);
+                assert_eq!(dst[16..24], [0x18; 8]);
+        }
+}
@@ -0,0 +1,366 @@
+//! Pixel graphics drawing.
+//!
+//! A [`Surface`] only has to write single pixels: lines, rectangles, bitmaps
+//! and text are drawn on top of that. The kernel implements it for the VGA
+//! graphics modes, while [`RamSurface`] draws into a memory buffer so the
+//! primitives can be checked pixel by pixel.
+//!
+//! Positions are signed and anything outside the surface is clipped, so
+//! shapes can be partially off-screen.
+use super::font::Psf;
+
+/// Width in pixels of a text character.
+pub const CHAR_WIDTH: usize = 8;
+
+/// Pixel display used by the drawing primitives.
+pub trait Surface
+{
+        /// Returns the width of the surface in pixels.
+        fn width(&self) -> usize;
+
+        /// Returns the height of the surface in pixels.
+        fn height(&self) -> usize;
+
+        /// Sets the pixel at `x`/`y`, which are within the surface, to
+        /// `color`.
+        fn write_pixel(
+                &mut self,
+                x: usize,
+                y: usize,
+                color: u8,
+        );
+
+        /// Sets the pixel at `x`/`y` to `color`, if it is within the surface.
+        fn pixel(
+                &mut self,
+                x: i32,
+                y: i32,
+                color: u8,
+        )
+        {
+                if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y))
+                        && x < self.width()
+                        && y < self.height()
+                {
+                        self.write_pixel(x, y, color);
+                }
+        }
+
+        /// Draws a line from `x0`/`y0` to `x1`/`y1`, both ends included.
+        fn line(
+                &mut self,
+                (x0, y0): (i32, i32),
+                (x1, y1): (i32, i32),
+                color: u8,
+        )
+        {
+                // Bresenham's algorithm, for every octant.
+                let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+                let (sx, sy) = ((x1 - x0).signum(), (y1 - y0).signum());
+                let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+
+                loop {
+                        self.pixel(x, y, color);
+                        if x == x1 && y == y1 {
+                                break;
+                        }
+                        let e2 = 2 * err;
+                        if e2 >= dy {
+                                err += dy;
+                                x += sx;
+                        }
+                        if e2 <= dx {
+                                err += dx;
+                                y += sy;
+                        }
+                }
+        }
+
+        /// Draws the outline of the `width`×`height` rectangle whose top left
+        /// corner is `x`/`y`.
+        fn rect(
+                &mut self,
+                (x, y): (i32, i32),
+                (width, height): (usize, usize),
+                color: u8,
+        )
+        {
+                if width == 0 || height == 0 {
+                        return;
+                }
+                // Far edges past `i32::MAX` are clipped anyway.
+                let edge = |start: i32, len: usize| {
+                        start.saturating_add(i32::try_from(len - 1).unwrap_or(i32::MAX))
+                };
+                let (right, bottom) = (edge(x, width), edge(y, height));
+
+                self.fill_rect((x, y), (width, 1), color);
+                self.fill_rect((x, bottom), (width, 1), color);
+                self.fill_rect((x, y), (1, height), color);
+                self.fill_rect((right, y), (1, height), color);
+        }
+
+        /// Fills the `width`×`height` rectangle whose top left corner is
+        /// `x`/`y`.
+        fn fill_rect(
+                &mut self,
+                (x, y): (i32, i32),
+                (width, height): (usize, usize),
+                color: u8,
+        )
+        {
+                let clip = |start: i32, len: usize, max: usize| {
+                        let len = i64::try_from(len).unwrap_or(i64::MAX);
+                        let end = (start as i64).saturating_add(len).clamp(0, max as i64) as usize;
+                        (start.clamp(0, max as i32) as usize)..end
+                };
+                let (cols, rows) = (clip(x, width, self.width()), clip(y, height, self.height()));
+
+                for y in rows {
+                        for x in cols.clone() {
+                                self.write_pixel(x, y, color);
+                        }
+                }
+        }
+
+        /// Fills the whole surface with `color`.
+        fn clear(
+                &mut self,
+                color: u8,
+        )
+        {
+                let size = (self.width(), self.height());
+                self.fill_rect((0, 0), size, color);
+        }
+
+        /// Copies `pixels`, `width` pixels per row, with its top left corner
+        /// at `x`/`y`.
+        fn blit(
+                &mut self,
+                (x, y): (i32, i32),
+                width: usize,
+                pixels: &[u8],
+        )
+        {
+                if width == 0 {
+                        return;
+                }
+                for (row, line) in pixels.chunks(width).enumerate() {
+                        for (col, &color) in line.iter().enumerate() {
+                                self.pixel(x + col as i32, y + row as i32, color);
+                        }
+                }
+        }
+
+        /// Draws glyph `c` of `font` with its top left corner at `x`/`y`.
+        ///
+        /// Pixels outside the glyph strokes are left untouched if
+        /// `background` is `None`.
+        fn draw_char(
+                &mut self,
+                (x, y): (i32, i32),
+                c: u8,
+                font: &Psf,
+                foreground: u8,
+                background: Option<u8>,
+        )
+        {
+                let Some(glyph) = font.glyph(c as usize) else {
+                        return;
+                };
+
+                for (row, &bits) in glyph.iter().enumerate() {
+                        for col in 0..CHAR_WIDTH {
+                                let color = match bits & (0x80 >> col) {
+                                        0 => match background {
+                                                Some(background) => background,
+                                                None => continue,
+                                        },
+                                        _ => foreground,
+                                };
+                                self.pixel(x + col as i32, y + row as i32, color);
+                        }
+                }
+        }
+
+        /// Draws `str` with its top left corner at `x`/`y`, one line of text
+        /// per `\n`.
+        ///
+        /// Like the text console, bytes outside of printable ASCII are drawn
+        /// as a square.
+        fn draw_str(
+                &mut self,
+                (x, y): (i32, i32),
+                str: &str,
+                font: &Psf,
+                foreground: u8,
+                background: Option<u8>,
+        )
+        {
+                let height = font.height() as i32;
+
+                for (row, line) in str.split('\n').enumerate() {
+                        for (col, byte) in line.bytes().enumerate() {
+                                let c = match byte {
+                                        0x20..=0x7e => byte,
+                                        _ => 0xfe,
+                                };
+                                let pos = (x + (col * CHAR_WIDTH) as i32, y + row as i32 * height);
+                                self.draw_char(pos, c, font, foreground, background);
+                        }
+                }
+        }
+}
+
+/// Surface drawing into a memory buffer, one byte per pixel.
+#[derive(Debug)]
+pub struct RamSurface<'a>
+{
+        pixels: &'a mut [u8],
+        width:  usize,
+        height: usize,
+}
+
+impl<'a> RamSurface<'a>
+{
+        /// Creates a `width` pixels wide surface using `pixels` as memory.
+        pub fn new(
+                pixels: &'a mut [u8],
+                width: usize,
+        ) -> Self
+        {
+                let height = pixels.len() / width;
+
+                Self {
+                        pixels,
+                        width,
+                        height,
+                }
+        }
+
+        /// Returns the color of the pixel at `x`/`y`.
+        pub fn pixel_at(
+                &self,
+                x: usize,
+                y: usize,
+        ) -> u8
+        {
+                self.pixels[y * self.width + x]
+        }
+}
+
+impl Surface for RamSurface<'_>
+{
+        fn width(&self) -> usize { self.width }
+
+        fn height(&self) -> usize { self.height }
+
+        fn write_pixel(
+                &mut self,
+                x: usize,
+                y: usize,
+                color: u8,
+        )
+        {
+                self.pixels[y * self.width + x] = color;
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        /// Renders the surface as one string per row, `.` for color 0 and `#`
+        /// for anything else.
+        fn rows(surface: &RamSurface) -> Vec<String>
+        {
+                (0..surface.height())
+                        .map(|y| {
+                                (0..surface.width())
+                                        .map(|x| match surface.pixel_at(x, y) {
+                                                0 => '.',
+                                                _ => '#',
+                                        })
+                                        .collect()
+                        })
+                        .collect()
+        }
+
+        #[test]
+        fn lines_cover_every_octant()
+        {
+                let mut mem = vec![0; 5 * 5];
+                let mut surface = RamSurface::new(&mut mem, 5);
+
+                surface.line((0, 0), (4, 4), 1);
+                surface.line((4, 0), (0, 4), 1);
+                surface.line((2, 4), (2, 0), 1);
+
+                assert_eq!(
+                        rows(&surface),
+                        ["#.#.#", ".###.", "..#..", ".###.", "#.#.#"]
+                );
+        }
+
+        #[test]
+        fn shapes_are_clipped()
+        {
+                let mut mem = vec![0; 4 * 3];
+                let mut surface = RamSurface::new(&mut mem, 4);
+
+                surface.fill_rect((-2, -2), (4, 3), 1);
+                surface.rect((2, 1), (5, 5), 2);
+                surface.line((-5, 2), (-1, 2), 3);
+
+                assert_eq!(rows(&surface), ["##..", "..##", "..#."]);
+                assert_eq!(surface.pixel_at(2, 2), 2);
+        }
+
+        #[test]
+        fn oversized_shapes_are_clipped()
+        {
+                let mut mem = vec![0; 4 * 3];
+                let mut surface = RamSurface::new(&mut mem, 4);
+
+                surface.rect((1, 1), (i32::MAX as usize, usize::MAX), 1);
+                surface.rect((i32::MAX, i32::MAX), (i32::MAX as usize, 2), 1);
+                assert_eq!(rows(&surface), ["....", ".###", ".#.."]);
+
+                surface.fill_rect((-1, 2), (usize::MAX, usize::MAX), 2);
+                assert_eq!(rows(&surface), ["....", ".###", "####"]);
+        }
+
+        #[test]
+        fn blit_copies_rows()
+        {
+                let mut mem = vec![0; 4 * 3];
+                let mut surface = RamSurface::new(&mut mem, 4);
+
+                surface.blit((1, 1), 2, &[1, 2, 3, 4, 5, 6]);
+
+                assert_eq!(mem, [0, 0, 0, 0, 0, 1, 2, 0, 0, 3, 4, 0]);
+        }
+
+        #[test]
+        fn text_uses_font_glyphs()
+        {
+                let mut glyphs = [0u8; 256 * 2];
+                glyphs[b'A' as usize * 2..][..2].copy_from_slice(&[0x81, 0x3c]);
+                let font = Psf::from_glyphs(&glyphs, 2).unwrap();
+                let mut mem = vec![9; 16 * 4];
+                let mut surface = RamSurface::new(&mut mem, 16);
+
+                surface.draw_str((0, 0), "AA\nA", &font, 1, Some(0));
+
+                assert_eq!(
+                        rows(&surface),
+                        [
+                                "#......##......#",
+                                "..####....####..",
+                                "#......#########",
+                                "..####..########"
+                        ]
+                );
+        }
+}
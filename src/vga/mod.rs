@@ -0,0 +1,156 @@
+//! Hardware-independent VGA logic.
+//!
+//! The register access and the video memory mapping live in the kernel's
+//! `drivers::video` module; this module only contains the computations those
+//! drivers rely on, and the console that writes through a
+//! [`backend::TextBackend`].
+
+pub mod attribute;
+pub mod backend;
+pub mod config;
+pub mod console;
+pub mod cp437;
+pub mod font;
+pub mod graphics;
+pub mod mode;
+pub mod palette;
+pub mod sgr;
+pub mod snapshot;
+pub mod tabs;
+pub mod text;
+
+/// Standard 16-color VGA color palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum VGAColor
+{
+        Black      = 0x00,
+        Blue       = 0x01,
+        Green      = 0x02,
+        Cyan       = 0x03,
+        Red        = 0x04,
+        Magenta    = 0x05,
+        Brown      = 0x06,
+        LightGray  = 0x07,
+        DarkGray   = 0x08,
+        LightBlue  = 0x09,
+        LightGreen = 0x0a,
+        LightCyan  = 0x0b,
+        LightRed   = 0x0c,
+        Pink       = 0x0d,
+        Yellow     = 0x0e,
+        White      = 0x0f,
+}
+
+impl VGAColor
+{
+        /// Returns the color of index `index`, modulo 16.
+        pub const fn from_index(index: u8) -> Self
+        {
+                match index & 0x0f {
+                        0x00 => VGAColor::Black,
+                        0x01 => VGAColor::Blue,
+                        0x02 => VGAColor::Green,
+                        0x03 => VGAColor::Cyan,
+                        0x04 => VGAColor::Red,
+                        0x05 => VGAColor::Magenta,
+                        0x06 => VGAColor::Brown,
+                        0x07 => VGAColor::LightGray,
+                        0x08 => VGAColor::DarkGray,
+                        0x09 => VGAColor::LightBlue,
+                        0x0a => VGAColor::LightGreen,
+                        0x0b => VGAColor::LightCyan,
+                        0x0c => VGAColor::LightRed,
+                        0x0d => VGAColor::Pink,
+                        0x0e => VGAColor::Yellow,
+                        _ => VGAColor::White,
+                }
+        }
+}
+
+/// Types of text mode cursor shapes available in VGA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CursorTypes
+{
+        Underline,
+        LowerThird,
+        LowerHalf,
+        Full,
+        None,
+}
+
+/// VGA memory mapping ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MemoryRanges
+{
+        /// A0000h-BFFFFh (128K region)
+        Large  = 0,
+        /// A0000h-AFFFFh (64K region)
+        Medium = 1,
+        /// B8000h-BFFFFh (32K region)
+        Small  = 3,
+}
+
+impl MemoryRanges
+{
+        /// Physical address of the first byte of the range.
+        pub const fn base(self) -> u32
+        {
+                match self {
+                        MemoryRanges::Large | MemoryRanges::Medium => 0xa0000,
+                        MemoryRanges::Small => 0xb8000,
+                }
+        }
+
+        /// Size of the range in bytes.
+        pub const fn size(self) -> u32
+        {
+                match self {
+                        MemoryRanges::Large => 0x20000,
+                        MemoryRanges::Medium => 0x10000,
+                        MemoryRanges::Small => 0x8000,
+                }
+        }
+}
+
+/// Standard VGA text mode resolutions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution
+{
+        /// 40 columns × 10 rows text mode
+        R40_10,
+        /// 40 columns × 25 rows text mode
+        R40_25,
+        /// 40 columns × 50 rows text mode
+        R40_50,
+        /// 80 columns × 10 rows text mode
+        R80_10,
+        /// 80 columns × 25 rows text mode (most common)
+        R80_25,
+        /// 80 columns × 50 rows text mode
+        R80_50,
+        /// 120 columns × 25 rows text mode
+        R120_25,
+        /// 120 columns × 50 rows text mode
+        R120_50,
+}
+
+impl Resolution
+{
+        /// Returns the number of columns and rows of the resolution.
+        pub const fn dimensions(self) -> (u8, u8)
+        {
+                match self {
+                        Resolution::R40_10 => (40, 10),
+                        Resolution::R40_25 => (40, 25),
+                        Resolution::R40_50 => (40, 50),
+                        Resolution::R80_10 => (80, 10),
+                        Resolution::R80_25 => (80, 25),
+                        Resolution::R80_50 => (80, 50),
+                        Resolution::R120_25 => (120, 25),
+                        Resolution::R120_50 => (120, 50),
+                }
+        }
+}
@@ -0,0 +1,532 @@
+//! VGA mode register tables.
+//!
+//! Each [`TextMode`] and [`GraphicsMode`] is described by the full set of
+//! values to load in the Miscellaneous Output, Sequencer, CRTC, Graphics
+//! Controller and Attribute Controller registers, in register index order. The
+//! kernel programs them, with the font matching [`TextMode::char_height`] for
+//! text modes.
+//!
+//! Reference: http://www.osdever.net/FreeVGA/vga/vga.htm and the public
+//! domain `modes.c` by Chris Giese.
+use core::fmt;
+
+use super::MemoryRanges;
+
+/// Text modes the VGA hardware can be switched to at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode
+{
+        /// 80 columns × 25 rows, 9×16 font, 720×400 at 70Hz (BIOS mode 03h)
+        T80x25,
+        /// 80 columns × 50 rows, 9×8 font, 720×400 at 70Hz
+        T80x50,
+        /// 90 columns × 60 rows, 8×8 font, 720×480 at 60Hz
+        T90x60,
+}
+
+/// Pixel graphics modes the VGA hardware can be switched to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsMode
+{
+        /// 320×200, 256 colors, one byte per pixel (BIOS mode 13h)
+        G320x200x256,
+        /// 640×480, 16 colors, one bit per pixel in each of the 4 planes
+        /// (BIOS mode 12h)
+        G640x480x16,
+}
+
+/// Values of every VGA register needed to set a mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeRegisters
+{
+        /// Miscellaneous Output register
+        pub misc: u8,
+        /// Sequencer registers 0x00 to 0x04
+        pub seq:  [u8; 5],
+        /// CRTC registers 0x00 to 0x18
+        pub crtc: [u8; 25],
+        /// Graphics Controller registers 0x00 to 0x08
+        pub gc:   [u8; 9],
+        /// Attribute Controller registers 0x00 to 0x14
+        pub ac:   [u8; 21],
+}
+
+/// Graphics Controller registers shared by every text mode.
+const TEXT_GC: [u8; 9] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0e, 0x00, 0xff];
+
+/// Attribute Controller registers shared by every text mode: identity
+/// palette (except brown), 9th column duplication for line characters,
+/// blinking enabled.
+const TEXT_AC: [u8; 21] = [
+        0x00,
+        0x01,
+        0x02,
+        0x03,
+        0x04,
+        0x05,
+        0x14,
+        0x07,
+        0x38,
+        0x39,
+        0x3a,
+        0x3b,
+        0x3c,
+        0x3d,
+        0x3e,
+        0x3f,
+        0x0c,
+        0x00,
+        0x0f,
+        0x08,
+        0x00,
+];
+
+const MODE_80X25: ModeRegisters = ModeRegisters {
+        misc: 0x67,
+        seq:  [0x03, 0x00, 0x03, 0x00, 0x02],
+        crtc: [
+                0x5f,
+                0x4f,
+                0x50,
+                0x82,
+                0x55,
+                0x81,
+                0xbf,
+                0x1f,
+                0x00,
+                0x4f,
+                0x0d,
+                0x0e,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x9c,
+                0x8e,
+                0x8f,
+                0x28,
+                0x1f,
+                0x96,
+                0xb9,
+                0xa3,
+                0xff,
+        ],
+        gc:   TEXT_GC,
+        ac:   TEXT_AC,
+};
+
+const MODE_80X50: ModeRegisters = ModeRegisters {
+        misc: 0x67,
+        seq:  [0x03, 0x00, 0x03, 0x00, 0x02],
+        crtc: [
+                0x5f,
+                0x4f,
+                0x50,
+                0x82,
+                0x55,
+                0x81,
+                0xbf,
+                0x1f,
+                0x00,
+                0x47,
+                0x06,
+                0x07,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x9c,
+                0x8e,
+                0x8f,
+                0x28,
+                0x1f,
+                0x96,
+                0xb9,
+                0xa3,
+                0xff,
+        ],
+        gc:   TEXT_GC,
+        ac:   TEXT_AC,
+};
+
+const MODE_90X60: ModeRegisters = ModeRegisters {
+        misc: 0xe7,
+        seq:  [0x03, 0x01, 0x03, 0x00, 0x02],
+        crtc: [
+                0x6b,
+                0x59,
+                0x5a,
+                0x82,
+                0x60,
+                0x8d,
+                0x0b,
+                0x3e,
+                0x00,
+                0x47,
+                0x06,
+                0x07,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0xea,
+                0x0c,
+                0xdf,
+                0x2d,
+                0x08,
+                0xe8,
+                0x05,
+                0xa3,
+                0xff,
+        ],
+        gc:   TEXT_GC,
+        ac:   TEXT_AC,
+};
+
+const MODE_320X200X256: ModeRegisters = ModeRegisters {
+        misc: 0x63,
+        seq:  [0x03, 0x01, 0x0f, 0x00, 0x0e],
+        crtc: [
+                0x5f,
+                0x4f,
+                0x50,
+                0x82,
+                0x54,
+                0x80,
+                0xbf,
+                0x1f,
+                0x00,
+                0x41,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x9c,
+                0x0e,
+                0x8f,
+                0x28,
+                0x40,
+                0x96,
+                0xb9,
+                0xa3,
+                0xff,
+        ],
+        gc:   [0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x05, 0x0f, 0xff],
+        ac:   [
+                0x00,
+                0x01,
+                0x02,
+                0x03,
+                0x04,
+                0x05,
+                0x06,
+                0x07,
+                0x08,
+                0x09,
+                0x0a,
+                0x0b,
+                0x0c,
+                0x0d,
+                0x0e,
+                0x0f,
+                0x41,
+                0x00,
+                0x0f,
+                0x00,
+                0x00,
+        ],
+};
+
+const MODE_640X480X16: ModeRegisters = ModeRegisters {
+        misc: 0xe3,
+        seq:  [0x03, 0x01, 0x0f, 0x00, 0x06],
+        crtc: [
+                0x5f,
+                0x4f,
+                0x50,
+                0x82,
+                0x54,
+                0x80,
+                0x0b,
+                0x3e,
+                0x00,
+                0x40,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0xea,
+                0x0c,
+                0xdf,
+                0x28,
+                0x00,
+                0xe7,
+                0x04,
+                0xe3,
+                0xff,
+        ],
+        gc:   [0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x05, 0x0f, 0xff],
+        ac:   [
+                0x00,
+                0x01,
+                0x02,
+                0x03,
+                0x04,
+                0x05,
+                0x06,
+                0x07,
+                0x08,
+                0x09,
+                0x0a,
+                0x0b,
+                0x0c,
+                0x0d,
+                0x0e,
+                0x0f,
+                0x01,
+                0x00,
+                0x0f,
+                0x00,
+                0x00,
+        ],
+};
+
+impl ModeRegisters
+{
+        /// Returns the registers with the Graphics Controller memory map set
+        /// to `range`.
+        pub const fn with_memory_map(
+                mut self,
+                range: MemoryRanges,
+        ) -> Self
+        {
+                self.gc[0x06] = (self.gc[0x06] & 0xf3) | (range as u8) << 2;
+                self
+        }
+}
+
+/// Prints one line per controller, with the values in index order.
+impl fmt::Display for ModeRegisters
+{
+        fn fmt(
+                &self,
+                f: &mut fmt::Formatter<'_>,
+        ) -> fmt::Result
+        {
+                let controllers: [(&str, &[u8]); 5] = [
+                        ("misc", core::slice::from_ref(&self.misc)),
+                        ("seq", &self.seq),
+                        ("crtc", &self.crtc),
+                        ("gc", &self.gc),
+                        ("ac", &self.ac),
+                ];
+
+                for (name, values) in controllers {
+                        write!(f, "{:4}", name)?;
+                        for value in values {
+                                write!(f, " {:02x}", value)?;
+                        }
+                        writeln!(f)?;
+                }
+                Ok(())
+        }
+}
+
+impl TextMode
+{
+        /// Every text mode, from the lowest resolution.
+        pub const ALL: [TextMode; 3] = [TextMode::T80x25, TextMode::T80x50, TextMode::T90x60];
+
+        /// Returns the text mode named `name`, given as `COLSxROWS` such as
+        /// `80x50`.
+        pub fn find(name: &str) -> Option<TextMode>
+        {
+                let (cols, rows) = name.split_once('x')?;
+                let dimensions = (cols.parse().ok()?, rows.parse().ok()?);
+
+                TextMode::ALL
+                        .into_iter()
+                        .find(|mode| mode.dimensions() == dimensions)
+        }
+
+        /// Returns the number of columns and rows of the mode.
+        pub const fn dimensions(self) -> (u8, u8)
+        {
+                match self {
+                        TextMode::T80x25 => (80, 25),
+                        TextMode::T80x50 => (80, 50),
+                        TextMode::T90x60 => (90, 60),
+                }
+        }
+
+        /// Returns the number of scanlines of a character, which is the
+        /// height of the font to load.
+        pub const fn char_height(self) -> u8
+        {
+                match self {
+                        TextMode::T80x25 => 16,
+                        TextMode::T80x50 | TextMode::T90x60 => 8,
+                }
+        }
+
+        /// Returns the register values setting the mode.
+        pub const fn registers(self) -> &'static ModeRegisters
+        {
+                match self {
+                        TextMode::T80x25 => &MODE_80X25,
+                        TextMode::T80x50 => &MODE_80X50,
+                        TextMode::T90x60 => &MODE_90X60,
+                }
+        }
+}
+
+impl GraphicsMode
+{
+        /// Returns the width and height of the mode in pixels.
+        pub const fn dimensions(self) -> (usize, usize)
+        {
+                match self {
+                        GraphicsMode::G320x200x256 => (320, 200),
+                        GraphicsMode::G640x480x16 => (640, 480),
+                }
+        }
+
+        /// Returns the number of colors a pixel can take.
+        pub const fn colors(self) -> usize
+        {
+                match self {
+                        GraphicsMode::G320x200x256 => 256,
+                        GraphicsMode::G640x480x16 => 16,
+                }
+        }
+
+        /// Returns the register values setting the mode.
+        pub const fn registers(self) -> &'static ModeRegisters
+        {
+                match self {
+                        GraphicsMode::G320x200x256 => &MODE_320X200X256,
+                        GraphicsMode::G640x480x16 => &MODE_640X480X16,
+                }
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        /// Rebuilds a 10-bit vertical CRTC value from its low byte and its
+        /// bits 8 and 9 in the Overflow register.
+        fn vertical(
+                regs: &ModeRegisters,
+                low: usize,
+                bit8: u8,
+                bit9: u8,
+        ) -> u32
+        {
+                let overflow = regs.crtc[0x07];
+                regs.crtc[low] as u32
+                        | ((overflow >> bit8) as u32 & 1) << 8
+                        | ((overflow >> bit9) as u32 & 1) << 9
+        }
+
+        #[test]
+        fn tables_match_dimensions()
+        {
+                for mode in TextMode::ALL {
+                        let regs = mode.registers();
+                        let (cols, rows) = mode.dimensions();
+                        let scanlines = vertical(regs, 0x12, 1, 6) + 1;
+                        let char_height = (regs.crtc[0x09] & 0x1f) + 1;
+
+                        assert_eq!(regs.crtc[0x01] + 1, cols, "{:?}", mode);
+                        assert_eq!(regs.crtc[0x13] * 2, cols, "{:?}", mode);
+                        assert_eq!(char_height, mode.char_height(), "{:?}", mode);
+                        assert_eq!(scanlines, rows as u32 * char_height as u32, "{:?}", mode);
+                }
+        }
+
+        #[test]
+        fn vertical_timings_are_ordered()
+        {
+                for mode in TextMode::ALL {
+                        let regs = mode.registers();
+                        let display_end = vertical(regs, 0x12, 1, 6);
+                        let retrace_start = vertical(regs, 0x10, 2, 7);
+                        let total = vertical(regs, 0x06, 0, 5);
+
+                        assert!(display_end < retrace_start, "{:?}", mode);
+                        assert!(retrace_start < total, "{:?}", mode);
+                }
+        }
+
+        #[test]
+        fn graphics_tables_match_dimensions()
+        {
+                for mode in [GraphicsMode::G320x200x256, GraphicsMode::G640x480x16] {
+                        let regs = mode.registers();
+                        let (width, height) = mode.dimensions();
+                        let scan_doubling = (regs.crtc[0x09] & 0x1f) as u32 + 1;
+                        let scanlines = vertical(regs, 0x12, 1, 6) + 1;
+                        let pixel_width = match regs.ac[0x10] & 0x40 {
+                                0 => 1,
+                                _ => 2,
+                        };
+
+                        assert_eq!(scanlines / scan_doubling, height as u32, "{:?}", mode);
+                        assert_eq!(
+                                (regs.crtc[0x01] as usize + 1) * 8 / pixel_width,
+                                width,
+                                "{:?}",
+                                mode
+                        );
+                        assert_eq!(regs.gc[0x06] & 0x01, 0x01, "{:?}", mode);
+                }
+        }
+
+        #[test]
+        fn memory_map_keeps_other_bits()
+        {
+                let regs = TextMode::T80x25
+                        .registers()
+                        .with_memory_map(MemoryRanges::Medium);
+
+                assert_eq!(regs.gc[0x06], 0x06);
+                assert_eq!(regs.gc[..0x06], TEXT_GC[..0x06]);
+        }
+
+        #[test]
+        fn display_lists_every_register()
+        {
+                let dump = TextMode::T80x25.registers().to_string();
+                let lines: Vec<&str> = dump.lines().collect();
+
+                assert_eq!(lines.len(), 5);
+                assert_eq!(lines[0], "misc 67");
+                assert_eq!(lines[1], "seq  03 00 03 00 02");
+                assert_eq!(lines[2].split(' ').skip(1).count(), 25);
+                assert!(lines[4].starts_with("ac   00 01 02 03 04 05 14 07"));
+        }
+
+        #[test]
+        fn text_modes_map_b8000()
+        {
+                for mode in TextMode::ALL {
+                        assert_eq!(mode.registers().gc[0x06] & 0x0c, 0x0c, "{:?}", mode);
+                        assert_eq!(mode.registers().gc[0x06] & 0x01, 0, "{:?}", mode);
+                }
+        }
+
+        #[test]
+        fn finds_modes_by_name()
+        {
+                assert_eq!(TextMode::find("80x50"), Some(TextMode::T80x50));
+                assert_eq!(TextMode::find("90x60"), Some(TextMode::T90x60));
+                assert_eq!(TextMode::find("80x60"), None);
+                assert_eq!(TextMode::find("80"), None);
+        }
+}
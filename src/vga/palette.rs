@@ -0,0 +1,250 @@
+//! Console color themes.
+//!
+//! Text attributes select one of 16 colors, which the Attribute Controller
+//! maps to entries of the 256 colors DAC palette. A [`Theme`] gives the RGB
+//! value of each of the 16 colors, in [`VGAColor`](super::VGAColor) order.
+//!
+//! The DAC only keeps 6 bits per component: [`Rgb`] values are 8-bit and
+//! converted when programmed.
+
+/// 24-bit RGB color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgb
+{
+        pub r: u8,
+        pub g: u8,
+        pub b: u8,
+}
+
+impl Rgb
+{
+        /// Builds a color from its `0xRRGGBB` value.
+        pub const fn hex(value: u32) -> Self
+        {
+                Self {
+                        r: (value >> 16) as u8,
+                        g: (value >> 8) as u8,
+                        b: value as u8,
+                }
+        }
+
+        /// Builds a color from 6-bit DAC components.
+        pub const fn from_dac(
+                r: u8,
+                g: u8,
+                b: u8,
+        ) -> Self
+        {
+                const fn expand(c: u8) -> u8 { (c & 0x3f) << 2 | (c & 0x3f) >> 4 }
+
+                Self {
+                        r: expand(r),
+                        g: expand(g),
+                        b: expand(b),
+                }
+        }
+
+        /// Returns the 6-bit DAC components of the color.
+        pub const fn to_dac(self) -> (u8, u8, u8) { (self.r >> 2, self.g >> 2, self.b >> 2) }
+
+        /// Returns the color dimmed to `level` out of `levels`.
+        pub const fn scale(
+                self,
+                level: u8,
+                levels: u8,
+        ) -> Self
+        {
+                const fn mul(
+                        c: u8,
+                        level: u8,
+                        levels: u8,
+                ) -> u8
+                {
+                        (c as u16 * level as u16 / levels as u16) as u8
+                }
+
+                if level >= levels {
+                        return self;
+                }
+                Self {
+                        r: mul(self.r, level, levels),
+                        g: mul(self.g, level, levels),
+                        b: mul(self.b, level, levels),
+                }
+        }
+}
+
+/// Colors of the 16 text attributes.
+pub type Palette = [Rgb; 16];
+
+/// Named console color scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme
+{
+        /// Name used to select the theme, e.g. on the kernel command line
+        pub name:    &'static str,
+        /// Colors in attribute order
+        pub palette: Palette,
+}
+
+/// Builds a palette from `0xRRGGBB` values.
+const fn palette(hex: [u32; 16]) -> Palette
+{
+        let mut palette = [Rgb { r: 0, g: 0, b: 0 }; 16];
+        let mut i = 0;
+
+        while i < 16 {
+                palette[i] = Rgb::hex(hex[i]);
+                i += 1;
+        }
+        palette
+}
+
+/// CGA colors, as displayed by the BIOS text mode.
+pub const DEFAULT: Theme = Theme {
+        name:    "default",
+        palette: palette([
+                0x000000,
+                0x0000aa,
+                0x00aa00,
+                0x00aaaa,
+                0xaa0000,
+                0xaa00aa,
+                0xaa5500,
+                0xaaaaaa,
+                0x555555,
+                0x5555ff,
+                0x55ff55,
+                0x55ffff,
+                0xff5555,
+                0xff55ff,
+                0xffff55,
+                0xffffff,
+        ]),
+};
+
+/// Solarized dark, by Ethan Schoonover.
+pub const SOLARIZED: Theme = Theme {
+        name:    "solarized",
+        palette: palette([
+                0x002b36,
+                0x268bd2,
+                0x859900,
+                0x2aa198,
+                0xdc322f,
+                0xd33682,
+                0xb58900,
+                0x93a1a1,
+                0x586e75,
+                0x6c71c4,
+                0x859900,
+                0x2aa198,
+                0xcb4b16,
+                0xd33682,
+                0xb58900,
+                0xfdf6e3,
+        ]),
+};
+
+/// Gruvbox dark, by Pavel Pertsev.
+pub const GRUVBOX: Theme = Theme {
+        name:    "gruvbox",
+        palette: palette([
+                0x282828,
+                0x458588,
+                0x98971a,
+                0x689d6a,
+                0xcc241d,
+                0xb16286,
+                0xd79921,
+                0xa89984,
+                0x928374,
+                0x83a598,
+                0xb8bb26,
+                0x8ec07c,
+                0xfb4934,
+                0xd3869b,
+                0xfabd2f,
+                0xebdbb2,
+        ]),
+};
+
+/// Saturated colors on a black background, for low quality displays.
+pub const HIGH_CONTRAST: Theme = Theme {
+        name:    "high-contrast",
+        palette: palette([
+                0x000000,
+                0x0060ff,
+                0x00d000,
+                0x00e0e0,
+                0xff2020,
+                0xff40ff,
+                0xffa000,
+                0xe0e0e0,
+                0xa0a0a0,
+                0x80a0ff,
+                0x60ff60,
+                0x80ffff,
+                0xff8080,
+                0xff80ff,
+                0xffff00,
+                0xffffff,
+        ]),
+};
+
+/// Every built-in theme.
+pub const THEMES: [&Theme; 4] = [&DEFAULT, &SOLARIZED, &GRUVBOX, &HIGH_CONTRAST];
+
+impl Theme
+{
+        /// Returns the built-in theme called `name`, ignoring case.
+        pub fn find(name: &str) -> Option<&'static Theme>
+        {
+                THEMES.into_iter()
+                        .find(|theme| theme.name.eq_ignore_ascii_case(name))
+        }
+}
+
+/// Returns `palette` dimmed to `level` out of `levels`, used to fade the
+/// screen in and out.
+pub fn fade(
+        palette: &Palette,
+        level: u8,
+        levels: u8,
+) -> Palette
+{
+        palette.map(|color| color.scale(level, levels))
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn dac_conversion_round_trips()
+        {
+                assert_eq!(Rgb::hex(0xaa5500).to_dac(), (0x2a, 0x15, 0x00));
+                assert_eq!(Rgb::from_dac(0x2a, 0x15, 0x00), Rgb::hex(0xaa5500));
+                assert_eq!(Rgb::from_dac(0x3f, 0x3f, 0x3f), Rgb::hex(0xffffff));
+        }
+
+        #[test]
+        fn themes_are_found_by_name()
+        {
+                assert_eq!(Theme::find("Gruvbox"), Some(&GRUVBOX));
+                assert_eq!(Theme::find("high-contrast"), Some(&HIGH_CONTRAST));
+                assert_eq!(Theme::find("missing"), None);
+        }
+
+        #[test]
+        fn fade_scales_every_color()
+        {
+                let black = fade(&DEFAULT.palette, 0, 4);
+                let half = fade(&DEFAULT.palette, 2, 4);
+
+                assert!(black.iter().all(|&c| c == Rgb::default()));
+                assert_eq!(half[15], Rgb::hex(0x7f7f7f));
+                assert_eq!(fade(&SOLARIZED.palette, 4, 4), SOLARIZED.palette);
+        }
+}
@@ -0,0 +1,213 @@
+//! ANSI Select Graphic Rendition escape sequences.
+//!
+//! Text printed to the console may contain `ESC [ <params> m` sequences
+//! changing the [`Attribute`] of the following characters, for instance
+//! `"\x1b[1;31merror\x1b[0m"`. Supported parameters:
+//!
+//! | Parameter           | Effect                                   |
+//! |---------------------|------------------------------------------|
+//! | `0` or none         | reset to the console default attribute   |
+//! | `1`, `22`           | bold on, off                             |
+//! | `4`, `24`           | underline on, off                        |
+//! | `5`, `25`           | blink on, off                            |
+//! | `7`, `27`           | reverse video on, off                    |
+//! | `30`-`37`, `90`-`97`| foreground color, normal or bright       |
+//! | `39`                | default foreground                       |
+//! | `40`-`47`, `100`-`107` | background color, normal or bright    |
+//! | `49`                | default background                       |
+//!
+//! Other parameters are ignored, and so are sequences ending with another
+//! byte than `m`. Underlined text is drawn blue, and underlines the other
+//! blue text on screen meanwhile, see [`super::attribute`].
+//!
+//! Reference: https://en.wikipedia.org/wiki/ANSI_escape_code#SGR
+use super::VGAColor;
+use super::attribute::{Attribute, AttributeFlags};
+
+const ESC: u8 = 0x1b;
+
+/// Maximum number of parameters kept in a sequence.
+const MAX_PARAMS: usize = 8;
+
+/// ANSI color order to VGA color order.
+const ANSI_COLORS: [VGAColor; 8] = [
+        VGAColor::Black,
+        VGAColor::Red,
+        VGAColor::Green,
+        VGAColor::Brown,
+        VGAColor::Blue,
+        VGAColor::Magenta,
+        VGAColor::Cyan,
+        VGAColor::LightGray,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State
+{
+        Ground,
+        Escape,
+        Params,
+}
+
+/// Byte by byte escape sequence parser.
+#[derive(Debug, Clone)]
+pub struct SgrParser
+{
+        state:  State,
+        params: [u16; MAX_PARAMS],
+        count:  usize,
+}
+
+impl Default for SgrParser
+{
+        fn default() -> Self { Self::new() }
+}
+
+impl SgrParser
+{
+        pub const fn new() -> Self
+        {
+                Self {
+                        state:  State::Ground,
+                        params: [0; MAX_PARAMS],
+                        count:  0,
+                }
+        }
+
+        /// Feeds `byte` to the parser.
+        ///
+        /// Returns the byte back if it is text to print, or `None` if it is
+        /// part of an escape sequence. Complete sequences update `attr`,
+        /// resetting it to `default` when asked to.
+        pub fn feed(
+                &mut self,
+                byte: u8,
+                attr: &mut Attribute,
+                default: Attribute,
+        ) -> Option<u8>
+        {
+                match (self.state, byte) {
+                        (State::Ground, ESC) => self.state = State::Escape,
+                        (State::Ground, _) => return Some(byte),
+                        (State::Escape, b'[') => {
+                                self.state = State::Params;
+                                self.params = [0; MAX_PARAMS];
+                                self.count = 0;
+                        }
+                        // Not a control sequence, drop the escape.
+                        (State::Escape, _) => {
+                                self.state = State::Ground;
+                                return Some(byte);
+                        }
+                        (State::Params, b'0'..=b'9') => {
+                                let param = &mut self.params[self.count.min(MAX_PARAMS - 1)];
+                                *param = param
+                                        .saturating_mul(10)
+                                        .saturating_add((byte - b'0') as u16);
+                        }
+                        (State::Params, b';') => self.count += 1,
+                        (State::Params, b'm') => {
+                                let count = (self.count + 1).min(MAX_PARAMS);
+                                for &param in &self.params[..count] {
+                                        apply(param, attr, default);
+                                }
+                                self.state = State::Ground;
+                        }
+                        // Intermediate bytes of other sequences.
+                        (State::Params, 0x20..=0x3f) => {}
+                        (State::Params, _) => self.state = State::Ground,
+                }
+                None
+        }
+}
+
+/// Applies the SGR parameter `param` to `attr`.
+fn apply(
+        param: u16,
+        attr: &mut Attribute,
+        default: Attribute,
+)
+{
+        let color = |base: u16, bright: bool| {
+                let color = ANSI_COLORS[(param - base) as usize] as u8 | (bright as u8) << 3;
+                VGAColor::from_index(color)
+        };
+
+        match param {
+                0 => *attr = default,
+                1 => attr.flags.insert(AttributeFlags::BOLD),
+                4 => attr.flags.insert(AttributeFlags::UNDERLINE),
+                5 => attr.flags.insert(AttributeFlags::BLINK),
+                7 => attr.flags.insert(AttributeFlags::REVERSE),
+                22 => attr.flags.remove(AttributeFlags::BOLD),
+                24 => attr.flags.remove(AttributeFlags::UNDERLINE),
+                25 => attr.flags.remove(AttributeFlags::BLINK),
+                27 => attr.flags.remove(AttributeFlags::REVERSE),
+                30..=37 => attr.foreground = color(30, false),
+                39 => attr.foreground = default.foreground,
+                40..=47 => attr.background = color(40, false),
+                49 => attr.background = default.background,
+                90..=97 => attr.foreground = color(90, true),
+                100..=107 => attr.background = color(100, true),
+                _ => {}
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        const DEFAULT: Attribute = Attribute::new(VGAColor::LightGray, VGAColor::Black);
+
+        /// Feeds `input` and returns the printed text and final attribute.
+        fn parse(input: &str) -> (String, Attribute)
+        {
+                let mut parser = SgrParser::new();
+                let mut attr = DEFAULT;
+                let text = input
+                        .bytes()
+                        .filter_map(|b| parser.feed(b, &mut attr, DEFAULT))
+                        .map(char::from)
+                        .collect();
+
+                (text, attr)
+        }
+
+        #[test]
+        fn plain_text_is_printed()
+        {
+                assert_eq!(parse("hello"), ("hello".to_string(), DEFAULT));
+        }
+
+        #[test]
+        fn colors_and_flags()
+        {
+                let (text, attr) = parse("\x1b[1;31merr\x1b[44;4mx");
+
+                assert_eq!(text, "errx");
+                assert_eq!(attr.foreground, VGAColor::Red);
+                assert_eq!(attr.background, VGAColor::Blue);
+                assert_eq!(attr.flags, AttributeFlags::BOLD | AttributeFlags::UNDERLINE);
+
+                let (_, attr) = parse("\x1b[93;105;7m");
+                assert_eq!(attr.foreground, VGAColor::Yellow);
+                assert_eq!(attr.background, VGAColor::Pink);
+                assert_eq!(attr.flags, AttributeFlags::REVERSE);
+        }
+
+        #[test]
+        fn resets()
+        {
+                assert_eq!(parse("\x1b[31;5m\x1b[0m").1, DEFAULT);
+                assert_eq!(parse("\x1b[31;5m\x1b[m").1, DEFAULT);
+                assert_eq!(parse("\x1b[32;42;1m\x1b[39;49;22m").1, DEFAULT);
+        }
+
+        #[test]
+        fn other_sequences_are_ignored()
+        {
+                assert_eq!(parse("a\x1b[2Jb\x1b[?25hc"), ("abc".to_string(), DEFAULT));
+                assert_eq!(parse("\x1bxy").0, "xy");
+        }
+}
@@ -0,0 +1,165 @@
+//! Text screen captures for rendering assertions.
+//!
+//! A [`Snapshot`] keeps the characters of the visible text cells, without
+//! their attributes, and can be compared against a golden text file where
+//! each line is a screen row. Missing rows and trailing spaces in the golden
+//! file are treated as blank cells, characters past the screen are ignored.
+use core::fmt;
+
+/// Characters displayed on a `COLS` × `ROWS` text screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot<const COLS: usize, const ROWS: usize>
+{
+        chars: [[u8; COLS]; ROWS],
+}
+
+/// Cell whose character differs from the golden file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellDiff
+{
+        pub row:      usize,
+        pub col:      usize,
+        pub expected: char,
+        pub actual:   char,
+}
+
+impl<const COLS: usize, const ROWS: usize> Snapshot<COLS, ROWS>
+{
+        /// Captures the screen from the visible cells, `cells[0]` being the
+        /// top left corner.
+        ///
+        /// Cells missing at the end of `cells` are captured as spaces.
+        pub fn capture(cells: &[u16]) -> Self
+        {
+                let mut chars = [[b' '; COLS]; ROWS];
+
+                for (i, cell) in cells.iter().take(COLS * ROWS).enumerate() {
+                        chars[i / COLS][i % COLS] = *cell as u8;
+                }
+                Self { chars }
+        }
+
+        /// Returns the character displayed at `row`/`col`.
+        pub fn char_at(
+                &self,
+                row: usize,
+                col: usize,
+        ) -> char
+        {
+                self.chars[row][col] as char
+        }
+
+        /// Returns the cells that differ from `expected`.
+        pub fn diff<'a>(
+                &'a self,
+                expected: &'a str,
+        ) -> impl Iterator<Item = CellDiff> + 'a
+        {
+                let mut lines = expected.lines();
+
+                (0..ROWS).flat_map(move |row| {
+                        let mut line = lines.next().unwrap_or("").chars();
+
+                        (0..COLS).filter_map(move |col| {
+                                let expected = line.next().unwrap_or(' ');
+                                let actual = self.char_at(row, col);
+
+                                (expected != actual).then_some(CellDiff {
+                                        row,
+                                        col,
+                                        expected,
+                                        actual,
+                                })
+                        })
+                })
+        }
+
+        /// Returns `true` if the screen matches `expected`.
+        pub fn matches(
+                &self,
+                expected: &str,
+        ) -> bool
+        {
+                self.diff(expected).next().is_none()
+        }
+}
+
+/// Writes the screen as text, one line per row.
+impl<const COLS: usize, const ROWS: usize> fmt::Display for Snapshot<COLS, ROWS>
+{
+        fn fmt(
+                &self,
+                f: &mut fmt::Formatter<'_>,
+        ) -> fmt::Result
+        {
+                for row in &self.chars {
+                        for &c in row {
+                                fmt::Write::write_char(f, c as char)?;
+                        }
+                        f.write_str("\n")?;
+                }
+                Ok(())
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        fn cells(text: &str) -> Vec<u16> { text.bytes().map(|c| 0x0700 | c as u16).collect() }
+
+        #[test]
+        fn capture_and_display()
+        {
+                let snapshot = Snapshot::<4, 2>::capture(&cells("abcde"));
+
+                assert_eq!(snapshot.char_at(0, 3), 'd');
+                assert_eq!(snapshot.char_at(1, 0), 'e');
+                assert_eq!(snapshot.char_at(1, 1), ' ');
+                assert_eq!(snapshot.to_string(), "abcd\ne   \n");
+        }
+
+        #[test]
+        fn golden_padding_is_blank()
+        {
+                let snapshot = Snapshot::<4, 3>::capture(&cells("ab      "));
+
+                assert!(snapshot.matches("ab"));
+                assert!(snapshot.matches("ab  \n\n"));
+                assert!(!snapshot.matches("ab\nc"));
+        }
+
+        #[test]
+        fn diff_reports_cells()
+        {
+                let snapshot = Snapshot::<4, 2>::capture(&cells("abcdefgh"));
+                let diff: Vec<_> = snapshot.diff("abxd\nefg").collect();
+
+                assert_eq!(
+                        diff,
+                        [
+                                CellDiff {
+                                        row:      0,
+                                        col:      2,
+                                        expected: 'x',
+                                        actual:   'c',
+                                },
+                                CellDiff {
+                                        row:      1,
+                                        col:      3,
+                                        expected: ' ',
+                                        actual:   'h',
+                                },
+                        ]
+                );
+        }
+
+        #[test]
+        fn golden_asset_matches()
+        {
+                let snapshot = Snapshot::<80, 25>::capture(&cells(&format!("{:80}Hello", "")));
+
+                assert!(snapshot.matches(include_str!("../.assets/basic_a_80_25.txt")));
+        }
+}
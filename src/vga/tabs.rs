@@ -0,0 +1,117 @@
+//! Horizontal tab stops.
+//!
+//! A tab moves the cursor to the next column holding a stop. Like on a
+//! terminal, stops are set every 8 columns by default and can be placed
+//! anywhere, one bit per column of the widest possible screen.
+
+/// Number of columns a [`TabStops`] covers, every column of a text mode.
+const COLUMNS: usize = u8::MAX as usize + 1;
+
+/// Default number of columns between tab stops.
+pub const TAB_WIDTH: u8 = 8;
+
+/// Set of columns holding a tab stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TabStops
+{
+        stops: [u64; COLUMNS / 64],
+}
+
+impl TabStops
+{
+        /// Returns a set with no stop.
+        pub const fn new() -> Self
+        {
+                Self {
+                        stops: [0; COLUMNS / 64],
+                }
+        }
+
+        /// Returns a set with a stop every `width` columns, after column 0.
+        ///
+        /// A width of 0 gives a set with no stop.
+        pub const fn every(width: u8) -> Self
+        {
+                let mut tabs = Self::new();
+                let mut col = width as usize;
+
+                while width != 0 && col < COLUMNS {
+                        tabs.stops[col / 64] |= 1 << (col % 64);
+                        col += width as usize;
+                }
+                tabs
+        }
+
+        /// Places a stop at column `col`.
+        pub fn set(
+                &mut self,
+                col: u8,
+        )
+        {
+                self.stops[col as usize / 64] |= 1 << (col % 64);
+        }
+
+        /// Removes the stop of column `col`, if any.
+        pub fn clear(
+                &mut self,
+                col: u8,
+        )
+        {
+                self.stops[col as usize / 64] &= !(1 << (col % 64));
+        }
+
+        /// Returns whether column `col` holds a stop.
+        pub fn contains(
+                &self,
+                col: u8,
+        ) -> bool
+        {
+                self.stops[col as usize / 64] & (1 << (col % 64)) != 0
+        }
+
+        /// Returns the first stop after column `col`, if any.
+        pub fn next(
+                &self,
+                col: usize,
+        ) -> Option<usize>
+        {
+                (col + 1..COLUMNS).find(|&c| self.contains(c as u8))
+        }
+}
+
+impl Default for TabStops
+{
+        fn default() -> Self { Self::every(TAB_WIDTH) }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        #[test]
+        fn default_stops_every_8_columns()
+        {
+                let tabs = TabStops::default();
+
+                assert_eq!(tabs.next(0), Some(8));
+                assert_eq!(tabs.next(8), Some(16));
+                assert_eq!(tabs.next(250), None);
+                assert!(tabs.contains(248));
+                assert!(!tabs.contains(0));
+        }
+
+        #[test]
+        fn stops_can_be_moved()
+        {
+                let mut tabs = TabStops::every(0);
+
+                assert_eq!(tabs.next(0), None);
+                tabs.set(5);
+                tabs.set(70);
+                assert_eq!(tabs.next(0), Some(5));
+                assert_eq!(tabs.next(5), Some(70));
+                tabs.clear(70);
+                assert_eq!(tabs.next(5), None);
+        }
+}
@@ -0,0 +1,635 @@
+//! Text mode cursor and scrolling arithmetic.
+//!
+//! [`TextGrid`] keeps track of where the console writes and which part of
+//! video memory is displayed, and performs the memory moves needed to scroll.
+//! It works on a plain `[u16]` slice of text cells, so the same code drives
+//! the real VGA memory and an in-memory buffer in tests.
+//!
+//! All positions are expressed in cells (one character and its attribute),
+//! relative to the start of video memory. This is also the unit used by the
+//! CRTC Start Address and Cursor Location registers.
+use core::cmp;
+
+/// Default 16-bit word for clearing VGA text mode memory.
+/// Represents a space character (0x20) with light gray foreground (0x07).
+/// Format: [15:12]=background color, [11:8]=foreground color, [7:0]=ASCII
+/// character
+pub const BLANK: u16 = 0x0720;
+
+/// Scrolling directions for VGA text mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollDir
+{
+        /// Moves the visible_origin up.
+        VisualUp,
+        /// Moves the visible_origin down.
+        VisualDown,
+        /// Moves the index down and if there isn't enough space,
+        /// moves down both the origin and visible_origin.
+        Down,
+        /// Moves the visible_origin to the vram_base.
+        Top,
+        /// Moves the visible_origin to vram_end minus screen_size.
+        Bottom,
+}
+
+/// Position of the console inside text mode video memory.
+///
+/// ```text
+/// 0 -----------------> +---------------+
+///                      |   scrollback  |
+/// vc_visible_origin -> +---------------+
+///                      |               |
+/// vc_origin ---------> +---------------+ ^
+///                      | $> uname      | |
+/// vc_index ------------|--v            | vc_rows
+///                      |               | |
+/// origin_end() ------> +---------------+ v
+///                      |<-- vc_cols -->|
+///                      .               .
+///                      +---------------+ <-- vc_vram_cells
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextGrid
+{
+        /// Total number of cells in video memory
+        vc_vram_cells:     usize,
+        /// Cell where the next character will be written
+        vc_index:          usize,
+        /// First cell displayed on screen
+        vc_visible_origin: usize,
+        /// First cell of the current screen
+        vc_origin:         usize,
+        /// Number of rows in the display
+        vc_rows:           u8,
+        /// Number of columns in the display
+        vc_cols:           u8,
+        /// Lines moved out of the current screen so far, wrapping
+        vc_scrolled:       usize,
+}
+
+impl TextGrid
+{
+        /// Creates a grid at the beginning of a video memory of `vram_cells`
+        /// cells.
+        pub const fn new(
+                vram_cells: usize,
+                cols: u8,
+                rows: u8,
+        ) -> Self
+        {
+                assert!(cols as usize * rows as usize <= vram_cells);
+
+                Self {
+                        vc_vram_cells:     vram_cells,
+                        vc_index:          0,
+                        vc_visible_origin: 0,
+                        vc_origin:         0,
+                        vc_rows:           rows,
+                        vc_cols:           cols,
+                        vc_scrolled:       0,
+                }
+        }
+
+        pub fn cols(&self) -> u8 { self.vc_cols }
+
+        pub fn rows(&self) -> u8 { self.vc_rows }
+
+        pub fn index(&self) -> usize { self.vc_index }
+
+        pub fn origin(&self) -> usize { self.vc_origin }
+
+        pub fn visible_origin(&self) -> usize { self.vc_visible_origin }
+
+        pub fn vram_cells(&self) -> usize { self.vc_vram_cells }
+
+        /// Number of cells displayed on screen.
+        pub fn screen_cells(&self) -> usize { self.vc_rows as usize * self.vc_cols as usize }
+
+        /// First cell after the current screen.
+        pub fn origin_end(&self) -> usize { self.vc_origin + self.screen_cells() }
+
+        /// Current column of the index.
+        pub fn column(&self) -> usize
+        {
+                if self.vc_index == self.origin_end() {
+                        return self.vc_cols as usize;
+                }
+                (self.vc_index - self.vc_origin) % self.vc_cols as usize
+        }
+
+        /// Current row of the index, relative to the origin.
+        pub fn row(&self) -> usize
+        {
+                cmp::min(
+                        (self.vc_index - self.vc_origin) / self.vc_cols as usize,
+                        self.vc_rows as usize - 1,
+                )
+        }
+
+        /// Number of the line of the index, counting every line written so
+        /// far. It does not change when the screen scrolls or moves in video
+        /// memory.
+        pub fn line(&self) -> usize { self.vc_scrolled.wrapping_add(self.row()) }
+
+        /// Returns the first cell of `line`, as numbered by
+        /// [`TextGrid::line`], or `None` if it is no longer on the current
+        /// screen.
+        pub fn line_start(
+                &self,
+                line: usize,
+        ) -> Option<usize>
+        {
+                let row = line.wrapping_sub(self.vc_scrolled);
+
+                (row < self.vc_rows as usize).then(|| self.vc_origin + row * self.vc_cols as usize)
+        }
+
+        /// Value for the CRTC Start Address registers.
+        pub fn start_address(&self) -> u16 { self.vc_visible_origin as u16 }
+
+        /// Value for the CRTC Cursor Location registers.
+        pub fn cursor_address(&self) -> u16 { self.vc_index as u16 }
+
+        /// Computes the position of the beginning of the line where `pos` is
+        /// located.
+        #[inline(always)]
+        pub fn start_of_line(
+                &self,
+                pos: usize,
+        ) -> usize
+        {
+                pos - (pos % self.vc_cols as usize)
+        }
+
+        /// Moves every position back to the beginning of video memory, the
+        /// lines of the screen being dropped.
+        pub fn reset(&mut self)
+        {
+                self.vc_scrolled = self.vc_scrolled.wrapping_add(self.vc_rows as usize);
+                self.vc_index = 0;
+                self.vc_origin = 0;
+                self.vc_visible_origin = 0;
+        }
+
+        /// Changes the number of cells of video memory the grid may use.
+        pub fn set_vram_cells(
+                &mut self,
+                vram_cells: usize,
+        )
+        {
+                assert!(self.origin_end() <= vram_cells);
+
+                self.vc_vram_cells = vram_cells;
+        }
+
+        /// Changes the screen dimensions and moves back to the beginning of
+        /// video memory.
+        pub fn resize(
+                &mut self,
+                cols: u8,
+                rows: u8,
+        )
+        {
+                assert!(cols as usize * rows as usize <= self.vc_vram_cells);
+
+                self.vc_cols = cols;
+                self.vc_rows = rows;
+                self.reset();
+        }
+
+        /// Changes the screen dimensions, keeping the text of the current
+        /// screen.
+        ///
+        /// The lines up to the cursor are moved to the beginning of video
+        /// memory, truncated or padded to the new width. If there are more of
+        /// them than the new number of rows, the top ones are dropped. The
+        /// rest of video memory, scrollback included, is blanked.
+        pub fn reshape(
+                &mut self,
+                mem: &mut [u16],
+                cols: u8,
+                rows: u8,
+        )
+        {
+                assert!(cols as usize * rows as usize <= self.vc_vram_cells);
+
+                let (old_cols, new_cols) = (self.vc_cols as usize, cols as usize);
+                let used = self.row() + 1;
+                let kept = cmp::min(used, rows as usize);
+                let first = self.vc_origin + (used - kept) * old_cols;
+                let column = cmp::min(self.column(), new_cols);
+
+                mem.copy_within(first..first + kept * old_cols, 0);
+
+                // Rows move towards the beginning when they shrink and towards
+                // the end when they grow, so each direction is processed from the
+                // side that cannot overwrite a row not moved yet.
+                let width = cmp::min(old_cols, new_cols);
+                let mut move_row = |row: usize| {
+                        mem.copy_within(row * old_cols..row * old_cols + width, row * new_cols);
+                        mem[row * new_cols + width..(row + 1) * new_cols].fill(BLANK);
+                };
+                if new_cols <= old_cols {
+                        (0..kept).for_each(&mut move_row);
+                } else {
+                        (0..kept).rev().for_each(&mut move_row);
+                }
+                mem[kept * new_cols..self.vc_vram_cells].fill(BLANK);
+
+                let scrolled = self.vc_scrolled.wrapping_add(used - kept);
+                self.vc_cols = cols;
+                self.vc_rows = rows;
+                self.reset();
+                self.vc_scrolled = scrolled;
+                self.vc_index = (kept - 1) * new_cols + column;
+        }
+
+        /// Moves the index to an absolute `row`/`col` of the current screen.
+        pub fn set_position(
+                &mut self,
+                col: usize,
+                row: usize,
+        )
+        {
+                let col = cmp::min(col, self.vc_cols as usize - 1);
+                let row = cmp::min(row, self.vc_rows as usize - 1);
+
+                self.vc_index = self.vc_origin + row * self.vc_cols as usize + col;
+        }
+
+        /// Writes `word` at the index and advances it, scrolling when the
+        /// screen is full.
+        pub fn put(
+                &mut self,
+                mem: &mut [u16],
+                word: u16,
+        )
+        {
+                if self.vc_index == self.origin_end() {
+                        self.scroll(mem, ScrollDir::Down, 1);
+                }
+
+                mem[self.vc_index] = word;
+                self.vc_index += 1;
+        }
+
+        /// Writes `word` at the index without advancing it, so the next
+        /// character replaces it.
+        pub fn overwrite(
+                &mut self,
+                mem: &mut [u16],
+                word: u16,
+        )
+        {
+                if self.vc_index == self.origin_end() {
+                        self.scroll(mem, ScrollDir::Down, 1);
+                }
+
+                mem[self.vc_index] = word;
+        }
+
+        /// Moves the index back to the beginning of its line.
+        ///
+        /// An index past the end of the screen is at the beginning of the
+        /// line about to scroll in, and stays there.
+        pub fn carriage_return(&mut self) { self.vc_index = self.start_of_line(self.vc_index); }
+
+        /// Moves the index one cell back, to the end of the previous line
+        /// from the beginning of a line.
+        ///
+        /// Returns `false` if the index was at the top left of the screen.
+        pub fn backspace(&mut self) -> bool
+        {
+                if self.vc_index == self.vc_origin {
+                        return false;
+                }
+                self.vc_index -= 1;
+                true
+        }
+
+        /// Moves the index to the beginning of the next line.
+        pub fn newline(
+                &mut self,
+                mem: &mut [u16],
+        )
+        {
+                self.scroll(mem, ScrollDir::Down, 1);
+        }
+
+        /// Scrolls the text buffer in the specified direction.
+        ///
+        /// `lines` is ignored by [`ScrollDir::Top`] and [`ScrollDir::Bottom`].
+        pub fn scroll(
+                &mut self,
+                mem: &mut [u16],
+                dir: ScrollDir,
+                lines: usize,
+        )
+        {
+                debug_assert!(self.vc_index <= self.origin_end());
+
+                let line = self.vc_cols as usize;
+                let delta = lines.saturating_mul(line);
+
+                match dir {
+                        ScrollDir::VisualUp => {
+                                self.vc_visible_origin =
+                                        self.vc_visible_origin.saturating_sub(delta);
+                        }
+                        ScrollDir::VisualDown => {
+                                self.vc_visible_origin = cmp::min(
+                                        self.vc_visible_origin.saturating_add(delta),
+                                        self.vc_origin,
+                                );
+                        }
+                        ScrollDir::Down => {
+                                // An index at the end of the screen is still on the last line,
+                                // its line feed is just pending.
+                                let last_line = self.origin_end() - line;
+                                let current =
+                                        cmp::min(self.start_of_line(self.vc_index), last_line);
+                                let target = current.saturating_add(delta);
+
+                                if target <= last_line {
+                                        self.vc_index = target;
+                                } else {
+                                        let overflow =
+                                                cmp::min(target - last_line, self.screen_cells());
+                                        self.shift(mem, overflow);
+                                        self.vc_index = self.origin_end() - line;
+                                }
+                                self.vc_visible_origin = self.vc_origin;
+                        }
+                        ScrollDir::Bottom => {
+                                self.vc_visible_origin = self.vc_origin;
+                        }
+                        ScrollDir::Top => {
+                                self.vc_visible_origin = 0;
+                        }
+                }
+        }
+
+        /// Moves the screen `delta` cells further in video memory and blanks
+        /// the cells uncovered at the bottom.
+        ///
+        /// If video memory has no room left after the screen, the part of the
+        /// screen that remains visible is copied back to the beginning of
+        /// video memory, dropping the scrollback.
+        fn shift(
+                &mut self,
+                mem: &mut [u16],
+                delta: usize,
+        )
+        {
+                let end = self.origin_end();
+
+                if end + delta > self.vc_vram_cells {
+                        mem.copy_within(self.vc_origin + delta..end, 0);
+                        self.vc_origin = 0;
+                } else {
+                        self.vc_origin += delta;
+                }
+
+                self.vc_scrolled = self.vc_scrolled.wrapping_add(delta / self.vc_cols as usize);
+
+                let end = self.origin_end();
+                mem[end - delta..end].fill(BLANK);
+        }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests
+{
+        use super::*;
+
+        const COLS: u8 = 4;
+        const ROWS: u8 = 3;
+
+        fn grid(vram_lines: usize) -> (TextGrid, Vec<u16>)
+        {
+                let cells = vram_lines * COLS as usize;
+                (TextGrid::new(cells, COLS, ROWS), vec![BLANK; cells])
+        }
+
+        fn puts(
+                grid: &mut TextGrid,
+                mem: &mut [u16],
+                s: &str,
+        )
+        {
+                for byte in s.bytes() {
+                        match byte {
+                                b'\n' => grid.newline(mem),
+                                _ => grid.put(mem, 0x0700 | byte as u16),
+                        }
+                }
+        }
+
+        fn screen(
+                grid: &TextGrid,
+                mem: &[u16],
+        ) -> String
+        {
+                mem[grid.visible_origin()..grid.visible_origin() + grid.screen_cells()]
+                        .chunks(COLS as usize)
+                        .map(|line| {
+                                line.iter()
+                                        .map(|&c| (c & 0xff) as u8 as char)
+                                        .collect::<String>()
+                        })
+                        .collect::<Vec<_>>()
+                        .join("|")
+        }
+
+        #[test]
+        fn put_advances_index()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "abcde");
+                assert_eq!(grid.index(), 5);
+                assert_eq!(grid.column(), 1);
+                assert_eq!(grid.row(), 1);
+                assert_eq!(grid.cursor_address(), 5);
+                assert_eq!(screen(&grid, &mem), "abcd|e   |    ");
+        }
+
+        #[test]
+        fn newline_moves_to_next_line()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "ab\nc\n");
+                assert_eq!(grid.index(), 8);
+                assert_eq!(grid.origin(), 0);
+                assert_eq!(screen(&grid, &mem), "ab  |c   |    ");
+        }
+
+        #[test]
+        fn newline_on_last_line_scrolls()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "a\nb\nc\nd");
+                assert_eq!(grid.origin(), 4);
+                assert_eq!(grid.visible_origin(), 4);
+                assert_eq!(grid.start_address(), 4);
+                assert_eq!(grid.index(), 13);
+                assert_eq!(screen(&grid, &mem), "b   |c   |d   ");
+        }
+
+        #[test]
+        fn full_line_wraps_before_scrolling()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "\n\nwxyz");
+                assert_eq!(grid.index(), grid.origin_end());
+                assert_eq!(grid.origin(), 0);
+
+                puts(&mut grid, &mut mem, "\n");
+                assert_eq!(grid.origin(), 4);
+                assert_eq!(grid.index(), 12);
+
+                puts(&mut grid, &mut mem, "1234");
+                puts(&mut grid, &mut mem, "5");
+                assert_eq!(grid.origin(), 8);
+                assert_eq!(screen(&grid, &mem), "wxyz|1234|5   ");
+        }
+
+        #[test]
+        fn carriage_return_and_backspace_move_back()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "abcde");
+                grid.carriage_return();
+                assert_eq!(grid.index(), 4);
+
+                assert!(grid.backspace());
+                grid.put(&mut mem, 0x0700 | b'x' as u16);
+                assert_eq!(screen(&grid, &mem), "abcx|e   |    ");
+
+                grid.set_position(0, 0);
+                assert!(!grid.backspace());
+                grid.overwrite(&mut mem, 0x0700 | b'y' as u16);
+                assert_eq!(grid.index(), 0);
+                assert_eq!(screen(&grid, &mem), "ybcx|e   |    ");
+        }
+
+        #[test]
+        fn scroll_wraps_to_vram_base()
+        {
+                let (mut grid, mut mem) = grid(4);
+
+                puts(&mut grid, &mut mem, "a\nb\nc\nd");
+                assert_eq!(grid.origin(), 4);
+
+                puts(&mut grid, &mut mem, "\ne");
+                assert_eq!(grid.origin(), 0);
+                assert_eq!(grid.index(), 9);
+                assert_eq!(screen(&grid, &mem), "c   |d   |e   ");
+
+                // Lines keep their numbers across the wrap.
+                assert_eq!(grid.line(), 4);
+                assert_eq!(grid.line_start(2), Some(0));
+                assert_eq!(grid.line_start(1), None);
+                assert_eq!(grid.line_start(5), None);
+        }
+
+        #[test]
+        fn scroll_down_many_lines_blanks_screen()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "abc");
+                grid.scroll(&mut mem, ScrollDir::Down, 10);
+                assert_eq!(grid.index(), grid.origin_end() - COLS as usize);
+                assert_eq!(screen(&grid, &mem), "    |    |    ");
+        }
+
+        #[test]
+        fn visual_scroll_is_bounded()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "a\nb\nc\nd\ne\nf");
+                assert_eq!(grid.origin(), 12);
+
+                grid.scroll(&mut mem, ScrollDir::VisualUp, 2);
+                assert_eq!(grid.visible_origin(), 4);
+                assert_eq!(screen(&grid, &mem), "b   |c   |d   ");
+
+                grid.scroll(&mut mem, ScrollDir::VisualUp, 5);
+                assert_eq!(grid.visible_origin(), 0);
+
+                grid.scroll(&mut mem, ScrollDir::VisualDown, 10);
+                assert_eq!(grid.visible_origin(), grid.origin());
+
+                grid.scroll(&mut mem, ScrollDir::Top, 0);
+                assert_eq!(grid.visible_origin(), 0);
+                grid.scroll(&mut mem, ScrollDir::Bottom, 0);
+                assert_eq!(grid.visible_origin(), 12);
+        }
+
+        #[test]
+        fn reshape_keeps_text()
+        {
+                let (mut grid, mut mem) = grid(8);
+
+                puts(&mut grid, &mut mem, "a\nbcd\nef");
+                grid.reshape(&mut mem, 6, 2);
+                assert_eq!(grid.index(), 8);
+                assert_eq!(grid.origin(), 0);
+                assert_eq!(
+                        mem[..12],
+                        [
+                                0x0762,
+                                0x0763,
+                                0x0764,
+                                BLANK,
+                                BLANK,
+                                BLANK,
+                                0x0765,
+                                0x0766,
+                                BLANK,
+                                BLANK,
+                                BLANK,
+                                BLANK,
+                        ]
+                );
+
+                grid.reshape(&mut mem, 2, 3);
+                assert_eq!(grid.index(), 4);
+                assert_eq!(mem[..6], [0x0762, 0x0763, 0x0765, 0x0766, BLANK, BLANK]);
+                assert!(mem[6..].iter().all(|&c| c == BLANK));
+        }
+
+        #[test]
+        fn reshape_after_scroll()
+        {
+                let (mut grid, mut mem) = grid(4);
+
+                puts(&mut grid, &mut mem, "a\nb\nc\nd\ne\nf");
+                grid.reshape(&mut mem, 5, 2);
+                assert_eq!(grid.index(), 6);
+
+                let text: String = mem[..10]
+                        .iter()
+                        .map(|&c| (c & 0xff) as u8 as char)
+                        .collect();
+                assert_eq!(text, "e    f    ");
+        }
+
+        #[test]
+        fn set_position_is_clamped()
+        {
+                let (mut grid, _) = grid(8);
+
+                grid.set_position(2, 1);
+                assert_eq!(grid.index(), 6);
+                grid.set_position(100, 100);
+                assert_eq!(grid.index(), 11);
+        }
+}